@@ -0,0 +1,59 @@
+//! Compares `run` with `run_fast` on programs that run for a while.
+//!
+//! Run with `cargo bench`.
+
+use befuddle::{BefungeExecution, BefungeField, MemoryIo};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+
+const PROGRAMS: &[(&str, &str)] = &[
+    // Counts down from 100000 on the stack.
+    (
+        "countdown",
+        "55+:*:*55+*>1-:v\n\
+         \x20          ^   _@",
+    ),
+    // Counts up to 10000 in the cell at (0, 2), reading and writing it
+    // with `g` and `p` every time round.
+    (
+        "memory",
+        ">02g1+:02p55+:*:*`#@_v\n\
+         ^                    <",
+    ),
+    // Prints "hello" 10000 times.
+    (
+        "strings",
+        "55+:*:*>\"olleh\",,,,,1-:v\n\
+         \x20      ^               _@",
+    ),
+];
+
+fn execution(program: &str) -> BefungeExecution<MemoryIo, BefungeField<i32>> {
+    BefungeExecution::builder(BefungeField::<i32>::from_source(program, 80, 25))
+        .io(MemoryIo::default())
+        .build()
+}
+
+fn engines(c: &mut Criterion) {
+    for &(name, program) in PROGRAMS {
+        let mut group = c.benchmark_group(name);
+        group.sample_size(20);
+        group.bench_function("run", |b| {
+            b.iter_batched(
+                || execution(program),
+                |mut exec| exec.run().unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+        group.bench_function("run_fast", |b| {
+            b.iter_batched(
+                || execution(program),
+                |mut exec| exec.run_fast().unwrap(),
+                BatchSize::SmallInput,
+            )
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, engines);
+criterion_main!(benches);
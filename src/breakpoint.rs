@@ -0,0 +1,69 @@
+use std::fmt;
+
+use crate::InstructionPointer;
+
+/// A condition that stops `BefungeExecution::run` before the next IP takes
+/// its turn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Breakpoint {
+    /// The IP stands on this cell.
+    Position(i32, i32),
+    /// The IP is about to execute this instruction outside string mode.
+    Instruction(i32),
+    /// The IP's stack holds more than this many values.
+    StackDepth(usize),
+    /// The top of the IP's stack equals this value. An empty stack has 0 on
+    /// top.
+    StackTop(i32),
+}
+
+impl Breakpoint {
+    /// Whether `ip` meets the condition once at `position`, with
+    /// `instruction` there.
+    pub(crate) fn matches(
+        &self,
+        ip: &InstructionPointer,
+        position: (i32, i32),
+        instruction: i32,
+    ) -> bool {
+        match *self {
+            Breakpoint::Position(x, y) => position == (x, y),
+            Breakpoint::Instruction(op) => !ip.string_mode() && instruction == op,
+            Breakpoint::StackDepth(depth) => ip.stack().len() > depth,
+            Breakpoint::StackTop(value) => ip.stacks().peek(0) == value,
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Breakpoint::Position(x, y) => write!(f, "cell ({}, {})", x, y),
+            Breakpoint::Instruction(op) => match std::char::from_u32(op as u32) {
+                Some(c) if !c.is_control() => write!(f, "instruction '{}'", c),
+                _ => write!(f, "instruction {}", op),
+            },
+            Breakpoint::StackDepth(depth) => write!(f, "stack deeper than {}", depth),
+            Breakpoint::StackTop(value) => write!(f, "stack top {}", value),
+        }
+    }
+}
+
+/// A change `p` or `s` made to a watched cell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Watch {
+    pub x: i32,
+    pub y: i32,
+    pub old: i32,
+    pub new: i32,
+}
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "cell ({}, {}) changed from {} to {}",
+            self.x, self.y, self.old, self.new
+        )
+    }
+}
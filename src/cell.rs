@@ -0,0 +1,62 @@
+use std::fmt::Debug;
+
+/// A value stored in a playfield cell.
+///
+/// Stack values are 32-bit, so `p` narrows them to fit the cell and `g`
+/// widens or truncates them back.
+pub trait FungeCell: Copy + Debug + PartialEq + 'static {
+    const SPACE: Self;
+
+    fn from_value(value: i32) -> Self;
+
+    fn to_value(self) -> i32;
+}
+
+impl FungeCell for u8 {
+    const SPACE: Self = b' ';
+
+    fn from_value(value: i32) -> Self {
+        value as u8
+    }
+
+    fn to_value(self) -> i32 {
+        self as i32
+    }
+}
+
+impl FungeCell for i32 {
+    const SPACE: Self = b' ' as i32;
+
+    fn from_value(value: i32) -> Self {
+        value
+    }
+
+    fn to_value(self) -> i32 {
+        self
+    }
+}
+
+impl FungeCell for i64 {
+    const SPACE: Self = b' ' as i64;
+
+    fn from_value(value: i32) -> Self {
+        value as i64
+    }
+
+    fn to_value(self) -> i32 {
+        self as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        assert_eq!(u8::from_value(300).to_value(), 44);
+        assert_eq!(u8::from_value(-1).to_value(), 255);
+        assert_eq!(i32::from_value(-1).to_value(), -1);
+        assert_eq!(i64::from_value(i32::MIN).to_value(), i32::MIN);
+    }
+}
@@ -0,0 +1,412 @@
+//! The static control-flow graph of a Befunge-93 program.
+//!
+//! The graph is found without running the program. Every state an IP can
+//! reach from `(0, 0)` is followed through the instructions that move it,
+//! taking both ways out of `_` and `|` and all four out of `?`. Straight
+//! runs of states become segments, which end where the path branches,
+//! jumps with `#`, stops at `@` or runs into another path. `p` is taken to
+//! change nothing, so a program that rewrites its own code may go where
+//! the graph does not.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::{BefungeCommand, Delta, FungeSpace};
+
+/// How `ControlFlowGraph` is written out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GraphFormat {
+    /// A Graphviz digraph.
+    Dot,
+    /// The segments and edges as a JSON object.
+    Json,
+}
+
+impl GraphFormat {
+    /// JSON for paths ending in `.json`, DOT for anything else.
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(extension) if extension == "json" => GraphFormat::Json,
+            _ => GraphFormat::Dot,
+        }
+    }
+}
+
+/// Where an IP is and how it is moving, just before it executes the cell
+/// it is on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize)]
+pub struct State {
+    pub x: i32,
+    pub y: i32,
+    pub delta: Delta,
+    pub string_mode: bool,
+}
+
+/// Why control passes from one segment to another.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    /// The path carries on into a segment other paths also reach.
+    Next,
+    /// `#` skips the next cell.
+    Jump,
+    /// `_` or `|` popped zero.
+    Zero,
+    /// `_` or `|` popped anything else.
+    NonZero,
+    /// `?` picked the direction the next segment starts in.
+    Random,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// A run of states that always execute one after the other.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Segment {
+    pub start: State,
+    /// The cells the segment passes through, in order.
+    pub cells: Vec<(i32, i32)>,
+    /// The instructions it passes through, leaving out spaces outside
+    /// strings.
+    pub code: String,
+}
+
+/// The segments of a program, the first of which starts at `(0, 0)`, and
+/// the edges between them.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ControlFlowGraph {
+    pub segments: Vec<Segment>,
+    pub edges: Vec<Edge>,
+}
+
+/// Every state an IP can reach from `(0, 0)`, in the order they were
+/// found, and how they lead into each other.
+#[derive(Debug)]
+pub(crate) struct StateGraph {
+    pub(crate) states: Vec<State>,
+    /// For each state, the states it goes on to, and why.
+    pub(crate) exits: Vec<Vec<(usize, EdgeKind)>>,
+    /// For each state, how many ways lead into it.
+    pub(crate) entries: Vec<usize>,
+}
+
+impl StateGraph {
+    pub(crate) fn new<F: FungeSpace + ?Sized>(field: &F) -> Self {
+        let start = State {
+            x: 0,
+            y: 0,
+            delta: Delta::RIGHT,
+            string_mode: false,
+        };
+        let mut states = vec![start];
+        let mut indices = HashMap::new();
+        indices.insert(start, 0);
+        let mut exits = Vec::new();
+        let mut entries = vec![0];
+        while exits.len() < states.len() {
+            let mut exit = Vec::new();
+            for (next, kind) in successors(field, states[exits.len()]) {
+                let index = *indices.entry(next).or_insert_with(|| {
+                    states.push(next);
+                    entries.push(0);
+                    states.len() - 1
+                });
+                entries[index] += 1;
+                exit.push((index, kind));
+            }
+            exits.push(exit);
+        }
+
+        Self {
+            states,
+            exits,
+            entries,
+        }
+    }
+}
+
+/// The states an IP can go on to from `state`, and why.
+fn successors<F: FungeSpace + ?Sized>(field: &F, state: State) -> Vec<(State, EdgeKind)> {
+    let value = field.cell(state.x, state.y);
+    let to = |delta: Delta, distance: i64, string_mode: bool| {
+        let (x, y) = field.advance(state.x, state.y, delta, distance);
+        State {
+            x,
+            y,
+            delta,
+            string_mode,
+        }
+    };
+    let next = |delta: Delta| vec![(to(delta, 1, false), EdgeKind::Next)];
+
+    if state.string_mode {
+        let string_mode = value != BefungeCommand::TOGGLE_STRING_MODE as i32;
+        return vec![(to(state.delta, 1, string_mode), EdgeKind::Next)];
+    }
+    match u8::try_from(value) {
+        Ok(BefungeCommand::TOGGLE_STRING_MODE) => vec![(to(state.delta, 1, true), EdgeKind::Next)],
+        Ok(BefungeCommand::RIGHT) => next(Delta::RIGHT),
+        Ok(BefungeCommand::LEFT) => next(Delta::LEFT),
+        Ok(BefungeCommand::UP) => next(Delta::UP),
+        Ok(BefungeCommand::DOWN) => next(Delta::DOWN),
+        Ok(BefungeCommand::BRIDGE) => vec![(to(state.delta, 2, false), EdgeKind::Jump)],
+        Ok(BefungeCommand::IF_LEFT_RIGHT) => vec![
+            (to(Delta::LEFT, 1, false), EdgeKind::NonZero),
+            (to(Delta::RIGHT, 1, false), EdgeKind::Zero),
+        ],
+        Ok(BefungeCommand::IF_UP_DOWN) => vec![
+            (to(Delta::UP, 1, false), EdgeKind::NonZero),
+            (to(Delta::DOWN, 1, false), EdgeKind::Zero),
+        ],
+        Ok(BefungeCommand::RANDOM) => Delta::ALL
+            .iter()
+            .map(|&delta| (to(delta, 1, false), EdgeKind::Random))
+            .collect(),
+        Ok(BefungeCommand::STOP) => Vec::new(),
+        _ => next(state.delta),
+    }
+}
+
+impl ControlFlowGraph {
+    /// The graph of the Befunge-93 program in `field`.
+    pub fn new<F: FungeSpace + ?Sized>(field: &F) -> Self {
+        let StateGraph {
+            states,
+            exits,
+            entries,
+        } = StateGraph::new(field);
+
+        // A segment starts at the start, wherever paths meet, and after
+        // anything but a plain step.
+        let mut leaders = vec![false; states.len()];
+        leaders[0] = true;
+        for (index, exit) in exits.iter().enumerate() {
+            if entries[index] != 1 {
+                leaders[index] = true;
+            }
+            for &(next, kind) in exit {
+                if kind != EdgeKind::Next || exit.len() > 1 {
+                    leaders[next] = true;
+                }
+            }
+        }
+        let mut segment_of = vec![usize::MAX; states.len()];
+        let mut count = 0;
+        for (index, &leader) in leaders.iter().enumerate() {
+            if leader {
+                segment_of[index] = count;
+                count += 1;
+            }
+        }
+
+        let mut segments = Vec::with_capacity(count);
+        let mut edges = Vec::new();
+        for (first, _) in leaders.iter().enumerate().filter(|(_, &leader)| leader) {
+            let mut segment = Segment {
+                start: states[first],
+                cells: Vec::new(),
+                code: String::new(),
+            };
+            let mut index = first;
+            loop {
+                let state = states[index];
+                segment.cells.push((state.x, state.y));
+                let value = field.cell(state.x, state.y);
+                if state.string_mode || value != BefungeCommand::NO_OP as i32 {
+                    segment.code.push(
+                        u32::try_from(value)
+                            .ok()
+                            .and_then(std::char::from_u32)
+                            .unwrap_or(std::char::REPLACEMENT_CHARACTER),
+                    );
+                }
+
+                match exits[index][..] {
+                    [(next, EdgeKind::Next)] if !leaders[next] => index = next,
+                    ref exit => {
+                        edges.extend(exit.iter().map(|&(next, kind)| Edge {
+                            from: segments.len(),
+                            to: segment_of[next],
+                            kind,
+                        }));
+                        break;
+                    }
+                }
+            }
+            segments.push(segment);
+        }
+
+        Self { segments, edges }
+    }
+
+    /// Writes the graph as a Graphviz digraph, one box per segment. Boxes
+    /// drawn twice round are where the program stops.
+    pub fn write_dot<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "digraph befunge {{")?;
+        writeln!(writer, "    node [shape=box, fontname=\"monospace\"];")?;
+        for (index, segment) in self.segments.iter().enumerate() {
+            let start = segment.start;
+            let label = format!(
+                "({}, {}) {}{}\n{}\n",
+                start.x,
+                start.y,
+                start.delta.name().unwrap_or("?"),
+                if start.string_mode { " in string" } else { "" },
+                segment.code
+            );
+            let stops = !self.edges.iter().any(|edge| edge.from == index);
+            writeln!(
+                writer,
+                "    s{} [label={}{}];",
+                index,
+                quote(&label),
+                if stops { ", peripheries=2" } else { "" }
+            )?;
+        }
+        for edge in &self.edges {
+            let label = match edge.kind {
+                EdgeKind::Next => "",
+                EdgeKind::Jump => "#",
+                EdgeKind::Zero => "0",
+                EdgeKind::NonZero => "not 0",
+                EdgeKind::Random => "?",
+            };
+            write!(writer, "    s{} -> s{}", edge.from, edge.to)?;
+            if label.is_empty() {
+                writeln!(writer, ";")?;
+            } else {
+                writeln!(writer, " [label={}];", quote(label))?;
+            }
+        }
+        writeln!(writer, "}}")
+    }
+
+    /// Writes the graph as pretty-printed JSON.
+    pub fn write_json<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writeln!(writer)
+    }
+
+    pub fn write<W: Write>(&self, writer: W, format: GraphFormat) -> io::Result<()> {
+        match format {
+            GraphFormat::Dot => self.write_dot(writer),
+            GraphFormat::Json => self.write_json(writer),
+        }
+    }
+}
+
+/// `text` as a quoted DOT string, with each line ending in a newline
+/// left-aligned.
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\l"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BefungeField;
+
+    fn graph_of(source: &str) -> ControlFlowGraph {
+        ControlFlowGraph::new(&BefungeField::<u8>::from_source(source, 80, 25))
+    }
+
+    fn edge_of(from: usize, to: usize, kind: EdgeKind) -> Edge {
+        Edge { from, to, kind }
+    }
+
+    fn code(graph: &ControlFlowGraph) -> Vec<&str> {
+        graph.segments.iter().map(|s| s.code.as_str()).collect()
+    }
+
+    #[test]
+    fn test_branches() {
+        let graph = graph_of("\"!dlroW ,olleH\">:#,_@");
+        // The `:` after the string starts a segment of its own because the
+        // loop back through `>` joins the path there.
+        assert_eq!(
+            code(&graph),
+            vec!["\"!dlroW ,olleH\">", ":#", "_", ",#", "@", ">"]
+        );
+        assert_eq!(graph.segments[3].start.delta, Delta::LEFT);
+        assert_eq!(graph.segments[3].cells, vec![(18, 0), (17, 0)]);
+        assert_eq!(
+            graph.edges,
+            vec![
+                edge_of(0, 1, EdgeKind::Next),
+                edge_of(1, 2, EdgeKind::Jump),
+                edge_of(2, 3, EdgeKind::NonZero),
+                edge_of(2, 4, EdgeKind::Zero),
+                edge_of(3, 5, EdgeKind::Jump),
+                edge_of(5, 1, EdgeKind::Next),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_random_and_loops() {
+        // `?` leads to `@` going either way along its row, and back round
+        // to itself going either way along its column.
+        let graph = graph_of("?@");
+        assert_eq!(code(&graph), vec!["?", "@", "@", "?", "?"]);
+        assert_eq!(graph.segments[2].cells.len(), 79);
+        assert_eq!(graph.segments[3].cells.len(), 25);
+        assert_eq!(graph.edges.len(), 12);
+        assert!(graph.edges[8..]
+            .iter()
+            .zip(1..)
+            .all(|(edge, to)| *edge == edge_of(4, to, EdgeKind::Random)));
+
+        // A loop with no way out leads back to its own start.
+        let graph = graph_of(">v\n^<");
+        assert_eq!(code(&graph), vec![">", "v<^>"]);
+        assert_eq!(
+            graph.edges,
+            vec![edge_of(0, 1, EdgeKind::Next), edge_of(1, 1, EdgeKind::Next)]
+        );
+    }
+
+    #[test]
+    fn test_write() {
+        let graph = graph_of("\"a\\\"_@");
+        let mut dot = Vec::new();
+        graph.write(&mut dot, GraphFormat::Dot).unwrap();
+        let dot = String::from_utf8(dot).unwrap();
+        assert!(dot.starts_with("digraph befunge {\n"));
+        assert!(dot.contains("    s0 [label=\"(0, 0) right\\l\\\"a\\\\\\\"_\\l\"];\n"));
+        assert!(dot.contains("[label=\"(5, 0) right\\l@\\l\", peripheries=2];\n"));
+        assert!(dot.contains("    s0 -> s2 [label=\"0\"];\n"));
+
+        let mut json = Vec::new();
+        graph.write(&mut json, GraphFormat::Json).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+        assert_eq!(json["segments"][0]["code"], "\"a\\\"_");
+        assert_eq!(json["edges"][0]["kind"], "non_zero");
+        assert_eq!(
+            GraphFormat::for_path(Path::new("cfg.json")),
+            GraphFormat::Json
+        );
+        assert_eq!(
+            GraphFormat::for_path(Path::new("cfg.dot")),
+            GraphFormat::Dot
+        );
+    }
+}
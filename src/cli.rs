@@ -0,0 +1,910 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use structopt::clap::AppSettings;
+use structopt::StructOpt;
+
+use crate::cfg::{ControlFlowGraph, GraphFormat};
+use crate::compile::{self, CompileSettings, Compiled};
+use crate::io::StreamIo;
+use crate::lint::{self, Lint};
+use crate::{
+    BefungeField, CoverageSummary, Dialect, FungeCell, FungeSpace, Limits, LoadError, LoadOptions,
+    LoadReport, Snapshot, SnapshotError, SnapshotFormat, SparseSpace, TraceFormat, TraceWriter,
+};
+
+/// `FungeIo` opened from the command line options.
+pub type CliIo = StreamIo<Box<dyn BufRead>, Box<dyn Write>>;
+
+/// The playfield cell type selected with `--cells`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CellType {
+    U8,
+    I32,
+    I64,
+}
+
+impl FromStr for CellType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "u8" => Ok(CellType::U8),
+            "i32" => Ok(CellType::I32),
+            "i64" => Ok(CellType::I64),
+            _ => Err(format!("unknown cell type `{}`", s)),
+        }
+    }
+}
+
+/// The full command line: either a program to run, or a subcommand.
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "befuddle",
+    about = "A Befunge interpreter",
+    setting = AppSettings::SubcommandsNegateReqs
+)]
+pub struct CliOptions {
+    #[structopt(flatten)]
+    pub exec: ExecOptions,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Step through a program in an interactive debugger
+    Debug(ExecOptions),
+    /// Run a program on several inputs and list the cells none of them
+    /// reached
+    Coverage(CoverageOptions),
+    /// Run programs and compare their output with what they should print
+    Test(TestOptions),
+    /// Compile a Befunge-93 program into a standalone Rust program
+    Compile(CompileOptions),
+    /// Draw the control flow of a Befunge-93 program as a Graphviz graph
+    Cfg(CfgOptions),
+    /// Warn about likely mistakes in a Befunge-93 program without running
+    /// it
+    Lint(LintOptions),
+}
+
+/// The size of the Befunge-93 playfield, and how a program is fitted into
+/// it.
+#[derive(Debug, StructOpt)]
+pub struct FieldOptions {
+    /// Width of the Befunge-93 playfield
+    #[structopt(long, default_value = "80", parse(try_from_str = parse_dimension))]
+    pub width: usize,
+
+    /// Height of the Befunge-93 playfield
+    #[structopt(long, default_value = "25", parse(try_from_str = parse_dimension))]
+    pub height: usize,
+
+    /// Refuse a Befunge-93 program that does not fit the playfield, instead
+    /// of warning about what is cut off
+    #[structopt(long)]
+    pub strict: bool,
+
+    /// Grow the Befunge-93 playfield to fit the program. `--width` and
+    /// `--height` are the least it can be
+    #[structopt(long)]
+    pub auto_size: bool,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct LintOptions {
+    /// Width of the Befunge-93 playfield
+    #[structopt(long, default_value = "80", parse(try_from_str = parse_dimension))]
+    pub width: usize,
+
+    /// Height of the Befunge-93 playfield
+    #[structopt(long, default_value = "25", parse(try_from_str = parse_dimension))]
+    pub height: usize,
+
+    /// Program to check, or `-` to read it from standard input
+    #[structopt(parse(from_os_str))]
+    pub program: PathBuf,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CfgOptions {
+    /// File to write the graph to instead of standard output: JSON if the
+    /// name ends in `.json`, DOT otherwise
+    #[structopt(short, long = "output", parse(from_os_str))]
+    pub output_file: Option<PathBuf>,
+
+    /// Write JSON instead of DOT
+    #[structopt(long)]
+    pub json: bool,
+
+    #[structopt(flatten)]
+    pub field: FieldOptions,
+
+    /// Program to graph, or `-` to read it from standard input
+    #[structopt(parse(from_os_str))]
+    pub program: PathBuf,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CompileOptions {
+    /// File to write the Rust program to instead of standard output
+    #[structopt(short, long = "output", parse(from_os_str))]
+    pub output_file: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    pub field: FieldOptions,
+
+    /// Type of the playfield cells: `u8`, `i32` or `i64`
+    #[structopt(long, default_value = "u8", possible_values = &["u8", "i32", "i64"])]
+    pub cells: CellType,
+
+    /// Seed for the `?` instruction, built into the program. Without it,
+    /// every run of the program picks its own
+    #[structopt(long)]
+    pub seed: Option<u64>,
+
+    /// Program to compile, or `-` to read it from standard input
+    #[structopt(parse(from_os_str))]
+    pub program: PathBuf,
+}
+
+#[derive(Debug, StructOpt)]
+pub struct CoverageOptions {
+    /// Input for one run of the program. Give it once per run; without
+    /// it, the program runs once on `--input`, or on no input at all
+    #[structopt(long = "run", parse(from_os_str), number_of_values = 1)]
+    pub runs: Vec<PathBuf>,
+
+    /// Also write the coverage of every row as JSON to this file
+    #[structopt(long, parse(from_os_str))]
+    pub summary: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    pub exec: ExecOptions,
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "befuddle", about = "A Befunge interpreter")]
+pub struct ExecOptions {
+    /// File read by `&` and `~` instead of standard input
+    #[structopt(short, long = "input", parse(from_os_str))]
+    pub input_file: Option<PathBuf>,
+
+    /// File written by `.` and `,` instead of standard output
+    #[structopt(short, long = "output", parse(from_os_str))]
+    pub output_file: Option<PathBuf>,
+
+    #[structopt(flatten)]
+    pub field: FieldOptions,
+
+    /// Type of the playfield cells: `u8`, `i32` or `i64`. Defaults to `u8`
+    /// for Befunge-93 and `i32` for Funge-98
+    #[structopt(long, possible_values = &["u8", "i32", "i64"])]
+    pub cells: Option<CellType>,
+
+    /// Seed for the `?` instruction, for reproducible runs
+    #[structopt(long)]
+    pub seed: Option<u64>,
+
+    /// Stop after executing this many instructions
+    #[structopt(long)]
+    pub max_steps: Option<u64>,
+
+    /// Stop after running for this many seconds
+    #[structopt(long, parse(try_from_str = parse_seconds))]
+    pub timeout: Option<Duration>,
+
+    /// Stop when an IP holds more than this many values on its stacks
+    #[structopt(long)]
+    pub max_stack: Option<usize>,
+
+    /// Use the operand order and truth rules of earlier befuddle releases
+    #[structopt(long, conflicts_with = "funge98")]
+    pub legacy: bool,
+
+    /// Run the program as Funge-98 instead of Befunge-93
+    #[structopt(long = "98", visible_alias = "funge98")]
+    pub funge98: bool,
+
+    /// Count the cells and instructions run, and print a report and a
+    /// heatmap to standard error at the end
+    #[structopt(long)]
+    pub profile: bool,
+
+    /// Run Befunge-93 programs with an engine that decodes them ahead,
+    /// which is faster for long runs. It steps aside for `--trace` and
+    /// `--profile`
+    #[structopt(long)]
+    pub fast: bool,
+
+    /// Write every step executed to this file: JSON Lines if the name ends
+    /// in `.jsonl`, text otherwise
+    #[structopt(long, parse(from_os_str))]
+    pub trace: Option<PathBuf>,
+
+    /// Save the state of the execution here when it stops: JSON if the name
+    /// ends in `.json`, binary otherwise
+    #[structopt(long, parse(from_os_str))]
+    pub snapshot: Option<PathBuf>,
+
+    /// Carry on from a saved snapshot instead of starting a program. Input
+    /// it had already read is skipped
+    #[structopt(long, parse(from_os_str), conflicts_with = "program")]
+    pub resume: Option<PathBuf>,
+
+    /// Program to run, or `-` to read it from standard input
+    #[structopt(parse(from_os_str), required_unless = "resume")]
+    pub program: Option<PathBuf>,
+}
+
+fn parse_dimension(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(0) => Err(String::from("must be greater than zero")),
+        Ok(n) => Ok(n),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    match s.parse::<f64>() {
+        Ok(secs) if secs.is_finite() && secs >= 0.0 => Ok(Duration::from_secs_f64(secs)),
+        Ok(_) => Err(String::from("must be a non-negative number of seconds")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+fn load_cells<C: FungeCell>(
+    dialect: Dialect,
+    source: &str,
+    width: usize,
+    height: usize,
+    options: LoadOptions,
+) -> Result<(Box<dyn FungeSpace>, LoadReport), LoadError> {
+    match dialect {
+        // Funge-Space has room for everything.
+        Dialect::Funge98 => Ok((
+            Box::new(SparseSpace::<C>::from_source(source)),
+            LoadReport::default(),
+        )),
+        _ => {
+            let (field, report) = BefungeField::<C>::load(source, width, height, options)?;
+            Ok((Box::new(field), report))
+        }
+    }
+}
+
+fn is_stdin(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+fn with_path(path: &Path, e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("{}: {}", path.display(), e))
+}
+
+/// Reads the program at `program`, or from standard input for `-`.
+pub fn read_program(program: &Path) -> io::Result<String> {
+    if is_stdin(program) {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source)?;
+        Ok(source)
+    } else {
+        fs::read_to_string(program).map_err(|e| with_path(program, e))
+    }
+}
+
+#[derive(Debug, StructOpt)]
+pub struct TestOptions {
+    /// Fail a program after it executes this many instructions
+    #[structopt(long, default_value = "10000000")]
+    pub max_steps: u64,
+
+    /// Fail a program after it runs for this many seconds
+    #[structopt(long, default_value = "10", parse(try_from_str = parse_seconds))]
+    pub timeout: Duration,
+
+    /// Programs to test, or directories to search for `.bf` and `.b98`
+    /// programs
+    #[structopt(parse(from_os_str), default_value = ".")]
+    pub paths: Vec<PathBuf>,
+}
+
+impl TestOptions {
+    pub fn limits(&self) -> Limits {
+        Limits::default().steps(self.max_steps).time(self.timeout)
+    }
+}
+
+impl FieldOptions {
+    /// How `--strict` and `--auto-size` fit the program into the field.
+    pub fn load_options(&self) -> LoadOptions {
+        LoadOptions::default()
+            .strict(self.strict)
+            .auto_size(self.auto_size)
+    }
+
+    /// Loads `source` into a Befunge-93 field of the size given, along
+    /// with what did not fit.
+    pub fn load<C: FungeCell>(
+        &self,
+        source: &str,
+    ) -> Result<(BefungeField<C>, LoadReport), LoadError> {
+        BefungeField::load(source, self.width, self.height, self.load_options())
+    }
+}
+
+impl LintOptions {
+    pub fn lint(&self, source: &str) -> Vec<Lint> {
+        lint::lint(source, self.width, self.height)
+    }
+}
+
+impl CfgOptions {
+    /// The graph of `source`, along with what did not fit the field.
+    pub fn graph(&self, source: &str) -> Result<(ControlFlowGraph, LoadReport), LoadError> {
+        let (field, report) = self.field.load::<u8>(source)?;
+        Ok((ControlFlowGraph::new(&field), report))
+    }
+
+    pub fn format(&self) -> GraphFormat {
+        match &self.output_file {
+            _ if self.json => GraphFormat::Json,
+            Some(path) => GraphFormat::for_path(path),
+            None => GraphFormat::Dot,
+        }
+    }
+
+    /// Writes `graph` where `--output` asks, or to standard output.
+    pub fn write_graph(&self, graph: &ControlFlowGraph) -> io::Result<()> {
+        match &self.output_file {
+            Some(path) => {
+                let file = File::create(path).map_err(|e| with_path(path, e))?;
+                let mut writer = BufWriter::new(file);
+                graph.write(&mut writer, self.format())?;
+                writer.flush()
+            }
+            None => graph.write(io::stdout().lock(), self.format()),
+        }
+    }
+}
+
+impl CompileOptions {
+    /// Compiles `source` with the field size, cell type and seed given,
+    /// reporting what did not fit the field.
+    pub fn compile(&self, source: &str) -> Result<(Compiled, LoadReport), LoadError> {
+        let name = match self.program.file_stem() {
+            Some(stem) if !is_stdin(&self.program) => stem.to_string_lossy().into_owned(),
+            _ => String::from("befuddle"),
+        };
+        let settings = CompileSettings {
+            name,
+            seed: self.seed,
+        };
+        match self.cells {
+            CellType::U8 => self.compile_cells::<u8>(source, &settings),
+            CellType::I32 => self.compile_cells::<i32>(source, &settings),
+            CellType::I64 => self.compile_cells::<i64>(source, &settings),
+        }
+    }
+
+    fn compile_cells<C: FungeCell>(
+        &self,
+        source: &str,
+        settings: &CompileSettings,
+    ) -> Result<(Compiled, LoadReport), LoadError> {
+        let (field, report) = self.field.load::<C>(source)?;
+        Ok((compile::compile(&field, settings), report))
+    }
+
+    /// Writes `compiled` where `--output` asks, or to standard output.
+    pub fn write_program(&self, compiled: &Compiled) -> io::Result<()> {
+        match &self.output_file {
+            Some(path) => fs::write(path, &compiled.source).map_err(|e| with_path(path, e)),
+            None => io::stdout().lock().write_all(compiled.source.as_bytes()),
+        }
+    }
+}
+
+impl CoverageOptions {
+    /// The name and input of each run, in order.
+    pub fn read_runs(&self) -> io::Result<Vec<(String, Vec<u8>)>> {
+        let paths = match (&self.runs[..], &self.exec.input_file) {
+            ([], Some(path)) => vec![path],
+            ([], None) => return Ok(vec![(String::from("no input"), Vec::new())]),
+            (runs, _) => runs.iter().collect(),
+        };
+        paths
+            .into_iter()
+            .map(|path| {
+                let input = fs::read(path).map_err(|e| with_path(path, e))?;
+                Ok((path.display().to_string(), input))
+            })
+            .collect()
+    }
+
+    /// Saves `summary` where `--summary` asks, if it does.
+    pub fn write_summary(&self, summary: &CoverageSummary) -> io::Result<()> {
+        match &self.summary {
+            Some(path) => {
+                let file = File::create(path).map_err(|e| with_path(path, e))?;
+                let mut writer = BufWriter::new(file);
+                serde_json::to_writer_pretty(&mut writer, summary)?;
+                writeln!(writer)?;
+                writer.flush()
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+impl ExecOptions {
+    /// The program path. Only missing when resuming a snapshot or when a
+    /// subcommand was given instead.
+    pub fn program(&self) -> &Path {
+        self.program.as_deref().unwrap_or_else(|| Path::new("-"))
+    }
+
+    pub fn limits(&self) -> Limits {
+        Limits {
+            steps: self.max_steps,
+            time: self.timeout,
+            stack: self.max_stack,
+        }
+    }
+
+    /// Loads `source` into the space the dialect and `--cells` call for,
+    /// along with what did not fit.
+    pub fn load_space(&self, source: &str) -> Result<(Box<dyn FungeSpace>, LoadReport), LoadError> {
+        self.space(
+            self.dialect(),
+            source,
+            self.field.width,
+            self.field.height,
+            self.field.load_options(),
+        )
+    }
+
+    /// The snapshot given with `--resume`, if any.
+    pub fn read_resume(&self) -> Result<Option<Snapshot>, SnapshotError> {
+        match &self.resume {
+            Some(path) => {
+                let file = File::open(path).map_err(|e| with_path(path, e))?;
+                Snapshot::read(BufReader::new(file)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// An empty space of the kind `snapshot` was taken from, to restore it
+    /// into. `--cells` still picks the cell type.
+    pub fn resume_space(&self, snapshot: &Snapshot) -> Box<dyn FungeSpace> {
+        let bounds = snapshot.bounds;
+        let (space, _) = self
+            .space(
+                snapshot.dialect,
+                "",
+                bounds.max_x.max(0) as usize + 1,
+                bounds.max_y.max(0) as usize + 1,
+                LoadOptions::default(),
+            )
+            .expect("an empty program fits any field");
+        space
+    }
+
+    /// Saves `snapshot` where `--snapshot` asks, if it does.
+    pub fn write_snapshot(&self, snapshot: &Snapshot) -> Result<(), SnapshotError> {
+        match &self.snapshot {
+            Some(path) => {
+                let file = File::create(path).map_err(|e| with_path(path, e))?;
+                snapshot.write(BufWriter::new(file), SnapshotFormat::for_path(path))
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn space(
+        &self,
+        dialect: Dialect,
+        source: &str,
+        width: usize,
+        height: usize,
+        options: LoadOptions,
+    ) -> Result<(Box<dyn FungeSpace>, LoadReport), LoadError> {
+        match self.cell_type_for(dialect) {
+            CellType::U8 => load_cells::<u8>(dialect, source, width, height, options),
+            CellType::I32 => load_cells::<i32>(dialect, source, width, height, options),
+            CellType::I64 => load_cells::<i64>(dialect, source, width, height, options),
+        }
+    }
+
+    /// Whether `.` and `,` write to the terminal rather than a file.
+    pub fn writes_stdout(&self) -> bool {
+        self.output_file.is_none()
+    }
+
+    pub fn dialect(&self) -> Dialect {
+        if self.legacy {
+            Dialect::Legacy
+        } else if self.funge98 {
+            Dialect::Funge98
+        } else {
+            Dialect::Befunge93
+        }
+    }
+
+    pub fn cell_type(&self) -> CellType {
+        self.cell_type_for(self.dialect())
+    }
+
+    fn cell_type_for(&self, dialect: Dialect) -> CellType {
+        match (self.cells, dialect) {
+            (Some(cells), _) => cells,
+            (None, Dialect::Funge98) => CellType::I32,
+            (None, _) => CellType::U8,
+        }
+    }
+
+    /// The tracer `--trace` asks for, if it does. The file is started
+    /// afresh unless `append`, for executions that carry on a trace an
+    /// earlier one began.
+    pub fn open_trace(&self, append: bool) -> io::Result<Option<TraceWriter<BufWriter<File>>>> {
+        match &self.trace {
+            Some(path) => {
+                let file = if append {
+                    OpenOptions::new().append(true).create(true).open(path)
+                } else {
+                    File::create(path)
+                }
+                .map_err(|e| with_path(path, e))?;
+                Ok(Some(TraceWriter::new(
+                    BufWriter::new(file),
+                    TraceFormat::for_path(path),
+                )))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn open_io(&self) -> io::Result<CliIo> {
+        let reader: Box<dyn BufRead> = match &self.input_file {
+            Some(path) => Box::new(BufReader::new(
+                File::open(path).map_err(|e| with_path(path, e))?,
+            )),
+            None => Box::new(BufReader::new(io::stdin())),
+        };
+        let writer: Box<dyn Write> = match &self.output_file {
+            Some(path) => Box::new(BufWriter::new(
+                File::create(path).map_err(|e| with_path(path, e))?,
+            )),
+            None => Box::new(io::stdout()),
+        };
+
+        Ok(StreamIo::new(reader, writer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LoadIssue;
+
+    #[test]
+    fn test_defaults() {
+        let options = ExecOptions::from_iter_safe(&["befuddle", "prog.bf"]).unwrap();
+        assert_eq!(options.program(), Path::new("prog.bf"));
+        assert_eq!(options.field.width, 80);
+        assert_eq!(options.field.height, 25);
+        assert!(options.input_file.is_none());
+        assert!(options.seed.is_none());
+        assert!(!options.legacy);
+        assert!(!options.profile);
+        assert!(!options.fast);
+        assert!(options.writes_stdout());
+        assert_eq!(options.field.load_options(), LoadOptions::default());
+    }
+
+    #[test]
+    fn test_load_options() {
+        let options =
+            ExecOptions::from_iter_safe(&["befuddle", "--strict", "--auto-size", "prog.bf"])
+                .unwrap();
+        let load = options.field.load_options();
+        assert!(load.strict && load.auto_size);
+
+        let options =
+            ExecOptions::from_iter_safe(&["befuddle", "--strict", "--height", "2", "prog.bf"])
+                .unwrap();
+        let error = options.load_space("@\n\n1").err().unwrap();
+        assert_eq!(
+            error.report.issues,
+            vec![LoadIssue::DroppedLine { y: 2, height: 2 }]
+        );
+
+        // Funge-Space never leaves anything out.
+        let options = ExecOptions::from_iter_safe(&[
+            "befuddle", "--98", "--strict", "--height", "2", "prog.bf",
+        ])
+        .unwrap();
+        assert!(options.load_space("@\n\n1").unwrap().1.is_clean());
+    }
+
+    #[test]
+    fn test_redirection() {
+        let options = ExecOptions::from_iter_safe(&[
+            "befuddle",
+            "-i",
+            "in.txt",
+            "--output",
+            "out.txt",
+            "--width",
+            "10",
+            "--seed",
+            "7",
+            "--profile",
+            "--fast",
+            "-",
+        ])
+        .unwrap();
+        assert_eq!(options.input_file, Some(PathBuf::from("in.txt")));
+        assert_eq!(options.output_file, Some(PathBuf::from("out.txt")));
+        assert_eq!(options.field.width, 10);
+        assert_eq!(options.seed, Some(7));
+        assert!(options.profile);
+        assert!(options.fast);
+        assert!(is_stdin(options.program()));
+    }
+
+    #[test]
+    fn test_limits() {
+        let options = ExecOptions::from_iter_safe(&[
+            "befuddle",
+            "--max-steps",
+            "100",
+            "--timeout",
+            "1.5",
+            "--max-stack",
+            "64",
+            "prog.bf",
+        ])
+        .unwrap();
+        assert_eq!(
+            options.limits(),
+            Limits::default()
+                .steps(100)
+                .time(Duration::from_millis(1500))
+                .stack(64)
+        );
+
+        let options = ExecOptions::from_iter_safe(&["befuddle", "prog.bf"]).unwrap();
+        assert_eq!(options.limits(), Limits::default());
+        assert!(ExecOptions::from_iter_safe(&["befuddle", "--timeout", "-1", "prog.bf"]).is_err());
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let options =
+            ExecOptions::from_iter_safe(&["befuddle", "--snapshot", "state.json", "prog.bf"])
+                .unwrap();
+        assert_eq!(options.snapshot, Some(PathBuf::from("state.json")));
+
+        let options = ExecOptions::from_iter_safe(&["befuddle", "--resume", "state.json"]).unwrap();
+        assert_eq!(options.resume, Some(PathBuf::from("state.json")));
+        assert!(options.program.is_none());
+
+        assert!(ExecOptions::from_iter_safe(&["befuddle", "--resume", "a", "prog.bf"]).is_err());
+        assert!(options.trace.is_none());
+
+        let options =
+            ExecOptions::from_iter_safe(&["befuddle", "--trace", "run.jsonl", "prog.bf"]).unwrap();
+        assert_eq!(options.trace, Some(PathBuf::from("run.jsonl")));
+        assert!(ExecOptions::from_iter_safe(&["befuddle"]).is_err());
+    }
+
+    #[test]
+    fn test_open_trace() {
+        let path = std::env::temp_dir().join(format!("befuddle-trace-{}.txt", std::process::id()));
+        let options = ExecOptions::from_iter_safe(&[
+            "befuddle",
+            "--trace",
+            path.to_str().unwrap(),
+            "prog.bf",
+        ])
+        .unwrap();
+        fs::write(&path, "earlier\n").unwrap();
+
+        // Carrying on a trace keeps what is there; starting one does not.
+        drop(options.open_trace(true).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "earlier\n");
+        drop(options.open_trace(false).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_dialect() {
+        let options = ExecOptions::from_iter_safe(&["befuddle", "--98", "prog.b98"]).unwrap();
+        assert_eq!(options.dialect(), Dialect::Funge98);
+
+        let options = ExecOptions::from_iter_safe(&["befuddle", "--legacy", "prog.bf"]).unwrap();
+        assert_eq!(options.dialect(), Dialect::Legacy);
+        assert_eq!(options.cell_type(), CellType::U8);
+
+        assert!(
+            ExecOptions::from_iter_safe(&["befuddle", "--legacy", "--funge98", "prog.bf"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_cell_type() {
+        let options = ExecOptions::from_iter_safe(&["befuddle", "--98", "prog.b98"]).unwrap();
+        assert_eq!(options.cell_type(), CellType::I32);
+
+        let options =
+            ExecOptions::from_iter_safe(&["befuddle", "--cells", "i64", "prog.bf"]).unwrap();
+        assert_eq!(options.cell_type(), CellType::I64);
+
+        assert!(ExecOptions::from_iter_safe(&["befuddle", "--cells", "u16", "prog.bf"]).is_err());
+    }
+
+    #[test]
+    fn test_debug_command() {
+        let cli = CliOptions::from_iter_safe(&["befuddle", "prog.bf"]).unwrap();
+        assert!(cli.command.is_none());
+        assert_eq!(cli.exec.program(), Path::new("prog.bf"));
+
+        let cli = CliOptions::from_iter_safe(&["befuddle", "debug", "--98", "prog.b98"]).unwrap();
+        match cli.command {
+            Some(Command::Debug(options)) => {
+                assert_eq!(options.program(), Path::new("prog.b98"));
+                assert_eq!(options.dialect(), Dialect::Funge98);
+            }
+            _ => panic!("expected the debug command"),
+        }
+
+        assert!(CliOptions::from_iter_safe(&["befuddle", "debug"]).is_err());
+        assert!(CliOptions::from_iter_safe(&["befuddle"]).is_err());
+    }
+
+    #[test]
+    fn test_coverage_command() {
+        let cli = CliOptions::from_iter_safe(&[
+            "befuddle",
+            "coverage",
+            "--run",
+            "a.txt",
+            "--run",
+            "b.txt",
+            "--summary",
+            "cov.json",
+            "prog.bf",
+        ])
+        .unwrap();
+        match cli.command {
+            Some(Command::Coverage(options)) => {
+                assert_eq!(
+                    options.runs,
+                    vec![PathBuf::from("a.txt"), PathBuf::from("b.txt")]
+                );
+                assert_eq!(options.summary, Some(PathBuf::from("cov.json")));
+                assert_eq!(options.exec.program(), Path::new("prog.bf"));
+            }
+            _ => panic!("expected the coverage command"),
+        }
+
+        let options = CoverageOptions::from_iter_safe(&["coverage", "prog.bf"]).unwrap();
+        assert_eq!(
+            options.read_runs().unwrap(),
+            vec![(String::from("no input"), Vec::new())]
+        );
+    }
+
+    #[test]
+    fn test_test_command() {
+        let cli = CliOptions::from_iter_safe(&["befuddle", "test"]).unwrap();
+        match cli.command {
+            Some(Command::Test(options)) => {
+                assert_eq!(options.paths, vec![PathBuf::from(".")]);
+                assert_eq!(options.limits().steps, Some(10_000_000));
+                assert_eq!(options.limits().time, Some(Duration::from_secs(10)));
+            }
+            _ => panic!("expected the test command"),
+        }
+
+        let options =
+            TestOptions::from_iter_safe(&["test", "--max-steps", "5", "a.bf", "tests"]).unwrap();
+        assert_eq!(options.max_steps, 5);
+        assert_eq!(
+            options.paths,
+            vec![PathBuf::from("a.bf"), PathBuf::from("tests")]
+        );
+    }
+
+    #[test]
+    fn test_invalid_dimension() {
+        assert!(ExecOptions::from_iter_safe(&["befuddle", "--height", "0", "prog.bf"]).is_err());
+        assert!(ExecOptions::from_iter_safe(&["befuddle"]).is_err());
+    }
+
+    #[test]
+    fn test_compile_command() {
+        let cli = CliOptions::from_iter_safe(&["befuddle", "compile", "-o", "prog.rs", "prog.bf"])
+            .unwrap();
+        match cli.command {
+            Some(Command::Compile(options)) => {
+                assert_eq!(options.output_file, Some(PathBuf::from("prog.rs")));
+                assert_eq!(options.cells, CellType::U8);
+                assert_eq!(options.seed, None);
+                let (compiled, report) = options.compile("1.@").unwrap();
+                assert!(compiled.source.contains("const NAME: &str = \"prog\";"));
+                assert!(report.is_clean());
+            }
+            _ => panic!("expected the compile command"),
+        }
+
+        let options =
+            CompileOptions::from_iter_safe(&["compile", "--cells", "i32", "--seed", "4", "-"])
+                .unwrap();
+        let (compiled, _) = options.compile("1.@").unwrap();
+        assert!(compiled.source.contains("type Cell = i32;"));
+        assert!(compiled.source.contains("const NAME: &str = \"befuddle\";"));
+        assert!(compiled
+            .source
+            .contains("const SEED: Option<u64> = Some(4);"));
+
+        let options =
+            CompileOptions::from_iter_safe(&["compile", "--width", "2", "--strict", "-"]).unwrap();
+        assert!(options.compile("1.@").is_err());
+        let options =
+            CompileOptions::from_iter_safe(&["compile", "--width", "2", "--auto-size", "-"])
+                .unwrap();
+        let (compiled, report) = options.compile("1.@").unwrap();
+        assert_eq!(compiled.code_cells, 3);
+        assert!(report.is_clean());
+    }
+
+    #[test]
+    fn test_cfg_command() {
+        let cli = CliOptions::from_iter_safe(&["befuddle", "cfg", "prog.bf"]).unwrap();
+        match cli.command {
+            Some(Command::Cfg(options)) => {
+                assert_eq!(options.format(), GraphFormat::Dot);
+                let (graph, report) = options.graph("1.@").unwrap();
+                assert_eq!(graph.segments.len(), 1);
+                assert!(report.is_clean());
+            }
+            _ => panic!("expected the cfg command"),
+        }
+
+        let options = CfgOptions::from_iter_safe(&["cfg", "-o", "cfg.json", "prog.bf"]).unwrap();
+        assert_eq!(options.format(), GraphFormat::Json);
+        let options = CfgOptions::from_iter_safe(&["cfg", "--json", "prog.bf"]).unwrap();
+        assert_eq!(options.format(), GraphFormat::Json);
+
+        let options = CfgOptions::from_iter_safe(&["cfg", "--height", "1", "prog.bf"]).unwrap();
+        let (_, report) = options.graph("1.@\n2").unwrap();
+        assert_eq!(
+            report.issues,
+            vec![LoadIssue::DroppedLine { y: 1, height: 1 }]
+        );
+        let options =
+            CfgOptions::from_iter_safe(&["cfg", "--height", "1", "--strict", "prog.bf"]).unwrap();
+        assert!(options.graph("1.@\n2").is_err());
+    }
+
+    #[test]
+    fn test_lint_command() {
+        let cli =
+            CliOptions::from_iter_safe(&["befuddle", "lint", "--width", "3", "prog.bf"]).unwrap();
+        match cli.command {
+            Some(Command::Lint(options)) => {
+                assert_eq!(options.height, 25);
+                assert_eq!(options.lint("1.@").len(), 0);
+                assert_eq!(options.lint("1.@.").len(), 1);
+            }
+            _ => panic!("expected the lint command"),
+        }
+    }
+}
@@ -0,0 +1,306 @@
+//! Compiling Befunge-93 programs ahead of time into standalone Rust
+//! programs.
+//!
+//! The compiler follows every path an IP can take from `(0, 0)`, decoding
+//! it into blocks the way the fast engine decodes traces, and writes each
+//! block as straight-line Rust in a loop that jumps between them. Programs
+//! that only use `p` on cells outside their code run as compiled
+//! throughout. Once `p` changes a cell any block was decoded from, the
+//! compiled program carries on with an interpreter instead, so it still
+//! prints what befuddle would.
+
+use std::any;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::fast::{self, Op, Origin, Trace};
+use crate::{BefungeField, Delta, FungeCell, FungeSpace};
+
+/// The instruction methods and fallback interpreter every compiled program
+/// includes.
+const RUNTIME: &str = include_str!("runtime.rs");
+
+/// What goes into a compiled program besides the field.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CompileSettings {
+    /// The name the program reports errors under.
+    pub name: String,
+    /// The seed for `?`, or `None` to seed it from the clock every run.
+    pub seed: Option<u64>,
+}
+
+/// A compiled program and how much of the field it covers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Compiled {
+    /// The Rust source of the program.
+    pub source: String,
+    /// The number of blocks the program was split into.
+    pub blocks: usize,
+    /// The number of instructions in those blocks.
+    pub instructions: usize,
+    /// The number of cells the blocks were decoded from.
+    pub code_cells: usize,
+    /// The number of places that run `p`, where the program may fall back
+    /// to its interpreter.
+    pub puts: usize,
+}
+
+struct Block {
+    origin: Origin,
+    trace: Trace,
+    /// The blocks the last op may go on to, in the order it picks from.
+    successors: Vec<usize>,
+}
+
+/// The origins the IP can go on to after the last op of `trace`.
+fn successors<C: FungeCell>(field: &BefungeField<C>, trace: &Trace) -> Vec<Origin> {
+    if let Some(next) = trace.next {
+        return vec![next];
+    }
+    let last = trace.origins[trace.origins.len() - 1];
+    let to = |delta: Delta| {
+        let (x, y) = field.advance(last.x, last.y, delta, 1);
+        Origin {
+            x,
+            y,
+            delta,
+            string_mode: false,
+        }
+    };
+    match trace.ops[trace.ops.len() - 1] {
+        Op::IfLeftRight => vec![to(Delta::LEFT), to(Delta::RIGHT)],
+        Op::IfUpDown => vec![to(Delta::UP), to(Delta::DOWN)],
+        Op::Random => Delta::ALL.iter().map(|&delta| to(delta)).collect(),
+        Op::Put => vec![to(last.delta)],
+        _ => Vec::new(),
+    }
+}
+
+/// Compiles the Befunge-93 program in `field`.
+pub fn compile<C: FungeCell>(field: &BefungeField<C>, settings: &CompileSettings) -> Compiled {
+    let (width, height) = (field.width(), field.height());
+    let mut code = vec![false; width * height];
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut indices = HashMap::new();
+    let mut pending = vec![Origin::START];
+    indices.insert(Origin::START, 0);
+
+    while let Some(origin) = pending.pop() {
+        let trace = fast::decode(field, origin, false, |x, y| {
+            code[y as usize * width + x as usize] = true;
+        });
+        let mut successors = Vec::new();
+        for next in self::successors(field, &trace) {
+            let count = indices.len();
+            let index = *indices.entry(next).or_insert_with(|| {
+                pending.push(next);
+                count
+            });
+            successors.push(index);
+        }
+        blocks.push(Block {
+            origin,
+            trace,
+            successors,
+        });
+    }
+    // Blocks are numbered in the order they were found, which `pending`
+    // does not keep.
+    blocks.sort_by_key(|block| indices[&block.origin]);
+
+    let mut source = String::new();
+    write_header(&mut source, field, settings, &code);
+    source.push_str(RUNTIME);
+    write_main(&mut source, field, &blocks);
+
+    Compiled {
+        source,
+        blocks: blocks.len(),
+        instructions: blocks.iter().map(|block| block.trace.ops.len()).sum(),
+        code_cells: code.iter().filter(|&&cell| cell).count(),
+        puts: blocks
+            .iter()
+            .filter(|block| block.trace.ops.last() == Some(&Op::Put))
+            .count(),
+    }
+}
+
+fn write_header<C: FungeCell>(
+    source: &mut String,
+    field: &BefungeField<C>,
+    settings: &CompileSettings,
+    code: &[bool],
+) {
+    let (width, height) = (field.width(), field.height());
+    // Writing to a `String` cannot fail.
+    let _ = write!(
+        source,
+        "// {} compiled from Befunge-93 by befuddle. Build it with\n\
+         //\n\
+         //     rustc -O --edition 2018 <this file>\n\
+         \n\
+         #![allow(dead_code)]\n\
+         \n\
+         use std::convert::TryFrom;\n\
+         use std::io::{{self, BufRead, BufReader, BufWriter, Stdin, Stdout, Write}};\n\
+         use std::process;\n\
+         use std::time::{{SystemTime, UNIX_EPOCH}};\n\
+         \n\
+         type Cell = {};\n\
+         \n\
+         const NAME: &str = {:?};\n\
+         const WIDTH: usize = {};\n\
+         const HEIGHT: usize = {};\n\
+         const SEED: Option<u64> = {:?};\n\
+         \n\
+         static FIELD: [Cell; WIDTH * HEIGHT] = [\n",
+        settings.name,
+        any::type_name::<C>(),
+        settings.name,
+        width,
+        height,
+        settings.seed,
+    );
+    for y in 0..height as i32 {
+        let row: Vec<_> = (0..width as i32)
+            .map(|x| field.cell(x, y).to_string())
+            .collect();
+        let _ = writeln!(source, "    {},", row.join(", "));
+    }
+    source.push_str("];\n\n/// The cells compiled code was decoded from.\nstatic CODE: [bool; WIDTH * HEIGHT] = [\n");
+    for row in code.chunks(width) {
+        let row: Vec<_> = row.iter().map(bool::to_string).collect();
+        let _ = writeln!(source, "    {},", row.join(", "));
+    }
+    source.push_str("];\n\n");
+}
+
+fn write_main<C: FungeCell>(source: &mut String, field: &BefungeField<C>, blocks: &[Block]) {
+    source.push_str(
+        "\nfn main() {\n    let mut m = Machine::new();\n    let mut block = 0;\n    loop {\n        block = match block {\n",
+    );
+    for (index, block) in blocks.iter().enumerate() {
+        let origin = block.origin;
+        let _ = writeln!(
+            source,
+            "            // ({}, {}) going {}{}",
+            origin.x,
+            origin.y,
+            origin
+                .delta
+                .name()
+                .expect("traces only follow cardinal deltas"),
+            if origin.string_mode {
+                " in string mode"
+            } else {
+                ""
+            }
+        );
+        let _ = writeln!(source, "            {} => {{", index);
+        for (&op, at) in block.trace.ops.iter().zip(&block.trace.origins) {
+            let (x, y) = (at.x, at.y);
+            let statement = match op {
+                Op::Nop => continue,
+                Op::Push(value) => format!("m.push({});", value),
+                Op::Not => String::from("m.not();"),
+                Op::Add => String::from("m.add();"),
+                Op::Subtract => String::from("m.subtract();"),
+                Op::Multiply => String::from("m.multiply();"),
+                Op::Divide => format!("m.divide({}, {});", x, y),
+                Op::Modulo => format!("m.modulo({}, {});", x, y),
+                Op::Greater => String::from("m.greater();"),
+                Op::Duplicate => String::from("m.duplicate();"),
+                Op::Swap => String::from("m.swap();"),
+                Op::Discard => String::from("m.discard();"),
+                Op::WriteInt => format!("m.write_int({}, {});", x, y),
+                Op::WriteChar => format!("m.write_char({}, {});", x, y),
+                Op::ReadInt => format!("m.read_int({}, {});", x, y),
+                Op::ReadChar => format!("m.read_char({}, {});", x, y),
+                Op::Get => format!("m.get({}, {});", x, y),
+                Op::Put => {
+                    let (next_x, next_y) = field.advance(x, y, at.delta, 1);
+                    format!(
+                        "if m.put({}, {}) {{\n                    m.interpret({}, {}, {}, {});\n                }}",
+                        x, y, next_x, next_y, at.delta.dx, at.delta.dy
+                    )
+                }
+                Op::IfLeftRight | Op::IfUpDown => format!(
+                    "if m.pop() != 0 {{ {} }} else {{ {} }}",
+                    block.successors[0], block.successors[1]
+                ),
+                Op::Random => {
+                    let successors: Vec<_> =
+                        block.successors.iter().map(usize::to_string).collect();
+                    format!("[{}][m.random()]", successors.join(", "))
+                }
+                Op::Stop => String::from("m.halt()"),
+            };
+            let _ = writeln!(source, "                {}", statement);
+        }
+        // A block that ends on `p` or runs out of room goes on to its only
+        // successor; the other ops above already give the next block.
+        if block.trace.next.is_some() || block.trace.ops.last() == Some(&Op::Put) {
+            let _ = writeln!(source, "                {}", block.successors[0]);
+        }
+        source.push_str("            }\n");
+    }
+    source.push_str("            _ => unreachable!(),\n        };\n    }\n}\n");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile_source(source: &str) -> Compiled {
+        let field = BefungeField::<u8>::from_source(source, 80, 25);
+        compile(&field, &CompileSettings::default())
+    }
+
+    #[test]
+    fn test_blocks() {
+        let compiled = compile_source("\"!dlroW ,olleH\">:#,_@");
+        // The string and the loop test, the loop body, and the `@`.
+        assert_eq!(compiled.blocks, 3);
+        assert_eq!(compiled.code_cells, 21);
+        assert_eq!(compiled.puts, 0);
+        assert!(compiled
+            .source
+            .contains("                m.write_char(18, 0);\n                m.duplicate();\n"));
+    }
+
+    #[test]
+    fn test_unreached_cells() {
+        // Nothing runs the second row, so it is data that `p` may change.
+        let compiled = compile_source("9:00p00g.@\n  ???");
+        // Up to the `p`, and after it.
+        assert_eq!(compiled.blocks, 2);
+        assert_eq!(compiled.code_cells, 10);
+        assert_eq!(compiled.puts, 1);
+        assert!(!compiled.source.contains("m.random()"));
+        assert!(compiled.source.contains("if m.put(4, 0) {"));
+    }
+
+    #[test]
+    fn test_settings() {
+        let field = BefungeField::<i64>::from_source("?@", 4, 2);
+        let settings = CompileSettings {
+            name: String::from("coin"),
+            seed: Some(7),
+        };
+        let compiled = compile(&field, &settings);
+        for line in &[
+            "type Cell = i64;",
+            "const NAME: &str = \"coin\";",
+            "const WIDTH: usize = 4;",
+            "const HEIGHT: usize = 2;",
+            "const SEED: Option<u64> = Some(7);",
+            "    63, 64, 32, 32,",
+            "    true, true, true, true,",
+            "    true, false, false, false,",
+        ] {
+            assert!(compiled.source.contains(line), "no `{}`", line);
+        }
+        // `?` itself, then wherever each of its four directions leads.
+        assert_eq!(compiled.blocks, 5);
+    }
+}
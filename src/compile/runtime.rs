@@ -0,0 +1,320 @@
+// The runtime of every program `befuddle compile` writes, pasted in after
+// the constants describing the program. It is not part of befuddle itself.
+//
+// The compiled blocks call the instruction methods of `Machine`, which work
+// as befuddle's interpreter does for Befunge-93. Once `p` rewrites a cell
+// that compiled code was made from, `interpret` carries on with the
+// rewritten field one instruction at a time.
+
+struct Machine {
+    field: Vec<Cell>,
+    stack: Vec<i32>,
+    input: BufReader<Stdin>,
+    output: BufWriter<Stdout>,
+    rng: u64,
+}
+
+fn index(x: i32, y: i32) -> usize {
+    y as usize * WIDTH + x as usize
+}
+
+fn contains(x: i32, y: i32) -> bool {
+    x >= 0 && y >= 0 && (x as usize) < WIDTH && (y as usize) < HEIGHT
+}
+
+impl Machine {
+    fn new() -> Self {
+        let rng = SEED.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+        });
+        Machine {
+            field: FIELD.to_vec(),
+            stack: Vec::new(),
+            input: BufReader::new(io::stdin()),
+            output: BufWriter::new(io::stdout()),
+            rng,
+        }
+    }
+
+    /// Ends the program as `befuddle` does once it halts.
+    fn halt(&mut self) -> ! {
+        let _ = writeln!(self.output).and_then(|_| self.output.flush());
+        process::exit(0)
+    }
+
+    /// Ends the program with an error raised at `(x, y)`.
+    fn fail(&mut self, message: &str, x: i32, y: i32) -> ! {
+        let _ = writeln!(self.output).and_then(|_| self.output.flush());
+        let value = self.field[index(x, y)] as i32;
+        let instruction = match char::from_u32(value as u32) {
+            Some(c) => format!("{:?}", c),
+            None => value.to_string(),
+        };
+        eprintln!(
+            "{}: {} at ({}, {}) executing {} with stack {:?}",
+            NAME, message, x, y, instruction, self.stack
+        );
+        process::exit(1)
+    }
+
+    fn io_error(&mut self, e: io::Error, x: i32, y: i32) -> ! {
+        self.fail(&format!("I/O error: {}", e), x, y)
+    }
+
+    fn push(&mut self, value: i32) {
+        self.stack.push(value);
+    }
+
+    fn pop(&mut self) -> i32 {
+        self.stack.pop().unwrap_or(0)
+    }
+
+    fn peek(&self, depth: usize) -> i32 {
+        self.stack
+            .len()
+            .checked_sub(depth + 1)
+            .map_or(0, |i| self.stack[i])
+    }
+
+    fn not(&mut self) {
+        let top = self.pop();
+        self.push((top == 0) as i32);
+    }
+
+    fn add(&mut self) {
+        let top = self.pop();
+        let second = self.pop();
+        self.push(second.wrapping_add(top));
+    }
+
+    fn subtract(&mut self) {
+        let top = self.pop();
+        let second = self.pop();
+        self.push(second.wrapping_sub(top));
+    }
+
+    fn multiply(&mut self) {
+        let top = self.pop();
+        let second = self.pop();
+        self.push(second.wrapping_mul(top));
+    }
+
+    fn divide(&mut self, x: i32, y: i32) {
+        if self.peek(0) == 0 {
+            self.fail("division by zero", x, y);
+        }
+        let top = self.pop();
+        let second = self.pop();
+        self.push(second.wrapping_div(top));
+    }
+
+    fn modulo(&mut self, x: i32, y: i32) {
+        if self.peek(0) == 0 {
+            self.fail("division by zero", x, y);
+        }
+        let top = self.pop();
+        let second = self.pop();
+        self.push(second.wrapping_rem(top));
+    }
+
+    fn greater(&mut self) {
+        let top = self.pop();
+        let second = self.pop();
+        self.push((second > top) as i32);
+    }
+
+    fn duplicate(&mut self) {
+        let top = self.pop();
+        self.push(top);
+        self.push(top);
+    }
+
+    fn swap(&mut self) {
+        let top = self.pop();
+        let second = self.pop();
+        self.push(top);
+        self.push(second);
+    }
+
+    fn discard(&mut self) {
+        self.pop();
+    }
+
+    fn write_int(&mut self, x: i32, y: i32) {
+        let top = self.peek(0);
+        if let Err(e) = write!(self.output, "{} ", top) {
+            self.io_error(e, x, y);
+        }
+        self.pop();
+    }
+
+    fn write_char(&mut self, x: i32, y: i32) {
+        let top = self.peek(0);
+        let c = match char::from_u32(top as u32) {
+            Some(c) => c,
+            None => self.fail(&format!("{} is not a valid character", top), x, y),
+        };
+        if let Err(e) = write!(self.output, "{}", c) {
+            self.io_error(e, x, y);
+        }
+        self.pop();
+    }
+
+    fn peek_byte(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.input.fill_buf()?.first().copied())
+    }
+
+    /// Reads a decimal integer, skipping anything before it, or `None` at
+    /// the end of the input.
+    fn scan_int(&mut self) -> io::Result<Option<i32>> {
+        self.output.flush()?;
+        let mut negative = false;
+        loop {
+            match self.peek_byte()? {
+                None => return Ok(None),
+                Some(b) if b.is_ascii_digit() => break,
+                Some(b) => {
+                    negative = b == b'-';
+                    self.input.consume(1);
+                }
+            }
+        }
+
+        let mut value: i32 = 0;
+        while let Some(b) = self.peek_byte()? {
+            if !b.is_ascii_digit() {
+                break;
+            }
+            value = value
+                .checked_mul(10)
+                .and_then(|v| v.checked_add((b - b'0') as i32))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "integer overflow"))?;
+            self.input.consume(1);
+        }
+
+        if self.peek_byte()? == Some(b'\r') {
+            self.input.consume(1);
+        }
+        if self.peek_byte()? == Some(b'\n') {
+            self.input.consume(1);
+        }
+        Ok(Some(if negative { -value } else { value }))
+    }
+
+    fn read_int(&mut self, x: i32, y: i32) {
+        match self.scan_int() {
+            Ok(value) => self.push(value.unwrap_or(-1)),
+            Err(e) => self.io_error(e, x, y),
+        }
+    }
+
+    fn read_char(&mut self, x: i32, y: i32) {
+        let read = self.output.flush().and_then(|_| self.peek_byte());
+        match read {
+            Ok(Some(b)) => {
+                self.input.consume(1);
+                self.push(b as i32);
+            }
+            Ok(None) => self.push(-1),
+            Err(e) => self.io_error(e, x, y),
+        }
+    }
+
+    /// The cell `g` or `p` at `(x, y)` addresses, failing if it is outside
+    /// the field.
+    fn address(&mut self, x: i32, y: i32) -> usize {
+        let (cell_x, cell_y) = (self.peek(1), self.peek(0));
+        if !contains(cell_x, cell_y) {
+            let message = format!("cell ({}, {}) is outside the field", cell_x, cell_y);
+            self.fail(&message, x, y);
+        }
+        let len = self.stack.len();
+        self.stack.truncate(len.saturating_sub(2));
+        index(cell_x, cell_y)
+    }
+
+    fn get(&mut self, x: i32, y: i32) {
+        let cell = self.address(x, y);
+        let value = self.field[cell] as i32;
+        self.push(value);
+    }
+
+    /// Runs `p`, returning whether it changed a cell compiled code was made
+    /// from.
+    fn put(&mut self, x: i32, y: i32) -> bool {
+        let cell = self.address(x, y);
+        let value = self.pop() as Cell;
+        let changed = self.field[cell] != value;
+        self.field[cell] = value;
+        changed && CODE[cell]
+    }
+
+    /// An index into `[right, left, down, up]`, as `?` picks it.
+    fn random(&mut self) -> usize {
+        let zone = u64::MAX - u64::MAX % 4;
+        loop {
+            self.rng = self.rng.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = self.rng;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^= z >> 31;
+            if z < zone {
+                return (z % 4) as usize;
+            }
+        }
+    }
+
+    /// Runs the program from `(x, y)` one instruction at a time.
+    fn interpret(&mut self, mut x: i32, mut y: i32, dx: i32, dy: i32) -> ! {
+        let mut delta = (dx, dy);
+        let mut string_mode = false;
+        loop {
+            let value = self.field[index(x, y)] as i32;
+            let mut distance = 1;
+            if string_mode {
+                if value == b'"' as i32 {
+                    string_mode = false;
+                } else {
+                    self.push(value);
+                }
+            } else {
+                match u8::try_from(value).unwrap_or(0) {
+                    b'"' => string_mode = true,
+                    b'>' => delta = (1, 0),
+                    b'<' => delta = (-1, 0),
+                    b'v' => delta = (0, 1),
+                    b'^' => delta = (0, -1),
+                    b'#' => distance = 2,
+                    b'!' => self.not(),
+                    b'+' => self.add(),
+                    b'-' => self.subtract(),
+                    b'*' => self.multiply(),
+                    b'/' => self.divide(x, y),
+                    b'%' => self.modulo(x, y),
+                    b'`' => self.greater(),
+                    b':' => self.duplicate(),
+                    b'\\' => self.swap(),
+                    b'$' => self.discard(),
+                    b'.' => self.write_int(x, y),
+                    b',' => self.write_char(x, y),
+                    b'&' => self.read_int(x, y),
+                    b'~' => self.read_char(x, y),
+                    b'g' => self.get(x, y),
+                    b'p' => {
+                        self.put(x, y);
+                    }
+                    b'_' => delta = if self.pop() != 0 { (-1, 0) } else { (1, 0) },
+                    b'|' => delta = if self.pop() != 0 { (0, -1) } else { (0, 1) },
+                    b'?' => delta = [(1, 0), (-1, 0), (0, 1), (0, -1)][self.random()],
+                    b'@' => self.halt(),
+                    digit @ b'0'..=b'9' => self.push((digit - b'0') as i32),
+                    _ => {}
+                }
+            }
+            x = (x + delta.0 * distance).rem_euclid(WIDTH as i32);
+            y = (y + delta.1 * distance).rem_euclid(HEIGHT as i32);
+        }
+    }
+}
@@ -0,0 +1,257 @@
+//! Finding the cells of a program a set of runs never reached.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::{FungeSpace, Profile};
+
+/// Which of a program's non-space cells were executed, merged over any
+/// number of runs.
+///
+/// Only the cells of the program as loaded count. Cells a run writes with
+/// `p` or passes through as spaces are ignored.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Coverage {
+    /// The program's cells keyed by `(y, x)`, so they sort row by row.
+    cells: BTreeMap<(i32, i32), i32>,
+    /// How many runs reached each cell of the program.
+    reached: HashMap<(i32, i32), usize>,
+    runs: usize,
+}
+
+/// The coverage of one row of the program.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RowCoverage {
+    pub y: i32,
+    pub cells: usize,
+    pub covered: usize,
+    pub percent: f64,
+    /// The columns of the cells no run reached.
+    pub missed: Vec<i32>,
+}
+
+/// Totals from a `Coverage`, ready to be written out as JSON.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct CoverageSummary {
+    pub runs: usize,
+    pub cells: usize,
+    pub covered: usize,
+    pub percent: f64,
+    /// Every row holding at least one cell, top first.
+    pub rows: Vec<RowCoverage>,
+}
+
+fn percent(covered: usize, cells: usize) -> f64 {
+    if cells == 0 {
+        100.0
+    } else {
+        100.0 * covered as f64 / cells as f64
+    }
+}
+
+impl Coverage {
+    /// Coverage of the program loaded into `space`, before any run.
+    pub fn new<S: FungeSpace + ?Sized>(space: &S) -> Self {
+        Self {
+            cells: space
+                .cells()
+                .into_iter()
+                .map(|(x, y, value)| ((y, x), value))
+                .collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Merges in the cells a run executed, from the profile of an
+    /// execution built with `ExecutionBuilder::profile`.
+    pub fn add_run(&mut self, profile: &Profile) {
+        self.runs += 1;
+        for (x, y) in profile.executed() {
+            if self.cells.contains_key(&(y, x)) {
+                *self.reached.entry((x, y)).or_default() += 1;
+            }
+        }
+    }
+
+    pub fn runs(&self) -> usize {
+        self.runs
+    }
+
+    /// How many runs executed `(x, y)`.
+    pub fn reached(&self, x: i32, y: i32) -> usize {
+        self.reached.get(&(x, y)).copied().unwrap_or_default()
+    }
+
+    pub fn summary(&self) -> CoverageSummary {
+        let mut rows: Vec<RowCoverage> = Vec::new();
+        for &(y, x) in self.cells.keys() {
+            if rows.last().map(|row| row.y) != Some(y) {
+                rows.push(RowCoverage {
+                    y,
+                    cells: 0,
+                    covered: 0,
+                    percent: 0.0,
+                    missed: Vec::new(),
+                });
+            }
+            let row = rows.last_mut().unwrap();
+            row.cells += 1;
+            if self.reached(x, y) > 0 {
+                row.covered += 1;
+            } else {
+                row.missed.push(x);
+            }
+        }
+        for row in &mut rows {
+            row.percent = percent(row.covered, row.cells);
+        }
+
+        let covered = self.reached.len();
+        CoverageSummary {
+            runs: self.runs,
+            cells: self.cells.len(),
+            covered,
+            percent: percent(covered, self.cells.len()),
+            rows,
+        }
+    }
+
+    /// Writes the program row by row, each row led by its coverage and
+    /// followed by a line marking the cells no run reached with `^`.
+    pub fn write_listing<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let summary = self.summary();
+        writeln!(
+            writer,
+            "Coverage of {} run{}: {} of {} cells ({:.1}%)",
+            summary.runs,
+            if summary.runs == 1 { "" } else { "s" },
+            summary.covered,
+            summary.cells,
+            summary.percent
+        )?;
+
+        let (min_x, min_y, max_y) = match (self.cells.keys().next(), self.cells.keys().last()) {
+            (Some(&(min_y, _)), Some(&(max_y, _))) => {
+                let min_x = self.cells.keys().map(|&(_, x)| x).min().unwrap();
+                (min_x, min_y, max_y)
+            }
+            _ => return Ok(()),
+        };
+        writeln!(writer)?;
+
+        let mut rows = summary.rows.iter().peekable();
+        for y in min_y..=max_y {
+            let row = match rows.peek() {
+                Some(row) if row.y == y => rows.next().unwrap(),
+                _ => {
+                    writeln!(writer, "{:>16} |", "-")?;
+                    continue;
+                }
+            };
+
+            let max_x = self
+                .cells
+                .range((y, min_x)..=(y, i32::MAX))
+                .next_back()
+                .map_or(min_x, |(&(_, x), _)| x);
+            let text: String = (min_x..=max_x)
+                .map(|x| match self.cells.get(&(y, x)) {
+                    Some(&value @ 0x21..=0x7e) => value as u8 as char,
+                    Some(_) => '.',
+                    None => ' ',
+                })
+                .collect();
+            writeln!(
+                writer,
+                "{:>4}/{:<4} {:>5.1}% | {}",
+                row.covered, row.cells, row.percent, text
+            )?;
+
+            if let Some(&last) = row.missed.last() {
+                let markers: String = (min_x..=last)
+                    .map(|x| if row.missed.contains(&x) { '^' } else { ' ' })
+                    .collect();
+                writeln!(writer, "{:16} | {}", "", markers)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BefungeExecution, BefungeField, MemoryIo};
+
+    /// Reads a number and prints "neg" if it is negative, "pos" otherwise.
+    const SIGN: &str = "&:0`!#v_\"sop\",,,@\n      >\"gen\",,,@";
+
+    fn coverage(inputs: &[&str]) -> Coverage {
+        let field = BefungeField::from_str(SIGN, 20, 2);
+        let mut coverage = Coverage::new(&field);
+        for input in inputs {
+            let mut exec = BefungeExecution::builder(field.clone())
+                .io(MemoryIo::from_input(*input))
+                .profile()
+                .build();
+            exec.run().unwrap();
+            coverage.add_run(exec.profile().unwrap());
+        }
+        coverage
+    }
+
+    #[test]
+    fn test_merges_runs() {
+        let positive = coverage(&["5"]).summary();
+        assert_eq!(positive.runs, 1);
+        assert_eq!(positive.cells, 27);
+        // `#` jumps over the `v`.
+        assert_eq!(positive.rows[0].missed, vec![6]);
+        assert_eq!(positive.rows[1].covered, 0);
+
+        let both = coverage(&["5", "-5"]);
+        assert_eq!(both.reached(0, 0), 2);
+        assert_eq!(both.reached(6, 1), 1);
+        let summary = both.summary();
+        assert_eq!(summary.covered, 27);
+        assert_eq!(summary.percent, 100.0);
+    }
+
+    #[test]
+    fn test_counts_program_cells_only() {
+        let summary = coverage(&["-5"]).summary();
+        // The spaces the IP crosses on its way down are not program cells.
+        assert_eq!(summary.rows[0].cells, 17);
+        assert_eq!(summary.rows[0].missed, (8..17).collect::<Vec<_>>());
+        assert_eq!(summary.covered, 18);
+        assert_eq!(summary.rows[1].percent, 100.0);
+    }
+
+    #[test]
+    fn test_listing() {
+        let mut listing = Vec::new();
+        coverage(&["-5"]).write_listing(&mut listing).unwrap();
+        assert_eq!(
+            String::from_utf8(listing).unwrap(),
+            "Coverage of 1 run: 18 of 27 cells (66.7%)\n\
+             \n   \
+             8/17    47.1% | &:0`!#v_\"sop\",,,@\n                 \
+             |         ^^^^^^^^^\n  \
+             10/10   100.0% |       >\"gen\",,,@\n"
+        );
+    }
+
+    #[test]
+    fn test_empty_program() {
+        let coverage = Coverage::new(&BefungeField::from_str("", 2, 2));
+        let mut listing = Vec::new();
+        coverage.write_listing(&mut listing).unwrap();
+        assert_eq!(
+            String::from_utf8(listing).unwrap(),
+            "Coverage of 0 runs: 0 of 0 cells (100.0%)\n"
+        );
+        assert!(coverage.summary().rows.is_empty());
+    }
+}
@@ -0,0 +1,102 @@
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum BefungeErrorKind {
+    /// `/` or `%` with a zero divisor.
+    DivisionByZero,
+    /// `,` with a value that is not a Unicode scalar value.
+    InvalidCharacter(i32),
+    /// `g` or `p` with coordinates outside the field.
+    OutOfBounds { x: i32, y: i32 },
+    /// The host failed to read input or write output.
+    Io(io::Error),
+}
+
+/// A fault raised by the program being executed.
+///
+/// Carries the state of the execution when the faulting instruction was
+/// reached, before any of its operands were popped.
+#[derive(Debug)]
+pub struct BefungeError {
+    kind: BefungeErrorKind,
+    position: (i32, i32),
+    instruction: i32,
+    stack: Vec<i32>,
+}
+
+impl BefungeError {
+    pub(crate) fn new(
+        kind: BefungeErrorKind,
+        position: (i32, i32),
+        instruction: i32,
+        stack: Vec<i32>,
+    ) -> Self {
+        Self {
+            kind,
+            position,
+            instruction,
+            stack,
+        }
+    }
+
+    pub fn kind(&self) -> &BefungeErrorKind {
+        &self.kind
+    }
+
+    pub fn position(&self) -> (i32, i32) {
+        self.position
+    }
+
+    pub fn instruction(&self) -> i32 {
+        self.instruction
+    }
+
+    pub fn stack(&self) -> &[i32] {
+        &self.stack
+    }
+}
+
+impl fmt::Display for BefungeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BefungeErrorKind::DivisionByZero => write!(f, "division by zero"),
+            BefungeErrorKind::InvalidCharacter(value) => {
+                write!(f, "{} is not a valid character", value)
+            }
+            BefungeErrorKind::OutOfBounds { x, y } => {
+                write!(f, "cell ({}, {}) is outside the field", x, y)
+            }
+            BefungeErrorKind::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl fmt::Display for BefungeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at ({}, {}) executing ",
+            self.kind, self.position.0, self.position.1
+        )?;
+        match u32::try_from(self.instruction)
+            .ok()
+            .and_then(char::from_u32)
+        {
+            Some(c) => write!(f, "{:?}", c)?,
+            None => write!(f, "{}", self.instruction)?,
+        }
+        write!(f, " with stack {:?}", self.stack)
+    }
+}
+
+impl Error for BefungeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.kind {
+            BefungeErrorKind::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
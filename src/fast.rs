@@ -0,0 +1,745 @@
+//! A faster engine for Befunge-93 programs that nothing watches step by
+//! step.
+//!
+//! Instead of reading and dispatching on the cell under the IP every step,
+//! the engine decodes the path an IP takes from a cell in a direction into
+//! a trace: the instructions it meets up to the next one whose effect on
+//! the path depends on the program's state (`_`, `|`, `?`, `@` and `p`).
+//! Unconditional turns, `#` and whole strings are folded into the trace.
+//! Traces are kept until `p` changes a cell one of them passed through.
+
+use std::convert::TryFrom;
+use std::mem;
+use std::time::Instant;
+
+use crate::{
+    BefungeCommand, BefungeError, BefungeErrorKind, BefungeExecution, Bounds, Delta, Dialect,
+    FungeIo, FungeSpace, Limit, StepOutcome,
+};
+
+/// The most instructions one trace holds, so that a loop with no branch in
+/// it still ends its trace.
+const MAX_TRACE: usize = 1024;
+
+/// The largest field, in cells, the engine keeps traces for. Larger fields
+/// run with `run` instead.
+const MAX_CELLS: usize = 1 << 18;
+
+/// How many steps apart the time limit is checked, at the most.
+const CLOCK_INTERVAL: u64 = 256;
+
+/// A slot without a trace.
+const NONE: u32 = u32::MAX;
+
+/// One decoded instruction. The last five end a trace.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Op {
+    Nop,
+    Push(i32),
+    Not,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Greater,
+    Duplicate,
+    Swap,
+    Discard,
+    WriteInt,
+    WriteChar,
+    ReadInt,
+    ReadChar,
+    Get,
+    Put,
+    IfLeftRight,
+    IfUpDown,
+    Random,
+    Stop,
+}
+
+/// The state of the IP just before it executes an instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub(crate) struct Origin {
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) delta: Delta,
+    pub(crate) string_mode: bool,
+}
+
+impl Origin {
+    /// Where an IP starts a program.
+    pub(crate) const START: Origin = Origin {
+        x: 0,
+        y: 0,
+        delta: Delta::RIGHT,
+        string_mode: false,
+    };
+}
+
+#[derive(Debug)]
+pub(crate) struct Trace {
+    pub(crate) ops: Vec<Op>,
+    /// Where the IP stands before each op.
+    pub(crate) origins: Vec<Origin>,
+    /// Where the IP goes after the last op, or `None` if the last op
+    /// decides that itself.
+    pub(crate) next: Option<Origin>,
+}
+
+fn direction(delta: Delta) -> Option<usize> {
+    match delta {
+        Delta::RIGHT => Some(0),
+        Delta::DOWN => Some(1),
+        Delta::LEFT => Some(2),
+        Delta::UP => Some(3),
+        _ => None,
+    }
+}
+
+/// Decodes the trace an IP starting at `start` follows, calling `read` with
+/// each cell the trace depends on.
+pub(crate) fn decode<F: FungeSpace + ?Sized>(
+    field: &F,
+    start: Origin,
+    legacy: bool,
+    mut read: impl FnMut(i32, i32),
+) -> Trace {
+    let mut trace = Trace {
+        ops: Vec::new(),
+        origins: Vec::new(),
+        next: None,
+    };
+    let mut at = start;
+
+    loop {
+        if trace.ops.len() == MAX_TRACE {
+            trace.next = Some(at);
+            return trace;
+        }
+
+        read(at.x, at.y);
+        let value = field.cell(at.x, at.y);
+        let origin = at;
+        let mut distance = 1;
+
+        let op = if at.string_mode {
+            if value == BefungeCommand::TOGGLE_STRING_MODE as i32 {
+                at.string_mode = false;
+                Op::Nop
+            } else {
+                Op::Push(value)
+            }
+        } else {
+            match u8::try_from(value) {
+                Ok(BefungeCommand::TOGGLE_STRING_MODE) => {
+                    at.string_mode = true;
+                    Op::Nop
+                }
+                Ok(BefungeCommand::RIGHT) => {
+                    at.delta = Delta::RIGHT;
+                    Op::Nop
+                }
+                Ok(BefungeCommand::LEFT) => {
+                    at.delta = Delta::LEFT;
+                    Op::Nop
+                }
+                Ok(BefungeCommand::UP) => {
+                    at.delta = Delta::UP;
+                    Op::Nop
+                }
+                Ok(BefungeCommand::DOWN) => {
+                    at.delta = Delta::DOWN;
+                    Op::Nop
+                }
+                Ok(BefungeCommand::BRIDGE) => {
+                    distance = 2;
+                    Op::Nop
+                }
+                Ok(BefungeCommand::NO_OP) => Op::Nop,
+                Ok(BefungeCommand::NEGATE) => Op::Not,
+                Ok(BefungeCommand::ADD) => Op::Add,
+                Ok(BefungeCommand::SUBTRACT) => Op::Subtract,
+                Ok(BefungeCommand::MULTIPLY) => Op::Multiply,
+                Ok(BefungeCommand::DIVIDE) => Op::Divide,
+                Ok(BefungeCommand::MODULO) => Op::Modulo,
+                Ok(BefungeCommand::COMPARE) => Op::Greater,
+                Ok(BefungeCommand::DUPLICATE) => Op::Duplicate,
+                Ok(BefungeCommand::SWAP) => Op::Swap,
+                Ok(BefungeCommand::DISCARD) => Op::Discard,
+                Ok(BefungeCommand::WRITE_INT) => Op::WriteInt,
+                Ok(BefungeCommand::WRITE_CHAR) => Op::WriteChar,
+                Ok(BefungeCommand::READ_INT) => Op::ReadInt,
+                Ok(BefungeCommand::READ_CHAR) => Op::ReadChar,
+                Ok(BefungeCommand::READ_CELL) => Op::Get,
+                Ok(BefungeCommand::WRITE_CELL) => Op::Put,
+                Ok(BefungeCommand::IF_LEFT_RIGHT) => Op::IfLeftRight,
+                Ok(BefungeCommand::IF_UP_DOWN) => Op::IfUpDown,
+                Ok(BefungeCommand::RANDOM) => Op::Random,
+                Ok(BefungeCommand::STOP) => Op::Stop,
+                Ok(digit @ b'0'..=b'9') => Op::Push((digit - b'0') as i32),
+                _ if legacy => Op::Push(value),
+                _ => Op::Nop,
+            }
+        };
+
+        trace.ops.push(op);
+        trace.origins.push(origin);
+        if let Op::Put | Op::IfLeftRight | Op::IfUpDown | Op::Random | Op::Stop = op {
+            return trace;
+        }
+        let (x, y) = field.advance(at.x, at.y, at.delta, distance);
+        at.x = x;
+        at.y = y;
+    }
+}
+
+/// Decoded traces, one slot for each cell, direction and string mode an IP
+/// can start one in.
+#[derive(Debug)]
+struct TraceCache {
+    bounds: Bounds,
+    width: usize,
+    /// For each slot, an index into `traces`, or `NONE`.
+    slots: Vec<u32>,
+    traces: Vec<Trace>,
+    /// Entries of `traces` no slot uses any more.
+    free: Vec<u32>,
+    /// For each cell, the slots whose traces read it.
+    readers: Vec<Vec<u32>>,
+}
+
+impl TraceCache {
+    /// A cache for a field of `bounds`, or `None` if it is too large.
+    fn new(bounds: Bounds) -> Option<Self> {
+        let width = bounds.max_x.checked_sub(bounds.min_x)? as usize + 1;
+        let height = bounds.max_y.checked_sub(bounds.min_y)? as usize + 1;
+        let cells = width.checked_mul(height).filter(|&n| n <= MAX_CELLS)?;
+
+        Some(Self {
+            bounds,
+            width,
+            slots: vec![NONE; cells * 8],
+            traces: Vec::new(),
+            free: Vec::new(),
+            readers: vec![Vec::new(); cells],
+        })
+    }
+
+    fn cell(&self, x: i32, y: i32) -> usize {
+        (y - self.bounds.min_y) as usize * self.width + (x - self.bounds.min_x) as usize
+    }
+
+    fn slot(&self, origin: Origin) -> usize {
+        let direction = direction(origin.delta).expect("traces only follow cardinal deltas");
+        self.cell(origin.x, origin.y) * 8 + direction * 2 + origin.string_mode as usize
+    }
+
+    /// The index of the trace starting at `origin`, decoding it first if
+    /// need be.
+    fn trace<F: FungeSpace>(&mut self, field: &F, origin: Origin, legacy: bool) -> usize {
+        let slot = self.slot(origin);
+        if self.slots[slot] != NONE {
+            return self.slots[slot] as usize;
+        }
+
+        let mut read = Vec::new();
+        let trace = decode(field, origin, legacy, |x, y| read.push(self.cell(x, y)));
+        for cell in read {
+            if !self.readers[cell].contains(&(slot as u32)) {
+                self.readers[cell].push(slot as u32);
+            }
+        }
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.traces[index as usize] = trace;
+                index
+            }
+            None => {
+                self.traces.push(trace);
+                (self.traces.len() - 1) as u32
+            }
+        };
+        self.slots[slot] = index;
+        index as usize
+    }
+
+    /// Drops every trace that read `(x, y)`.
+    fn invalidate(&mut self, x: i32, y: i32) {
+        let cell = self.cell(x, y);
+        for slot in mem::take(&mut self.readers[cell]) {
+            let index = mem::replace(&mut self.slots[slot as usize], NONE);
+            if index != NONE {
+                self.free.push(index);
+            }
+        }
+    }
+}
+
+/// Why `run_fast` stopped.
+enum Stop {
+    Halted,
+    /// A write grew the space past the bounds the cache covers, so `run`
+    /// has to carry on.
+    Grown,
+    Limit(Limit),
+    Fault(BefungeErrorKind),
+}
+
+fn pop(stack: &mut Vec<i32>) -> i32 {
+    stack.pop().unwrap_or_default()
+}
+
+fn peek(stack: &[i32], depth: usize) -> i32 {
+    stack.len().checked_sub(depth + 1).map_or(0, |i| stack[i])
+}
+
+impl<I: FungeIo, F: FungeSpace> BefungeExecution<I, F> {
+    /// Whether `run_fast` can use its own engine, which only handles a
+    /// single Befunge-93 IP that no breakpoint, watchpoint, history, tracer
+    /// or profile needs to see step by step.
+    fn runs_fast(&self) -> bool {
+        let ip = self.ip();
+        self.active
+            && self.dialect != Dialect::Funge98
+            && self.ips.len() == 1
+            && ip.stacks.depth() == 1
+            && direction(ip.delta).is_some()
+            && self.breakpoints.is_empty()
+            && self.watchpoints.is_empty()
+            && self.history.is_none()
+            && self.tracer.is_none()
+            && self.profile.is_none()
+    }
+
+    /// Runs like `run`, but with an engine that decodes the program into
+    /// traces once instead of reading every instruction as it goes, which
+    /// makes long-running programs several times faster.
+    ///
+    /// Anything that needs to see every step, such as breakpoints, a
+    /// history or a tracer, makes it fall back to `run`, as do Funge-98,
+    /// very large fields and a `p` that grows the space. Limits are honoured exactly, except that the time
+    /// limit is checked between traces.
+    pub fn run_fast(&mut self) -> Result<StepOutcome, BefungeError> {
+        let mut cache = match TraceCache::new(self.field.bounds()) {
+            Some(cache) if self.runs_fast() => cache,
+            _ => return self.run(),
+        };
+
+        let started = Instant::now();
+        let legacy = self.dialect == Dialect::Legacy;
+        let is_true = |value: i32| if legacy { value > 0 } else { value != 0 };
+        let max_steps = self.limits.steps.unwrap_or(u64::MAX);
+        let max_stack = self.limits.stack.unwrap_or(usize::MAX);
+        let mut next_clock = self.steps;
+        let offset = self.ip().storage_offset;
+
+        let ip = &mut self.ips[0];
+        let mut stack = mem::take(ip.stacks.toss_mut());
+        let mut at = Origin {
+            x: ip.x,
+            y: ip.y,
+            delta: ip.delta,
+            string_mode: ip.string_mode,
+        };
+
+        let stop = 'run: loop {
+            if let Some(time) = self.limits.time {
+                if self.steps >= next_clock {
+                    next_clock = self.steps + CLOCK_INTERVAL;
+                    if started.elapsed() >= time {
+                        break Stop::Limit(Limit::Time(time));
+                    }
+                }
+            }
+
+            let index = cache.trace(&self.field, at, legacy);
+            let trace = &cache.traces[index];
+            let mut delta = at.delta;
+            let mut written = None;
+
+            for (i, &op) in trace.ops.iter().enumerate() {
+                if self.steps >= max_steps {
+                    at = trace.origins[i];
+                    break 'run Stop::Limit(Limit::Steps(max_steps));
+                }
+                if stack.len() > max_stack {
+                    at = trace.origins[i];
+                    break 'run Stop::Limit(Limit::Stack(max_stack));
+                }
+                self.steps += 1;
+
+                let fault = match op {
+                    Op::Nop => continue,
+                    Op::Push(value) => {
+                        stack.push(value);
+                        continue;
+                    }
+                    Op::Not => {
+                        let top = pop(&mut stack);
+                        stack.push(if is_true(top) { 0 } else { 1 });
+                        continue;
+                    }
+                    Op::Add => {
+                        let top = pop(&mut stack);
+                        let second = pop(&mut stack);
+                        stack.push(top.wrapping_add(second));
+                        continue;
+                    }
+                    Op::Multiply => {
+                        let top = pop(&mut stack);
+                        let second = pop(&mut stack);
+                        stack.push(top.wrapping_mul(second));
+                        continue;
+                    }
+                    Op::Subtract | Op::Divide | Op::Modulo | Op::Greater => {
+                        let divisor = if legacy {
+                            peek(&stack, 1)
+                        } else {
+                            peek(&stack, 0)
+                        };
+                        if divisor == 0 && matches!(op, Op::Divide | Op::Modulo) {
+                            BefungeErrorKind::DivisionByZero
+                        } else {
+                            let top = pop(&mut stack);
+                            let second = pop(&mut stack);
+                            let (a, b) = if legacy { (top, second) } else { (second, top) };
+                            stack.push(match op {
+                                Op::Subtract => a.wrapping_sub(b),
+                                Op::Divide => a.wrapping_div(b),
+                                Op::Modulo => a.wrapping_rem(b),
+                                _ => (a > b) as i32,
+                            });
+                            continue;
+                        }
+                    }
+                    Op::Duplicate => {
+                        let top = pop(&mut stack);
+                        stack.push(top);
+                        stack.push(top);
+                        continue;
+                    }
+                    Op::Swap => {
+                        let top = pop(&mut stack);
+                        let second = pop(&mut stack);
+                        stack.push(top);
+                        stack.push(second);
+                        continue;
+                    }
+                    Op::Discard => {
+                        pop(&mut stack);
+                        continue;
+                    }
+                    Op::WriteInt => {
+                        let top = peek(&stack, 0);
+                        let separator = if legacy { None } else { Some(' ') };
+                        let written = self
+                            .io
+                            .write_int(top)
+                            .and_then(|_| separator.map_or(Ok(()), |c| self.io.write_char(c)));
+                        match written {
+                            Ok(()) => {
+                                pop(&mut stack);
+                                continue;
+                            }
+                            Err(e) => BefungeErrorKind::Io(e),
+                        }
+                    }
+                    Op::WriteChar => {
+                        let top = peek(&stack, 0);
+                        match u32::try_from(top).ok().and_then(char::from_u32) {
+                            Some(c) => match self.io.write_char(c) {
+                                Ok(()) => {
+                                    pop(&mut stack);
+                                    continue;
+                                }
+                                Err(e) => BefungeErrorKind::Io(e),
+                            },
+                            None => BefungeErrorKind::InvalidCharacter(top),
+                        }
+                    }
+                    Op::ReadInt => match self.io.read_int() {
+                        Ok(i) => {
+                            stack.push(i.unwrap_or(-1));
+                            continue;
+                        }
+                        Err(e) => BefungeErrorKind::Io(e),
+                    },
+                    Op::ReadChar => match self.io.read_char() {
+                        Ok(c) => {
+                            stack.push(c.map_or(-1, |c| c as i32));
+                            continue;
+                        }
+                        Err(e) => BefungeErrorKind::Io(e),
+                    },
+                    Op::Get | Op::Put => {
+                        let x = peek(&stack, 1).wrapping_add(offset.0);
+                        let y = peek(&stack, 0).wrapping_add(offset.1);
+                        if !self.field.contains(x, y) {
+                            BefungeErrorKind::OutOfBounds { x, y }
+                        } else if op == Op::Get {
+                            stack.truncate(stack.len().saturating_sub(2));
+                            stack.push(self.field.cell(x, y));
+                            continue;
+                        } else {
+                            stack.truncate(stack.len().saturating_sub(2));
+                            let value = pop(&mut stack);
+                            let old = self.field.cell(x, y);
+                            self.field.set_cell(x, y, value);
+                            if self.field.cell(x, y) != old {
+                                written = Some((x, y));
+                            }
+                            continue;
+                        }
+                    }
+                    Op::IfLeftRight => {
+                        let top = pop(&mut stack);
+                        delta = if is_true(top) {
+                            Delta::LEFT
+                        } else {
+                            Delta::RIGHT
+                        };
+                        continue;
+                    }
+                    Op::IfUpDown => {
+                        let top = pop(&mut stack);
+                        delta = if is_true(top) { Delta::UP } else { Delta::DOWN };
+                        continue;
+                    }
+                    Op::Random => {
+                        delta = Delta::ALL[self.rng.below(4) as usize];
+                        continue;
+                    }
+                    Op::Stop => {
+                        at = trace.origins[i];
+                        break 'run Stop::Halted;
+                    }
+                };
+                at = trace.origins[i];
+                break 'run Stop::Fault(fault);
+            }
+
+            at = match trace.next {
+                Some(next) => next,
+                None => {
+                    let last = trace.origins[trace.origins.len() - 1];
+                    let (x, y) = self.field.advance(last.x, last.y, delta, 1);
+                    Origin {
+                        x,
+                        y,
+                        delta,
+                        string_mode: false,
+                    }
+                }
+            };
+            if self.field.bounds() != cache.bounds {
+                break Stop::Grown;
+            }
+            if let Some((x, y)) = written {
+                cache.invalidate(x, y);
+            }
+        };
+
+        let ip = &mut self.ips[0];
+        *ip.stacks.toss_mut() = stack;
+        ip.x = at.x;
+        ip.y = at.y;
+        ip.delta = at.delta;
+        ip.string_mode = at.string_mode;
+
+        let outcome = match stop {
+            Stop::Grown => return self.run(),
+            Stop::Halted => {
+                self.active = false;
+                Ok(StepOutcome::Halted)
+            }
+            Stop::Limit(limit) => Ok(StepOutcome::LimitExceeded(limit)),
+            Stop::Fault(kind) => Err(self.fault(kind)),
+        };
+        self.io
+            .flush()
+            .map_err(|e| self.fault(BefungeErrorKind::Io(e)))?;
+        outcome
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BefungeField, Limits, MemoryIo, SparseSpace};
+
+    type Execution = BefungeExecution<MemoryIo, BefungeField<i32>>;
+
+    fn execution(program: &str, input: &str, dialect: Dialect, limits: Limits) -> Execution {
+        BefungeExecution::builder(BefungeField::<i32>::from_source(program, 20, 5))
+            .io(MemoryIo::from_input(input))
+            .dialect(dialect)
+            .seed(11)
+            .limits(limits)
+            .build()
+    }
+
+    /// Everything the two engines must agree on.
+    fn state(exec: &Execution, result: Result<StepOutcome, BefungeError>) -> String {
+        format!(
+            "{:?} {:?} {:?} {} {:?} {}",
+            result,
+            exec.pc(),
+            exec.stack(),
+            exec.steps(),
+            exec.field().cells(),
+            exec.io().output_string()
+        )
+    }
+
+    fn assert_same(program: &str, input: &str, dialect: Dialect, limits: Limits) {
+        let mut slow = execution(program, input, dialect, limits);
+        let result = slow.run();
+        let expected = state(&slow, result);
+
+        let mut fast = execution(program, input, dialect, limits);
+        assert!(fast.runs_fast());
+        let result = fast.run_fast();
+        assert_eq!(state(&fast, result), expected, "{:?}", program);
+    }
+
+    const PROGRAMS: &[&str] = &[
+        // Counts down from 9, printing each number.
+        "9>:.1-:v\n ^     _@",
+        // Reads two numbers and prints their quotient and remainder.
+        "&&\\:01p\\:01g/.01g%.@",
+        // Strings, a bridge and turns across the edges.
+        "v  >\"olleh\",,,,,#v\n>  ^   @,*25    <",
+        // Writes its counter as a digit into the loop it runs and prints it
+        // from there, so the trace of the loop has to be decoded again each
+        // time round.
+        "0>1+:5`#@_:\"0\"+91pv\n ^      ,0        <",
+        // Writes a `.` just ahead of itself.
+        "\".\"70p5  @",
+        // Wanders at random until it falls into the `@`.
+        "v\n>?<\n ?@\n >^",
+        // Division by zero.
+        "12-0/@",
+        // An unknown instruction, a character out of range and `g` off
+        // the field.
+        "a1-,@\n99gA@",
+        // A string that wraps around the whole field without closing.
+        "\"abc",
+        // Echoes its input, ending at end of input.
+        "~:1+!#@_,",
+    ];
+
+    #[test]
+    fn test_matches_run() {
+        for program in PROGRAMS {
+            for &dialect in &[Dialect::Befunge93, Dialect::Legacy] {
+                let limits = Limits::default().steps(5000);
+                assert_same(program, "17 5 echo", dialect, limits);
+            }
+        }
+    }
+
+    #[test]
+    fn test_limits_mid_trace() {
+        for program in PROGRAMS {
+            for steps in 0..40 {
+                assert_same(
+                    program,
+                    "7 3",
+                    Dialect::Befunge93,
+                    Limits::default().steps(steps),
+                );
+            }
+            let limits = Limits::default().steps(1000).stack(3);
+            assert_same(program, "7 3", Dialect::Befunge93, limits);
+        }
+    }
+
+    #[test]
+    fn test_resumes_after_limit() {
+        let program = PROGRAMS[3];
+        let mut slow = execution(program, "", Dialect::Befunge93, Limits::default());
+        let expected = slow.run();
+        let expected = state(&slow, expected);
+
+        let mut fast = execution(program, "", Dialect::Befunge93, Limits::default().steps(17));
+        assert_eq!(
+            fast.run_fast().unwrap(),
+            StepOutcome::LimitExceeded(Limit::Steps(17))
+        );
+        fast.set_limits(Limits::default());
+        let result = fast.run_fast();
+        assert_eq!(state(&fast, result), expected);
+    }
+
+    #[test]
+    fn test_falls_back() {
+        let mut exec = execution("@", "", Dialect::Funge98, Limits::default());
+        assert!(!exec.runs_fast());
+        assert_eq!(exec.run_fast().unwrap(), StepOutcome::Halted);
+
+        let mut exec = execution("1.@", "", Dialect::Befunge93, Limits::default());
+        exec.watch(0, 0);
+        assert!(!exec.runs_fast());
+
+        let mut exec = execution("@", "", Dialect::Befunge93, Limits::default());
+        exec.run_fast().unwrap();
+        assert!(!exec.runs_fast());
+        assert_eq!(exec.run_fast().unwrap(), StepOutcome::Halted);
+    }
+
+    #[test]
+    fn test_space_grows() {
+        // A Befunge-93 run on a sparse space whose `p` writes past its
+        // bounds, then carries on with `run` across the bigger space.
+        for program in &["1 99p@", "1 99p2.@"] {
+            let build = || {
+                BefungeExecution::builder(SparseSpace::from(*program))
+                    .io(MemoryIo::default())
+                    .build()
+            };
+            let mut slow = build();
+            let expected = slow.run();
+            let mut fast = build();
+            let result = fast.run_fast();
+            assert_eq!(
+                (
+                    result.unwrap(),
+                    fast.pc(),
+                    fast.field().cells(),
+                    fast.io().output_string()
+                ),
+                (
+                    expected.unwrap(),
+                    slow.pc(),
+                    slow.field().cells(),
+                    slow.io().output_string()
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalidates_written_cells() {
+        let field = BefungeField::<i32>::from_source("v\n>1", 2, 2);
+        let mut cache = TraceCache::new(field.bounds()).unwrap();
+        let start = Origin {
+            x: 0,
+            y: 0,
+            delta: Delta::RIGHT,
+            string_mode: false,
+        };
+        let index = cache.trace(&field, start, false);
+        assert_eq!(
+            cache.traces[index].ops[..3],
+            [Op::Nop, Op::Nop, Op::Push(1)]
+        );
+        assert_eq!(cache.trace(&field, start, false), index);
+
+        cache.invalidate(1, 0);
+        assert_eq!(cache.trace(&field, start, false), index);
+        cache.invalidate(1, 1);
+        assert_eq!(cache.slots.iter().filter(|&&s| s != NONE).count(), 0);
+        assert_eq!(cache.free, vec![index as u32]);
+    }
+}
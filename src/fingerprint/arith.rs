@@ -0,0 +1,120 @@
+use std::io;
+
+use super::{Context, Fingerprint};
+
+/// `NULL`: every instruction reflects, hiding whatever was loaded before.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Null;
+
+impl Fingerprint for Null {
+    fn name(&self) -> &str {
+        "NULL"
+    }
+
+    fn instructions(&self) -> &str {
+        "ABCDEFGHIJKLMNOPQRSTUVWXYZ"
+    }
+
+    fn execute(&mut self, _instruction: u8, context: &mut Context<'_>) -> io::Result<()> {
+        context.ip.reflect();
+        Ok(())
+    }
+}
+
+/// `ROMA`: pushes the values of the Roman numerals.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Roma;
+
+impl Fingerprint for Roma {
+    fn name(&self) -> &str {
+        "ROMA"
+    }
+
+    fn instructions(&self) -> &str {
+        "CDILMVX"
+    }
+
+    fn execute(&mut self, instruction: u8, context: &mut Context<'_>) -> io::Result<()> {
+        context.ip.push(match instruction {
+            b'C' => 100,
+            b'D' => 500,
+            b'I' => 1,
+            b'L' => 50,
+            b'M' => 1000,
+            b'V' => 5,
+            _ => 10,
+        });
+        Ok(())
+    }
+}
+
+/// `MODU`: modulo with the sign conventions `%` leaves open. A zero divisor
+/// gives zero.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Modu;
+
+impl Fingerprint for Modu {
+    fn name(&self) -> &str {
+        "MODU"
+    }
+
+    fn instructions(&self) -> &str {
+        "MRU"
+    }
+
+    fn execute(&mut self, instruction: u8, context: &mut Context<'_>) -> io::Result<()> {
+        let b = context.ip.pop();
+        let a = context.ip.pop();
+
+        context.ip.push(if b == 0 {
+            0
+        } else {
+            match instruction {
+                // Floored: the result takes the sign of the divisor.
+                b'M' => {
+                    let r = a.wrapping_rem(b);
+                    if r != 0 && (r < 0) != (b < 0) {
+                        r.wrapping_add(b)
+                    } else {
+                        r
+                    }
+                }
+                // Truncated, as in C.
+                b'R' => a.wrapping_rem(b),
+                _ => a.wrapping_rem(b).wrapping_abs(),
+            }
+        });
+        Ok(())
+    }
+}
+
+/// `BOOL`: bitwise logic.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Bool;
+
+impl Fingerprint for Bool {
+    fn name(&self) -> &str {
+        "BOOL"
+    }
+
+    fn instructions(&self) -> &str {
+        "ANOX"
+    }
+
+    fn execute(&mut self, instruction: u8, context: &mut Context<'_>) -> io::Result<()> {
+        if instruction == b'N' {
+            let a = context.ip.pop();
+            context.ip.push(!a);
+            return Ok(());
+        }
+
+        let b = context.ip.pop();
+        let a = context.ip.pop();
+        context.ip.push(match instruction {
+            b'A' => a & b,
+            b'O' => a | b,
+            _ => a ^ b,
+        });
+        Ok(())
+    }
+}
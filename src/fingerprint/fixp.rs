@@ -0,0 +1,70 @@
+use std::f64::consts::PI;
+use std::io;
+
+use super::{Context, Fingerprint};
+
+/// Fixed-point values are scaled by this much.
+const SCALE: f64 = 10000.0;
+
+/// `FIXP`: fixed-point and trigonometric arithmetic. Angles are in degrees
+/// and trigonometric values are scaled by 10000 and rounded; other results
+/// are truncated, and those that are not real numbers reflect.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Fixp;
+
+fn to_radians(value: i32) -> f64 {
+    (value as f64 / SCALE).to_radians()
+}
+
+impl Fingerprint for Fixp {
+    fn name(&self) -> &str {
+        "FIXP"
+    }
+
+    fn instructions(&self) -> &str {
+        "ABCDIJNOPQRSTUVX"
+    }
+
+    fn execute(&mut self, instruction: u8, context: &mut Context<'_>) -> io::Result<()> {
+        let ip = &mut *context.ip;
+
+        let result = match instruction {
+            b'A' | b'O' | b'X' | b'R' => {
+                let b = ip.pop();
+                let a = ip.pop();
+                match instruction {
+                    b'A' => Some((a & b) as f64),
+                    b'O' => Some((a | b) as f64),
+                    b'X' => Some((a ^ b) as f64),
+                    _ => Some((a as f64).powi(b)),
+                }
+            }
+            b'B' => Some(((ip.pop() as f64 / SCALE).acos().to_degrees() * SCALE).round()),
+            b'C' => Some((to_radians(ip.pop()).cos() * SCALE).round()),
+            b'D' => {
+                let n = ip.pop();
+                let r = match n {
+                    0 => 0,
+                    _ => context.rng.below(n.unsigned_abs()) as i64 * n.signum() as i64,
+                };
+                Some(r as f64)
+            }
+            b'I' => Some((to_radians(ip.pop()).sin() * SCALE).round()),
+            b'J' => Some(((ip.pop() as f64 / SCALE).asin().to_degrees() * SCALE).round()),
+            b'N' => Some(-(ip.pop() as f64)),
+            b'P' => Some(ip.pop() as f64 * PI),
+            b'Q' => Some((ip.pop() as f64).sqrt()),
+            b'S' => Some(ip.pop().signum() as f64),
+            b'T' => Some((to_radians(ip.pop()).tan() * SCALE).round()),
+            b'U' => Some(((ip.pop() as f64 / SCALE).atan().to_degrees() * SCALE).round()),
+            b'V' => Some((ip.pop() as f64).abs()),
+            _ => None,
+        };
+
+        match result {
+            Some(value) if value.is_finite() => ip.push(value as i64 as i32),
+            _ => ip.reflect(),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,50 @@
+use std::collections::HashMap;
+use std::io;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use super::{Context, Fingerprint};
+
+/// `HRTI`: a microsecond timer with one mark per IP.
+#[derive(Clone, Debug, Default)]
+pub struct Hrti {
+    marks: HashMap<i32, Instant>,
+}
+
+impl Fingerprint for Hrti {
+    fn name(&self) -> &str {
+        "HRTI"
+    }
+
+    fn instructions(&self) -> &str {
+        "EGMST"
+    }
+
+    fn execute(&mut self, instruction: u8, context: &mut Context<'_>) -> io::Result<()> {
+        let id = context.ip.id();
+
+        match instruction {
+            b'E' => {
+                self.marks.remove(&id);
+            }
+            // The granularity of the timer, in microseconds.
+            b'G' => context.ip.push(1),
+            b'M' => {
+                self.marks.insert(id, Instant::now());
+            }
+            b'S' => {
+                let micros = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.subsec_micros());
+                context.ip.push(micros as i32);
+            }
+            _ => match self.marks.get(&id) {
+                Some(mark) => {
+                    let micros = mark.elapsed().as_micros().min(i32::MAX as u128);
+                    context.ip.push(micros as i32);
+                }
+                None => context.ip.reflect(),
+            },
+        }
+        Ok(())
+    }
+}
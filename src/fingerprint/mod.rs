@@ -0,0 +1,246 @@
+//! Funge-98 fingerprints: named sets of `A`-`Z` instructions that programs
+//! load with `(` and unload with `)`.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::io;
+
+use crate::{FungeIo, FungeRng, FungeSpace, InstructionPointer};
+
+mod arith;
+mod fixp;
+mod hrti;
+mod orth;
+mod refc;
+mod strn;
+
+pub use self::arith::{Bool, Modu, Null, Roma};
+pub use self::fixp::Fixp;
+pub use self::hrti::Hrti;
+pub use self::orth::Orth;
+pub use self::refc::Refc;
+pub use self::strn::Strn;
+
+/// The state a fingerprint instruction can act on.
+pub struct Context<'a> {
+    pub ip: &'a mut InstructionPointer,
+    pub space: &'a mut dyn FungeSpace,
+    pub io: &'a mut dyn FungeIo,
+    pub rng: &'a mut FungeRng,
+}
+
+impl Context<'_> {
+    /// Moves the IP one step along its delta, as `#` does.
+    pub fn move_ip(&mut self) {
+        let (x, y) = self.ip.position();
+        let (x, y) = self.space.advance(x, y, self.ip.delta(), 1);
+        self.ip.set_position(x, y);
+    }
+
+    /// The cell at `(x, y)` relative to the IP's storage offset.
+    pub fn get(&self, (x, y): (i32, i32)) -> i32 {
+        let (ox, oy) = self.ip.storage_offset();
+        self.space.cell(x.wrapping_add(ox), y.wrapping_add(oy))
+    }
+
+    /// Stores `value` at `(x, y)` relative to the IP's storage offset,
+    /// returning `false` if the space does not extend there.
+    pub fn put(&mut self, (x, y): (i32, i32), value: i32) -> bool {
+        let (ox, oy) = self.ip.storage_offset();
+        self.space
+            .set_cell(x.wrapping_add(ox), y.wrapping_add(oy), value)
+    }
+}
+
+/// A set of instructions loadable with `(`.
+///
+/// Fingerprints live in the execution's registry for its whole run, so they
+/// can keep state between calls.
+pub trait Fingerprint: Debug {
+    /// The name `(` loads the fingerprint by, usually four letters.
+    fn name(&self) -> &str;
+
+    /// The instructions the fingerprint defines, each in `A`-`Z`.
+    fn instructions(&self) -> &str;
+
+    /// Executes `instruction`, one of `instructions`. Anything the
+    /// instruction cannot do should reflect the IP.
+    fn execute(&mut self, instruction: u8, context: &mut Context<'_>) -> io::Result<()>;
+}
+
+/// The id `(` and `)` identify a fingerprint by: its name in base 256.
+pub fn fingerprint_id(name: &str) -> i32 {
+    name.bytes()
+        .fold(0i32, |id, b| id.wrapping_mul(256).wrapping_add(b as i32))
+}
+
+/// The fingerprints available to an execution.
+#[derive(Debug, Default)]
+pub struct FingerprintRegistry {
+    fingerprints: Vec<Box<dyn Fingerprint>>,
+    ids: HashMap<i32, usize>,
+}
+
+impl FingerprintRegistry {
+    /// A registry with no fingerprints.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A registry with every fingerprint shipped with befuddle.
+    pub fn standard() -> Self {
+        let mut registry = Self::new();
+        registry.register(Null);
+        registry.register(Roma);
+        registry.register(Modu);
+        registry.register(Bool);
+        registry.register(Fixp);
+        registry.register(Strn);
+        registry.register(Orth);
+        registry.register(Hrti::default());
+        registry.register(Refc::default());
+        registry
+    }
+
+    /// Adds `fingerprint`, replacing any registered under the same name.
+    pub fn register<P: Fingerprint + 'static>(&mut self, fingerprint: P) {
+        let id = fingerprint_id(fingerprint.name());
+        match self.ids.get(&id) {
+            Some(&index) => self.fingerprints[index] = Box::new(fingerprint),
+            None => {
+                self.ids.insert(id, self.fingerprints.len());
+                self.fingerprints.push(Box::new(fingerprint));
+            }
+        }
+    }
+
+    /// The names of the registered fingerprints.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.fingerprints.iter().map(|f| f.name())
+    }
+
+    pub(crate) fn find(&self, id: i32) -> Option<usize> {
+        self.ids.get(&id).copied()
+    }
+
+    pub(crate) fn get(&self, index: usize) -> &dyn Fingerprint {
+        self.fingerprints[index].as_ref()
+    }
+
+    pub(crate) fn get_mut(&mut self, index: usize) -> &mut dyn Fingerprint {
+        self.fingerprints[index].as_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BefungeExecution, Dialect, MemoryIo, SparseSpace};
+
+    /// Runs `program` as Funge-98 after loading the fingerprint `name`.
+    fn run_with(name: &str, program: &str) -> BefungeExecution<MemoryIo, SparseSpace> {
+        let reversed: String = name.chars().rev().collect();
+        let source = format!("\"{}\"4($${}@", reversed, program);
+        let mut exec = BefungeExecution::builder(SparseSpace::from(source.as_str()))
+            .io(MemoryIo::default())
+            .dialect(Dialect::Funge98)
+            .build();
+        exec.run().unwrap();
+        exec
+    }
+
+    #[test]
+    fn test_fingerprint_id() {
+        assert_eq!(fingerprint_id("NULL"), 0x4e55_4c4c);
+        assert_eq!(fingerprint_id("ROMA"), 0x524f_4d41);
+    }
+
+    #[test]
+    fn test_register_replaces() {
+        let mut registry = FingerprintRegistry::standard();
+        let count = registry.names().count();
+        registry.register(Null);
+        assert_eq!(registry.names().count(), count);
+        assert_eq!(registry.find(fingerprint_id("NULL")), Some(0));
+        assert_eq!(registry.find(fingerprint_id("NOPE")), None);
+    }
+
+    #[test]
+    fn test_roma() {
+        assert_eq!(
+            run_with("ROMA", "MDCLXVI").stack(),
+            vec![1000, 500, 100, 50, 10, 5, 1]
+        );
+    }
+
+    #[test]
+    fn test_modu() {
+        assert_eq!(run_with("MODU", "07-3M07-3R07-3U").stack(), vec![2, -1, 1]);
+    }
+
+    #[test]
+    fn test_bool() {
+        assert_eq!(run_with("BOOL", "65A65O65X0N").stack(), vec![4, 7, 3, -1]);
+    }
+
+    #[test]
+    fn test_fixp() {
+        let exec = run_with("FIXP", "0C9a*aa*a*a**If5*Q");
+        assert_eq!(exec.stack(), vec![10000, 10000, 8]);
+    }
+
+    #[test]
+    fn test_strn() {
+        let exec = run_with("STRN", "0\"dlrow\"0\"olleh\"AN.D0\"24-\"Vf5*SD");
+        assert_eq!(exec.stack(), vec![-42]);
+        assert_eq!(exec.io().output_string(), "10 helloworld75");
+    }
+
+    #[test]
+    fn test_orth() {
+        let exec = run_with("ORTH", "01G0\"ih\"S");
+        assert_eq!(exec.stack(), vec![b'H' as i32]);
+        assert_eq!(exec.io().output_string(), "hi");
+    }
+
+    #[test]
+    fn test_refc() {
+        assert_eq!(run_with("REFC", "12R34R12RD").stack(), vec![0, 1, 1, 2]);
+    }
+
+    #[test]
+    fn test_hrti() {
+        let exec = run_with("HRTI", "GMT");
+        assert_eq!(exec.stack().len(), 2);
+        assert_eq!(exec.stack()[0], 1);
+    }
+
+    #[derive(Debug)]
+    struct Answer;
+
+    impl Fingerprint for Answer {
+        fn name(&self) -> &str {
+            "ANSR"
+        }
+
+        fn instructions(&self) -> &str {
+            "A"
+        }
+
+        fn execute(&mut self, _instruction: u8, context: &mut Context<'_>) -> io::Result<()> {
+            context.ip.push(42);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_custom_fingerprint() {
+        let mut exec = BefungeExecution::builder(SparseSpace::from("\"RSNA\"4(A@"))
+            .io(MemoryIo::default())
+            .dialect(Dialect::Funge98)
+            .fingerprint(Answer)
+            .build();
+        exec.run().unwrap();
+        assert_eq!(exec.stack(), vec![fingerprint_id("ANSR"), 1, 42]);
+    }
+}
@@ -0,0 +1,80 @@
+use std::io;
+
+use crate::Delta;
+
+use super::{Context, Fingerprint};
+
+/// `ORTH`: the instructions of the Orthogonal language. `G` and `P` take
+/// their coordinates as `y x` rather than `x y`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Orth;
+
+impl Fingerprint for Orth {
+    fn name(&self) -> &str {
+        "ORTH"
+    }
+
+    fn instructions(&self) -> &str {
+        "AEGOPSVWXYZ"
+    }
+
+    fn execute(&mut self, instruction: u8, context: &mut Context<'_>) -> io::Result<()> {
+        match instruction {
+            b'A' | b'E' | b'O' => {
+                let b = context.ip.pop();
+                let a = context.ip.pop();
+                context.ip.push(match instruction {
+                    b'A' => a & b,
+                    b'E' => a ^ b,
+                    _ => a | b,
+                });
+            }
+            b'G' => {
+                let x = context.ip.pop();
+                let y = context.ip.pop();
+                let value = context.get((x, y));
+                context.ip.push(value);
+            }
+            b'P' => {
+                let x = context.ip.pop();
+                let y = context.ip.pop();
+                let value = context.ip.pop();
+                if !context.put((x, y), value) {
+                    context.ip.reflect();
+                }
+            }
+            b'S' => {
+                let s = context.ip.pop_string();
+                for c in s.chars() {
+                    context.io.write_char(c)?;
+                }
+            }
+            b'V' => {
+                let dx = context.ip.pop();
+                let delta = context.ip.delta();
+                context.ip.set_delta(Delta::new(dx, delta.dy));
+            }
+            b'W' => {
+                let dy = context.ip.pop();
+                let delta = context.ip.delta();
+                context.ip.set_delta(Delta::new(delta.dx, dy));
+            }
+            b'X' => {
+                let x = context.ip.pop();
+                let (_, y) = context.ip.position();
+                context.ip.set_position(x, y);
+            }
+            b'Y' => {
+                let y = context.ip.pop();
+                let (x, _) = context.ip.position();
+                context.ip.set_position(x, y);
+            }
+            _ => {
+                if context.ip.pop() == 0 {
+                    context.move_ip();
+                }
+            }
+        }
+        Ok(())
+    }
+}
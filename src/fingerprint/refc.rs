@@ -0,0 +1,40 @@
+use std::io;
+
+use super::{Context, Fingerprint};
+
+/// `REFC`: turns vectors into single-cell references and back.
+#[derive(Clone, Debug, Default)]
+pub struct Refc {
+    vectors: Vec<(i32, i32)>,
+}
+
+impl Fingerprint for Refc {
+    fn name(&self) -> &str {
+        "REFC"
+    }
+
+    fn instructions(&self) -> &str {
+        "DR"
+    }
+
+    fn execute(&mut self, instruction: u8, context: &mut Context<'_>) -> io::Result<()> {
+        if instruction == b'R' {
+            let vector = context.ip.pop_vector();
+            let reference = match self.vectors.iter().position(|&v| v == vector) {
+                Some(index) => index,
+                None => {
+                    self.vectors.push(vector);
+                    self.vectors.len() - 1
+                }
+            };
+            context.ip.push(reference as i32);
+        } else {
+            let reference = context.ip.pop();
+            match self.vectors.get(reference as usize) {
+                Some(&vector) if reference >= 0 => context.ip.push_vector(vector),
+                _ => context.ip.reflect(),
+            }
+        }
+        Ok(())
+    }
+}
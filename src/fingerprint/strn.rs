@@ -0,0 +1,140 @@
+use std::cmp::Ordering;
+use std::io;
+
+use super::{Context, Fingerprint};
+
+/// `STRN`: operations on null-terminated strings on the stack. Counts out
+/// of range reflect.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Strn;
+
+/// The characters of `s` from `start`, at most `len` of them, or `None` if
+/// `start` lies beyond its end.
+fn substring(s: &str, start: i32, len: i32) -> Option<String> {
+    let count = s.chars().count();
+    if start < 0 || len < 0 || start as usize > count {
+        return None;
+    }
+
+    Some(s.chars().skip(start as usize).take(len as usize).collect())
+}
+
+impl Fingerprint for Strn {
+    fn name(&self) -> &str {
+        "STRN"
+    }
+
+    fn instructions(&self) -> &str {
+        "ACDFGILMNPRSV"
+    }
+
+    fn execute(&mut self, instruction: u8, context: &mut Context<'_>) -> io::Result<()> {
+        let ip = &mut *context.ip;
+
+        match instruction {
+            b'A' => {
+                let top = ip.pop_string();
+                let bottom = ip.pop_string();
+                ip.push_string(&(top + &bottom));
+            }
+            b'C' => {
+                let top = ip.pop_string();
+                let bottom = ip.pop_string();
+                ip.push(match top.cmp(&bottom) {
+                    Ordering::Less => -1,
+                    Ordering::Equal => 0,
+                    Ordering::Greater => 1,
+                });
+            }
+            b'D' => {
+                let s = ip.pop_string();
+                for c in s.chars() {
+                    context.io.write_char(c)?;
+                }
+            }
+            b'F' => {
+                let top = ip.pop_string();
+                let bottom = ip.pop_string();
+                let found = top.find(&bottom).map_or("", |i| &top[i..]);
+                ip.push_string(found);
+            }
+            b'G' => {
+                let (x, y) = ip.pop_vector();
+                let bounds = context.space.bounds();
+                let mut s = String::new();
+                let mut cx = x;
+                loop {
+                    let c = context.get((cx, y));
+                    if c == 0 {
+                        break;
+                    }
+                    if cx.wrapping_add(context.ip.storage_offset().0) > bounds.max_x {
+                        context.ip.reflect();
+                        return Ok(());
+                    }
+                    s.push(std::char::from_u32(c as u32).unwrap_or(char::REPLACEMENT_CHARACTER));
+                    cx = cx.wrapping_add(1);
+                }
+                context.ip.push_string(&s);
+            }
+            b'I' => {
+                let mut line = Vec::new();
+                while let Some(c) = context.io.read_char()? {
+                    if c == b'\n' {
+                        break;
+                    }
+                    line.push(c);
+                }
+                ip.push_string(String::from_utf8_lossy(&line).trim_end_matches('\r'));
+            }
+            b'L' | b'R' => {
+                let n = ip.pop();
+                let s = ip.pop_string();
+                let count = s.chars().count() as i32;
+                let start = if instruction == b'L' { 0 } else { count - n };
+                match substring(&s, start, n) {
+                    Some(part) if n <= count => ip.push_string(&part),
+                    _ => ip.reflect(),
+                }
+            }
+            b'M' => {
+                let n = ip.pop();
+                let start = ip.pop();
+                let s = ip.pop_string();
+                match substring(&s, start, n) {
+                    Some(part) => ip.push_string(&part),
+                    None => ip.reflect(),
+                }
+            }
+            b'N' => {
+                let s = ip.pop_string();
+                ip.push_string(&s);
+                ip.push(s.chars().count() as i32);
+            }
+            b'P' => {
+                let (x, y) = ip.pop_vector();
+                let s = ip.pop_string();
+                for (i, c) in s.chars().chain(Some('\0')).enumerate() {
+                    if !context.put((x.wrapping_add(i as i32), y), c as i32) {
+                        context.ip.reflect();
+                        break;
+                    }
+                }
+            }
+            b'S' => {
+                let n = ip.pop();
+                ip.push_string(&n.to_string());
+            }
+            _ => {
+                let s = ip.pop_string();
+                let s = s.trim_start();
+                let end = s
+                    .char_indices()
+                    .find(|&(i, c)| !(c.is_ascii_digit() || (i == 0 && (c == '-' || c == '+'))))
+                    .map_or(s.len(), |(i, _)| i);
+                ip.push(s[..end].parse::<i64>().map_or(0, |n| n as i32));
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,474 @@
+//! Checking programs against the output they are expected to produce.
+//!
+//! A test is a program with an `.out` file beside it holding its expected
+//! output, and optionally an `.in` file holding its input. The same can be
+//! given in a header of `;; key: value` lines at the top of the program,
+//! which is removed before the program is loaded:
+//!
+//! ```text
+//! ;; input: 5\n
+//! ;; output: pos
+//! ;; exit: 0
+//! ```
+//!
+//! The keys are `input`, `output`, `exit`, `seed`, and `width` and `height`
+//! for the size of a Befunge-93 field, 80 by 25 unless given. A program
+//! that does not fit its field fails. A value starts after
+//! the one space following the colon and runs to the end of the line, so
+//! trailing spaces, such as the one `.` writes, are part of it. Values may
+//! use `\n`, `\t` and `\\`. A header value wins over the file beside the
+//! program.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    BefungeExecution, BefungeField, Dialect, FungeSpace, Limits, LoadOptions, MemoryIo,
+    SparseSpace, StepOutcome,
+};
+
+/// The seed `?` uses in tests without a `seed` header, so they always
+/// take the same path.
+pub const DEFAULT_TEST_SEED: u64 = 0;
+
+/// The Befunge-93 field size in tests without `width` and `height`
+/// headers.
+pub const DEFAULT_TEST_SIZE: (usize, usize) = (80, 25);
+
+/// A program with the input it is given and the output it must produce.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GoldenTest {
+    pub program: PathBuf,
+    /// Funge-98 for `.b98` programs, Befunge-93 for any other.
+    pub dialect: Dialect,
+    /// The program with its header removed.
+    pub source: String,
+    pub input: Vec<u8>,
+    pub output: Vec<u8>,
+    pub exit_code: i32,
+    pub seed: u64,
+    /// The size of the Befunge-93 field the program is loaded into.
+    pub width: usize,
+    pub height: usize,
+}
+
+/// What running a `GoldenTest` produced.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestRun {
+    pub output: Vec<u8>,
+    /// The exit code, or why the program did not finish.
+    pub exit_code: Result<i32, String>,
+}
+
+fn invalid(program: &Path, message: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}: {}", program.display(), message),
+    )
+}
+
+fn dimension(program: &Path, key: &str, value: &str) -> io::Result<usize> {
+    match value.trim().parse::<usize>() {
+        Ok(0) => Err(invalid(
+            program,
+            format!("bad {}: must be greater than zero", key),
+        )),
+        Ok(n) => Ok(n),
+        Err(e) => Err(invalid(program, format!("bad {}: {}", key, e))),
+    }
+}
+
+fn unescape(value: &str) -> Result<String, String> {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some('\\') => unescaped.push('\\'),
+            Some(c) => return Err(format!("unknown escape `\\{}`", c)),
+            None => return Err(String::from("`\\` at the end of a value")),
+        }
+    }
+    Ok(unescaped)
+}
+
+/// Reads the file beside `program` with the given extension, if there is
+/// one.
+fn read_beside(program: &Path, extension: &str) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(program.with_extension(extension)) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+impl GoldenTest {
+    /// Loads the test for `program`, or `None` if neither a header nor an
+    /// `.out` file gives its expected output.
+    pub fn load(program: &Path) -> io::Result<Option<Self>> {
+        let contents = fs::read_to_string(program)?;
+        let mut input = None;
+        let mut output = None;
+        let mut exit_code = 0;
+        let mut seed = DEFAULT_TEST_SEED;
+        let (mut width, mut height) = DEFAULT_TEST_SIZE;
+
+        let mut source = contents.as_str();
+        while let Some(line) = source.lines().next().filter(|l| l.starts_with(";;")) {
+            source = source[line.len()..]
+                .strip_prefix("\r\n")
+                .or_else(|| source[line.len()..].strip_prefix('\n'))
+                .unwrap_or("");
+
+            let (key, value) = match line[2..].split_once(':') {
+                Some((key, value)) => (key.trim(), value.strip_prefix(' ').unwrap_or(value)),
+                None => return Err(invalid(program, format!("bad header line `{}`", line))),
+            };
+            let value = unescape(value).map_err(|e| invalid(program, e))?;
+            match key {
+                "input" => input = Some(value.into_bytes()),
+                "output" => output = Some(value.into_bytes()),
+                "exit" => {
+                    exit_code = value
+                        .trim()
+                        .parse()
+                        .map_err(|e| invalid(program, format!("bad exit code: {}", e)))?
+                }
+                "seed" => {
+                    seed = value
+                        .trim()
+                        .parse()
+                        .map_err(|e| invalid(program, format!("bad seed: {}", e)))?
+                }
+                "width" => width = dimension(program, key, &value)?,
+                "height" => height = dimension(program, key, &value)?,
+                _ => return Err(invalid(program, format!("unknown header key `{}`", key))),
+            }
+        }
+
+        let output = match output {
+            Some(output) => output,
+            None => match read_beside(program, "out")? {
+                Some(output) => output,
+                None => return Ok(None),
+            },
+        };
+        let input = match input {
+            Some(input) => input,
+            None => read_beside(program, "in")?.unwrap_or_default(),
+        };
+        let dialect = match program.extension() {
+            Some(extension) if extension == "b98" => Dialect::Funge98,
+            _ => Dialect::Befunge93,
+        };
+
+        Ok(Some(Self {
+            program: program.to_path_buf(),
+            dialect,
+            source: String::from(source),
+            input,
+            output,
+            exit_code,
+            seed,
+            width,
+            height,
+        }))
+    }
+
+    /// Runs the program on its input with in-memory I/O. A Befunge-93
+    /// program that does not fit its field fails without running.
+    pub fn run(&self, limits: Limits) -> TestRun {
+        let space: Box<dyn FungeSpace> = match self.dialect {
+            Dialect::Funge98 => Box::new(SparseSpace::<i32>::from_source(&self.source)),
+            _ => {
+                let options = LoadOptions::default().strict(true);
+                match BefungeField::<u8>::load(&self.source, self.width, self.height, options) {
+                    Ok((field, _)) => Box::new(field),
+                    Err(e) => {
+                        return TestRun {
+                            output: Vec::new(),
+                            exit_code: Err(e.to_string()),
+                        }
+                    }
+                }
+            }
+        };
+        let mut exec = BefungeExecution::builder(space)
+            .io(MemoryIo::from_input(self.input.clone()))
+            .dialect(self.dialect)
+            .args(vec![self.program.display().to_string()])
+            .seed(self.seed)
+            .limits(limits)
+            .build();
+
+        let exit_code = match exec.run() {
+            Ok(StepOutcome::Quit(code)) => Ok(code),
+            Ok(StepOutcome::LimitExceeded(limit)) => Err(format!("{} exceeded", limit)),
+            Ok(_) => Ok(0),
+            Err(e) => Err(e.to_string()),
+        };
+        TestRun {
+            output: exec.into_io().output().to_vec(),
+            exit_code,
+        }
+    }
+
+    pub fn passed(&self, run: &TestRun) -> bool {
+        run.output == self.output && run.exit_code == Ok(self.exit_code)
+    }
+
+    /// Writes how `run` differs from what was expected: the exit status,
+    /// then the output line by line, with missing lines marked `-` and
+    /// unexpected ones `+`.
+    pub fn write_diff<W: Write>(&self, mut writer: W, run: &TestRun) -> io::Result<()> {
+        match &run.exit_code {
+            Ok(code) if *code == self.exit_code => {}
+            Ok(code) => writeln!(
+                writer,
+                "exit status: expected {}, got {}",
+                self.exit_code, code
+            )?,
+            Err(e) => writeln!(writer, "error: {}", e)?,
+        }
+
+        if run.output == self.output {
+            return Ok(());
+        }
+        let expected = String::from_utf8_lossy(&self.output);
+        let actual = String::from_utf8_lossy(&run.output);
+        writeln!(writer, "output:")?;
+        for line in diff_lines(&expected, &actual) {
+            writeln!(writer, "{}", line)?;
+        }
+        if expected.lines().eq(actual.lines()) {
+            writeln!(writer, "(the output differs only in its final newline)")?;
+        }
+        Ok(())
+    }
+}
+
+/// The lines of `expected` and `actual`, prefixed with two spaces where
+/// they agree, `- ` where only `expected` has them and `+ ` where only
+/// `actual` does.
+fn diff_lines(expected: &str, actual: &str) -> Vec<String> {
+    let expected: Vec<_> = expected.lines().collect();
+    let actual: Vec<_> = actual.lines().collect();
+
+    // The length of the longest common subsequence of each pair of suffixes.
+    let mut common = vec![vec![0; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            common[i][j] = if expected[i] == actual[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            lines.push(format!("  {}", expected[i]));
+            i += 1;
+            j += 1;
+        } else if j == actual.len() || (i < expected.len() && common[i + 1][j] >= common[i][j + 1])
+        {
+            lines.push(format!("- {}", expected[i]));
+            i += 1;
+        } else {
+            lines.push(format!("+ {}", actual[j]));
+            j += 1;
+        }
+    }
+    lines
+}
+
+fn is_program(path: &Path) -> bool {
+    matches!(
+        path.extension().and_then(|extension| extension.to_str()),
+        Some("bf") | Some("b98")
+    )
+}
+
+/// The programs under `paths`, in order. Directories are searched
+/// recursively for `.bf` and `.b98` files; files are taken as they are.
+pub fn discover(paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut programs = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut entries = fs::read_dir(path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<io::Result<Vec<_>>>()?;
+            entries.sort();
+            for entry in entries {
+                if entry.is_dir() {
+                    programs.extend(discover(&[entry])?);
+                } else if is_program(&entry) {
+                    programs.push(entry);
+                }
+            }
+        } else {
+            programs.push(path.clone());
+        }
+    }
+    Ok(programs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    /// A fresh directory under the system's temporary directory.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("befuddle-golden-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_files_beside_program() {
+        let dir = scratch("files");
+        let program = dir.join("double.bf");
+        fs::write(&program, "&2*.@").unwrap();
+        fs::write(dir.join("double.in"), "21").unwrap();
+        fs::write(dir.join("double.out"), "42 ").unwrap();
+
+        let test = GoldenTest::load(&program).unwrap().unwrap();
+        assert_eq!(test.input, b"21");
+        assert_eq!(test.dialect, Dialect::Befunge93);
+        let run = test.run(Limits::default());
+        assert_eq!(run.output, b"42 ");
+        assert!(test.passed(&run));
+
+        fs::remove_file(dir.join("double.out")).unwrap();
+        assert_eq!(GoldenTest::load(&program).unwrap(), None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_header() {
+        let dir = scratch("header");
+        let program = dir.join("quit.b98");
+        fs::write(
+            &program,
+            ";; input: 7\\n\n;; output: 7 \\\\\n;; exit: 3\n&.\"\\\",3q",
+        )
+        .unwrap();
+
+        let test = GoldenTest::load(&program).unwrap().unwrap();
+        assert_eq!(test.source, "&.\"\\\",3q");
+        assert_eq!(test.input, b"7\n");
+        assert_eq!(test.output, b"7 \\");
+        assert_eq!(test.exit_code, 3);
+        assert_eq!(test.dialect, Dialect::Funge98);
+        assert!(test.passed(&test.run(Limits::default())));
+
+        // The space `.` writes after a number ends the value.
+        let program = dir.join("double.bf");
+        fs::write(&program, ";; input: 21\n;; output: 42 \n&2*.@").unwrap();
+        let test = GoldenTest::load(&program).unwrap().unwrap();
+        assert_eq!(test.output, b"42 ");
+        assert!(test.passed(&test.run(Limits::default())));
+
+        fs::write(&program, ";; colour: blue\n@").unwrap();
+        assert!(GoldenTest::load(&program).is_err());
+        fs::write(&program, ";; output: \n;; width: 0\n@").unwrap();
+        assert!(GoldenTest::load(&program).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_field_size() {
+        let dir = scratch("size");
+        let program = dir.join("wide.bf");
+        // The `@` is past the default width, so the program is cut off
+        // before it.
+        let source = format!("{}@", " ".repeat(80));
+        fs::write(&program, format!(";; output: \n{}", source)).unwrap();
+        let test = GoldenTest::load(&program).unwrap().unwrap();
+        assert_eq!((test.width, test.height), (80, 25));
+        let run = test.run(Limits::default());
+        assert!(!test.passed(&run));
+        assert_eq!(
+            run.exit_code,
+            Err(String::from(
+                "the program does not fit the field\n  \
+                 row 0 is cut off at the field's width of 80 cells"
+            ))
+        );
+
+        fs::write(
+            &program,
+            format!(";; output: \n;; width: 81\n;; height: 1\n{}", source),
+        )
+        .unwrap();
+        let test = GoldenTest::load(&program).unwrap().unwrap();
+        assert_eq!((test.width, test.height), (81, 1));
+        assert!(test.passed(&test.run(Limits::default())));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_diff() {
+        let test = GoldenTest {
+            program: PathBuf::from("test.bf"),
+            dialect: Dialect::Befunge93,
+            source: String::new(),
+            input: Vec::new(),
+            output: b"one\ntwo\nthree\n".to_vec(),
+            exit_code: 0,
+            seed: DEFAULT_TEST_SEED,
+            width: 80,
+            height: 25,
+        };
+        let run = TestRun {
+            output: b"one\n2\nthree\nfour\n".to_vec(),
+            exit_code: Ok(1),
+        };
+        assert!(!test.passed(&run));
+
+        let mut diff = Vec::new();
+        test.write_diff(&mut diff, &run).unwrap();
+        assert_eq!(
+            String::from_utf8(diff).unwrap(),
+            "exit status: expected 0, got 1\n\
+             output:\n  one\n- two\n+ 2\n  three\n+ four\n"
+        );
+
+        let run = TestRun {
+            output: b"one\ntwo\nthree".to_vec(),
+            exit_code: Err(String::from("limit of 10 steps exceeded")),
+        };
+        let mut diff = Vec::new();
+        test.write_diff(&mut diff, &run).unwrap();
+        let diff = String::from_utf8(diff).unwrap();
+        assert!(diff.starts_with("error: limit of 10 steps exceeded\n"));
+        assert!(diff.ends_with("final newline)\n"));
+    }
+
+    #[test]
+    fn test_discover() {
+        let dir = scratch("discover");
+        fs::create_dir(dir.join("nested")).unwrap();
+        for name in &["b.bf", "a.b98", "a.out", "nested/c.bf", "notes.txt"] {
+            fs::write(dir.join(name), "@").unwrap();
+        }
+
+        let programs = discover(std::slice::from_ref(&dir)).unwrap();
+        assert_eq!(
+            programs,
+            vec![dir.join("a.b98"), dir.join("b.bf"), dir.join("nested/c.bf")]
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
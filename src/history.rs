@@ -0,0 +1,362 @@
+//! The undo log behind `BefungeExecution::step_back`.
+
+use std::collections::VecDeque;
+use std::io;
+use std::mem;
+
+use crate::{Bounds, Delta, FungeIo, FungeRng, FungeSpace, InstructionPointer};
+
+/// A value a step read from its input.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Input {
+    Int(Option<i32>),
+    Char(Option<u8>),
+}
+
+/// How to put back the IPs a step changed.
+#[derive(Clone, Debug)]
+enum Ips {
+    /// The IP at `index` took a plain turn: only its registers and the top
+    /// of its TOSS changed. `popped` holds the values it popped from below
+    /// `low`, the shallowest its TOSS got, top first.
+    Turn {
+        index: usize,
+        x: i32,
+        y: i32,
+        delta: Delta,
+        string_mode: bool,
+        low: usize,
+        popped: Vec<i32>,
+    },
+    /// Every IP as it was, for instructions that reshape the stack stack,
+    /// load fingerprints or add and remove IPs.
+    All(Vec<InstructionPointer>),
+}
+
+/// The cells a step wrote, so they can be put back.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Writes {
+    /// The bounds of the space before the first write.
+    bounds: Option<Bounds>,
+    /// The previous value of each cell written, in order.
+    cells: Vec<(i32, i32, i32)>,
+}
+
+impl Writes {
+    /// Notes that `(x, y)` is about to be written.
+    pub fn write<S: FungeSpace + ?Sized>(&mut self, space: &S, x: i32, y: i32) {
+        if self.bounds.is_none() {
+            self.bounds = Some(space.bounds());
+        }
+        self.cells.push((x, y, space.cell(x, y)));
+    }
+
+    /// Adds the writes in `later`, which came after these.
+    pub fn append(&mut self, later: Writes) {
+        if self.bounds.is_none() {
+            self.bounds = later.bounds;
+        }
+        self.cells.extend(later.cells);
+    }
+
+    fn undo<S: FungeSpace + ?Sized>(&self, space: &mut S) {
+        for &(x, y, value) in self.cells.iter().rev() {
+            space.set_cell(x, y, value);
+        }
+        if let Some(bounds) = self.bounds {
+            space.restore_bounds(bounds);
+        }
+    }
+}
+
+/// Everything needed to undo one step.
+#[derive(Clone, Debug)]
+pub(crate) struct Undo {
+    pub current: usize,
+    pub next_ip_id: i32,
+    pub active: bool,
+    pub exit_code: Option<i32>,
+    pub rng: FungeRng,
+    ips: Ips,
+    pub writes: Writes,
+    input: Vec<Input>,
+    output: String,
+}
+
+impl Undo {
+    /// A record of the state before `ips[current]` takes its turn.
+    pub fn new(
+        ips: &[InstructionPointer],
+        current: usize,
+        next_ip_id: i32,
+        active: bool,
+        exit_code: Option<i32>,
+        rng: &FungeRng,
+    ) -> Self {
+        let ip = &ips[current];
+        Self {
+            current,
+            next_ip_id,
+            active,
+            exit_code,
+            rng: rng.clone(),
+            ips: Ips::Turn {
+                index: current,
+                x: ip.x,
+                y: ip.y,
+                delta: ip.delta,
+                string_mode: ip.string_mode,
+                low: ip.stacks.toss().len(),
+                popped: Vec::new(),
+            },
+            writes: Writes::default(),
+            input: Vec::new(),
+            output: String::new(),
+        }
+    }
+
+    /// Switches to keeping a copy of every IP, taken before the turn began.
+    pub fn snapshot(&mut self, ips: &[InstructionPointer]) {
+        if let Ips::Turn { index, x, y, .. } = self.ips {
+            let mut copy = ips.to_vec();
+            copy[index].x = x;
+            copy[index].y = y;
+            self.ips = Ips::All(copy);
+        }
+    }
+
+    /// Notes that the value on top of `toss` is about to be popped.
+    pub fn pop(&mut self, toss: &[i32]) {
+        if let Ips::Turn { low, popped, .. } = &mut self.ips {
+            if toss.len() == *low && *low > 0 {
+                *low -= 1;
+                popped.push(toss[*low]);
+            }
+        }
+    }
+
+    /// Puts the IPs and cells back as they were. The caller restores the
+    /// scalar fields.
+    pub fn apply<S: FungeSpace + ?Sized>(self, ips: &mut Vec<InstructionPointer>, space: &mut S) {
+        match self.ips {
+            Ips::Turn {
+                index,
+                x,
+                y,
+                delta,
+                string_mode,
+                low,
+                popped,
+            } => {
+                let ip = &mut ips[index];
+                ip.x = x;
+                ip.y = y;
+                ip.delta = delta;
+                ip.string_mode = string_mode;
+                let toss = ip.stacks.toss_mut();
+                toss.truncate(low);
+                toss.extend(popped.iter().rev());
+            }
+            Ips::All(all) => *ips = all,
+        }
+        self.writes.undo(space);
+    }
+
+    /// A rough count of the bytes the record takes up.
+    fn size(&self) -> usize {
+        let ips = match &self.ips {
+            Ips::Turn { popped, .. } => popped.len() * mem::size_of::<i32>(),
+            Ips::All(all) => all
+                .iter()
+                .map(|ip| {
+                    mem::size_of::<InstructionPointer>()
+                        + ip.stacks.sizes().iter().sum::<usize>() * mem::size_of::<i32>()
+                        + ip.semantics.iter().map(Vec::len).sum::<usize>() * mem::size_of::<usize>()
+                })
+                .sum(),
+        };
+
+        mem::size_of::<Self>()
+            + ips
+            + self.writes.cells.len() * mem::size_of::<(i32, i32, i32)>()
+            + self.input.len() * mem::size_of::<Input>()
+            + self.output.len()
+    }
+}
+
+/// The undo records of the latest steps, kept within a memory budget by
+/// forgetting the oldest.
+#[derive(Clone, Debug)]
+pub(crate) struct History {
+    undos: VecDeque<Undo>,
+    budget: usize,
+    /// The size of every record but the last, which is still growing.
+    used: usize,
+    /// Input read by undone steps, handed out again when they are redone.
+    replay: VecDeque<Input>,
+    /// Output written by undone steps, which redoing them does not write
+    /// again.
+    rewritten: String,
+}
+
+impl History {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            undos: VecDeque::new(),
+            budget,
+            used: 0,
+            replay: VecDeque::new(),
+            rewritten: String::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.undos.len()
+    }
+
+    /// Forgets every record, keeping the budget.
+    pub fn clear(&mut self) {
+        *self = Self::new(self.budget);
+    }
+
+    /// Output that undone steps wrote and redoing them will not write again.
+    pub fn rewritten(&self) -> &str {
+        &self.rewritten
+    }
+
+    /// Starts recording a step.
+    pub fn begin(&mut self, undo: Undo) {
+        if let Some(last) = self.undos.back() {
+            self.used += last.size();
+        }
+        self.undos.push_back(undo);
+
+        while self.used > self.budget && self.undos.len() > 1 {
+            let oldest = self.undos.pop_front().expect("history is not empty");
+            self.used -= oldest.size();
+        }
+    }
+
+    /// The record of the step being executed.
+    pub fn last_mut(&mut self) -> Option<&mut Undo> {
+        self.undos.back_mut()
+    }
+
+    /// Takes the record of the latest step, queueing its input and output
+    /// to be replayed when it is redone.
+    pub fn undo(&mut self) -> Option<Undo> {
+        let mut undo = self.undos.pop_back()?;
+        if let Some(last) = self.undos.back() {
+            self.used -= last.size();
+        }
+
+        for &input in undo.input.iter().rev() {
+            self.replay.push_front(input);
+        }
+        self.rewritten.insert_str(0, &undo.output);
+        undo.input.clear();
+        undo.output.clear();
+        Some(undo)
+    }
+
+    fn next_input(&mut self, read: impl Fn(Input) -> bool) -> Option<Input> {
+        match self.replay.front() {
+            Some(&input) if read(input) => self.replay.pop_front(),
+            Some(_) => {
+                // The program went another way; what it read before no
+                // longer applies.
+                self.replay.clear();
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn record_input(&mut self, input: Input) {
+        if let Some(undo) = self.undos.back_mut() {
+            undo.input.push(input);
+        }
+    }
+
+    /// Records `text` as written, returning whether it still has to be.
+    fn write(&mut self, text: &str) -> bool {
+        if let Some(undo) = self.undos.back_mut() {
+            undo.output.push_str(text);
+        }
+
+        if self.rewritten.starts_with(text) {
+            self.rewritten.drain(..text.len());
+            false
+        } else {
+            self.rewritten.clear();
+            true
+        }
+    }
+}
+
+/// `FungeIo` that logs what a step reads and writes to the history, if
+/// there is one, and replays what undone steps read.
+pub(crate) struct LoggedIo<'a, I: ?Sized> {
+    pub io: &'a mut I,
+    pub history: Option<&'a mut History>,
+}
+
+impl<I: FungeIo + ?Sized> LoggedIo<'_, I> {
+    fn write(
+        &mut self,
+        text: &str,
+        write: impl FnOnce(&mut I) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let needed = match &mut self.history {
+            Some(history) => history.write(text),
+            None => true,
+        };
+        if needed {
+            write(self.io)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<I: FungeIo + ?Sized> FungeIo for LoggedIo<'_, I> {
+    fn read_int(&mut self) -> io::Result<Option<i32>> {
+        let history = match &mut self.history {
+            Some(history) => history,
+            None => return self.io.read_int(),
+        };
+
+        let value = match history.next_input(|i| matches!(i, Input::Int(_))) {
+            Some(Input::Int(value)) => value,
+            _ => self.io.read_int()?,
+        };
+        history.record_input(Input::Int(value));
+        Ok(value)
+    }
+
+    fn read_char(&mut self) -> io::Result<Option<u8>> {
+        let history = match &mut self.history {
+            Some(history) => history,
+            None => return self.io.read_char(),
+        };
+
+        let value = match history.next_input(|i| matches!(i, Input::Char(_))) {
+            Some(Input::Char(value)) => value,
+            _ => self.io.read_char()?,
+        };
+        history.record_input(Input::Char(value));
+        Ok(value)
+    }
+
+    fn write_int(&mut self, value: i32) -> io::Result<()> {
+        self.write(&value.to_string(), |io| io.write_int(value))
+    }
+
+    fn write_char(&mut self, value: char) -> io::Result<()> {
+        self.write(value.encode_utf8(&mut [0; 4]), |io| io.write_char(value))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.io.flush()
+    }
+}
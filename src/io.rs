@@ -0,0 +1,227 @@
+use std::io::{self, BufRead, BufReader, Cursor, Stdin, Stdout, Write};
+
+/// Input and output used by the `&`, `~`, `.` and `,` instructions.
+pub trait FungeIo {
+    /// Reads a decimal integer, returning `None` at end of input.
+    fn read_int(&mut self) -> io::Result<Option<i32>>;
+
+    /// Reads a single byte, returning `None` at end of input.
+    fn read_char(&mut self) -> io::Result<Option<u8>>;
+
+    fn write_int(&mut self, value: i32) -> io::Result<()>;
+
+    fn write_char(&mut self, value: char) -> io::Result<()>;
+
+    fn flush(&mut self) -> io::Result<()>;
+
+    /// The number of input bytes consumed so far. I/O that does not keep
+    /// count reports 0.
+    fn input_position(&self) -> u64 {
+        0
+    }
+
+    /// Discards input until `input_position` reaches `position`, to pick up
+    /// where a snapshot left off. I/O that does not keep count ignores it.
+    fn skip_input(&mut self, _position: u64) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A `FungeIo` over any buffered reader and writer.
+#[derive(Clone, Debug)]
+pub struct StreamIo<R, W> {
+    reader: R,
+    writer: W,
+    /// The number of bytes consumed from `reader`.
+    position: u64,
+}
+
+/// Standard input and standard output.
+pub type StdIo = StreamIo<BufReader<Stdin>, Stdout>;
+
+/// In-memory input and output, for tests and embedding.
+pub type MemoryIo = StreamIo<Cursor<Vec<u8>>, Vec<u8>>;
+
+impl<R, W> StreamIo<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Self {
+            reader,
+            writer,
+            position: 0,
+        }
+    }
+
+    pub fn reader(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn writer(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn into_inner(self) -> (R, W) {
+        (self.reader, self.writer)
+    }
+}
+
+impl Default for StdIo {
+    fn default() -> Self {
+        Self::new(BufReader::new(io::stdin()), io::stdout())
+    }
+}
+
+impl MemoryIo {
+    pub fn from_input<T: Into<Vec<u8>>>(input: T) -> Self {
+        Self::new(Cursor::new(input.into()), Vec::new())
+    }
+
+    pub fn output(&self) -> &[u8] {
+        &self.writer
+    }
+
+    pub fn output_string(&self) -> String {
+        String::from_utf8_lossy(&self.writer).into_owned()
+    }
+}
+
+impl Default for MemoryIo {
+    fn default() -> Self {
+        Self::from_input(Vec::new())
+    }
+}
+
+impl<R: BufRead, W> StreamIo<R, W> {
+    fn peek(&mut self) -> io::Result<Option<u8>> {
+        Ok(self.reader.fill_buf()?.first().copied())
+    }
+
+    fn consume(&mut self, n: usize) {
+        self.reader.consume(n);
+        self.position += n as u64;
+    }
+}
+
+impl<R: BufRead, W: Write> FungeIo for StreamIo<R, W> {
+    fn read_int(&mut self) -> io::Result<Option<i32>> {
+        let mut negative = false;
+
+        loop {
+            match self.peek()? {
+                None => return Ok(None),
+                Some(b) if b.is_ascii_digit() => break,
+                Some(b) => {
+                    negative = b == b'-';
+                    self.consume(1);
+                }
+            }
+        }
+
+        let mut value: i32 = 0;
+        while let Some(b) = self.peek()? {
+            if !b.is_ascii_digit() {
+                break;
+            }
+            value = value
+                .checked_mul(10)
+                .and_then(|v| v.checked_add((b - b'0') as i32))
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "integer overflow"))?;
+            self.consume(1);
+        }
+
+        if self.peek()? == Some(b'\r') {
+            self.consume(1);
+        }
+        if self.peek()? == Some(b'\n') {
+            self.consume(1);
+        }
+
+        Ok(Some(if negative { -value } else { value }))
+    }
+
+    fn read_char(&mut self) -> io::Result<Option<u8>> {
+        let c = self.peek()?;
+        if c.is_some() {
+            self.consume(1);
+        }
+
+        Ok(c)
+    }
+
+    fn write_int(&mut self, value: i32) -> io::Result<()> {
+        write!(self.writer, "{}", value)
+    }
+
+    fn write_char(&mut self, value: char) -> io::Result<()> {
+        write!(self.writer, "{}", value)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    fn input_position(&self) -> u64 {
+        self.position
+    }
+
+    fn skip_input(&mut self, position: u64) -> io::Result<()> {
+        while self.position < position {
+            let available = self.reader.fill_buf()?.len();
+            if available == 0 {
+                break;
+            }
+            let remaining = position - self.position;
+            self.consume(available.min(remaining as usize));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_int() {
+        let mut io = MemoryIo::from_input("12\n-34 x5");
+        assert_eq!(io.read_int().unwrap(), Some(12));
+        assert_eq!(io.read_int().unwrap(), Some(-34));
+        assert_eq!(io.read_int().unwrap(), Some(5));
+        assert_eq!(io.read_int().unwrap(), None);
+    }
+
+    #[test]
+    fn test_read_int_consumes_newline() {
+        let mut io = MemoryIo::from_input("7\nab");
+        assert_eq!(io.read_int().unwrap(), Some(7));
+        assert_eq!(io.read_char().unwrap(), Some(b'a'));
+    }
+
+    #[test]
+    fn test_read_char() {
+        let mut io = MemoryIo::from_input("a\n");
+        assert_eq!(io.read_char().unwrap(), Some(b'a'));
+        assert_eq!(io.read_char().unwrap(), Some(b'\n'));
+        assert_eq!(io.read_char().unwrap(), None);
+    }
+
+    #[test]
+    fn test_write() {
+        let mut io = MemoryIo::default();
+        io.write_int(-3).unwrap();
+        io.write_char('a').unwrap();
+        assert_eq!(io.output_string(), "-3a");
+    }
+
+    #[test]
+    fn test_input_position() {
+        let mut io = MemoryIo::from_input("12\nab");
+        assert_eq!(io.read_int().unwrap(), Some(12));
+        assert_eq!(io.input_position(), 3);
+
+        let mut resumed = MemoryIo::from_input("12\nab");
+        resumed.skip_input(io.input_position()).unwrap();
+        assert_eq!(resumed.read_char().unwrap(), Some(b'a'));
+        resumed.skip_input(100).unwrap();
+        assert_eq!(resumed.input_position(), 5);
+    }
+}
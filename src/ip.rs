@@ -0,0 +1,142 @@
+use std::convert::TryFrom;
+
+use crate::{Delta, StackStack};
+
+/// One thread of execution through Funge-Space.
+///
+/// Befunge-93 programs only ever have one; Funge-98's `t` splits off more,
+/// each with its own position, delta, stacks and string mode.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InstructionPointer {
+    pub(crate) id: i32,
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) delta: Delta,
+    pub(crate) string_mode: bool,
+    pub(crate) stacks: StackStack,
+    pub(crate) storage_offset: (i32, i32),
+    /// For each of `A` to `Z`, the stack of loaded fingerprints defining
+    /// it, as indices into the execution's registry.
+    pub(crate) semantics: Vec<Vec<usize>>,
+}
+
+impl InstructionPointer {
+    pub(crate) fn new(id: i32) -> Self {
+        Self {
+            id,
+            x: 0,
+            y: 0,
+            delta: Delta::RIGHT,
+            string_mode: false,
+            stacks: StackStack::new(),
+            storage_offset: (0, 0),
+            semantics: vec![Vec::new(); 26],
+        }
+    }
+
+    /// The identifier reported by `y`, unique among the IPs of an execution.
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn position(&self) -> (i32, i32) {
+        (self.x, self.y)
+    }
+
+    pub fn set_position(&mut self, x: i32, y: i32) {
+        self.x = x;
+        self.y = y;
+    }
+
+    pub fn delta(&self) -> Delta {
+        self.delta
+    }
+
+    pub fn set_delta(&mut self, delta: Delta) {
+        self.delta = delta;
+    }
+
+    pub fn reflect(&mut self) {
+        self.delta = self.delta.reverse();
+    }
+
+    pub fn string_mode(&self) -> bool {
+        self.string_mode
+    }
+
+    /// The top of the stack stack.
+    pub fn stack(&self) -> &[i32] {
+        self.stacks.toss()
+    }
+
+    pub fn stacks(&self) -> &StackStack {
+        &self.stacks
+    }
+
+    pub fn stacks_mut(&mut self) -> &mut StackStack {
+        &mut self.stacks
+    }
+
+    pub fn storage_offset(&self) -> (i32, i32) {
+        self.storage_offset
+    }
+
+    pub fn push(&mut self, value: i32) {
+        self.stacks.push(value);
+    }
+
+    pub fn pop(&mut self) -> i32 {
+        self.stacks.pop()
+    }
+
+    pub fn push_vector(&mut self, (x, y): (i32, i32)) {
+        self.push(x);
+        self.push(y);
+    }
+
+    pub fn pop_vector(&mut self) -> (i32, i32) {
+        let y = self.pop();
+        let x = self.pop();
+        (x, y)
+    }
+
+    /// Pushes `s` as a null-terminated string that pops in order.
+    pub fn push_string(&mut self, s: &str) {
+        self.push(0);
+        for c in s.chars().rev() {
+            self.push(c as i32);
+        }
+    }
+
+    /// Pops a null-terminated string. Cells that are not characters become
+    /// U+FFFD.
+    pub fn pop_string(&mut self) -> String {
+        let mut s = String::new();
+        loop {
+            match self.pop() {
+                0 => return s,
+                c => s.push(
+                    u32::try_from(c)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .unwrap_or(char::REPLACEMENT_CHARACTER),
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strings() {
+        let mut ip = InstructionPointer::new(0);
+        ip.push(7);
+        ip.push_string("abc");
+        assert_eq!(ip.stack(), &[7, 0, 99, 98, 97]);
+        assert_eq!(ip.pop_string(), "abc");
+        assert_eq!(ip.stack(), &[7]);
+    }
+}
@@ -1,15 +1,60 @@
-use std::convert::{From, TryFrom, TryInto};
-use std::io;
-use std::fs;
-use std::path::PathBuf;
-use structopt::StructOpt;
-
-#[derive(Debug, StructOpt)]
-struct ExecOptions {
-    input_file: PathBuf,
-    output_file: PathBuf,
-    program: PathBuf,
-}
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::env;
+use std::io::{Read, Write};
+use std::mem;
+use std::path::MAIN_SEPARATOR;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+mod breakpoint;
+mod cell;
+pub mod cfg;
+mod cli;
+pub mod compile;
+mod coverage;
+mod error;
+mod fast;
+pub mod fingerprint;
+pub mod golden;
+mod history;
+mod io;
+mod ip;
+mod limits;
+pub mod lint;
+mod load;
+mod profile;
+mod rng;
+mod snapshot;
+mod space;
+mod stack;
+mod sysinfo;
+mod trace;
+pub mod tui;
+
+pub use crate::breakpoint::{Breakpoint, Watch};
+pub use crate::cell::FungeCell;
+pub use crate::cli::{
+    read_program, CellType, CfgOptions, CliIo, CliOptions, Command, CompileOptions,
+    CoverageOptions, ExecOptions, FieldOptions, LintOptions, TestOptions,
+};
+pub use crate::coverage::{Coverage, CoverageSummary, RowCoverage};
+pub use crate::error::{BefungeError, BefungeErrorKind};
+pub use crate::fingerprint::{Context, Fingerprint, FingerprintRegistry};
+pub use crate::io::{FungeIo, MemoryIo, StdIo, StreamIo};
+pub use crate::ip::InstructionPointer;
+pub use crate::limits::{Limit, Limits};
+pub use crate::load::{LoadError, LoadIssue, LoadOptions, LoadReport};
+pub use crate::profile::{Profile, HEAT_COLORS, HEAT_LEVELS};
+pub use crate::rng::FungeRng;
+pub use crate::snapshot::{IpSnapshot, Snapshot, SnapshotError, SnapshotFormat, SNAPSHOT_VERSION};
+pub use crate::space::{Bounds, FungeSpace, SparseSpace};
+pub use crate::stack::StackStack;
+pub use crate::trace::{TraceEvent, TraceFormat, TraceWriter, Tracer};
+
+use crate::fingerprint::fingerprint_id;
+use crate::history::{History, LoggedIo, Undo, Writes};
 
 #[derive(Clone, Copy, Debug)]
 struct BefungeCommand;
@@ -42,54 +87,105 @@ impl BefungeCommand {
     const DOWN: u8 = b'v';
     const IF_UP_DOWN: u8 = b'|';
     const READ_CHAR: u8 = b'~';
+
+    // Funge-98
+    const FETCH_CHAR: u8 = b'\'';
+    const LOAD_SEMANTICS: u8 = b'(';
+    const UNLOAD_SEMANTICS: u8 = b')';
+    const JUMP_OVER: u8 = b';';
+    const TURN_LEFT: u8 = b'[';
+    const TURN_RIGHT: u8 = b']';
+    const JUMP: u8 = b'j';
+    const ITERATE: u8 = b'k';
+    const CLEAR_STACK: u8 = b'n';
+    const QUIT: u8 = b'q';
+    const REFLECT: u8 = b'r';
+    const STORE_CHAR: u8 = b's';
+    const SPLIT: u8 = b't';
+    const STACK_UNDER_STACK: u8 = b'u';
+    const COMPARE_TURN: u8 = b'w';
+    const ABSOLUTE_DELTA: u8 = b'x';
+    const SYSINFO: u8 = b'y';
+    const NOP: u8 = b'z';
+    const BEGIN_BLOCK: u8 = b'{';
+    const END_BLOCK: u8 = b'}';
 }
 
-type BefungeCell = u8;
+/// The cell type of a strict Befunge-93 playfield.
+pub type BefungeCell = u8;
 
+/// The fixed-size Befunge-93 playfield, whose edges wrap around as a torus.
 #[derive(Clone, Debug)]
-pub struct BefungeField {
+pub struct BefungeField<C = BefungeCell> {
     width: usize,
     height: usize,
-    cells: Vec<BefungeCell>,
+    cells: Vec<C>,
 }
 
 impl BefungeField {
     pub fn new(width: usize, height: usize) -> Self {
+        Self::blank(width, height)
+    }
+
+    pub fn from_str(input: &str, width: usize, height: usize) -> Self {
+        Self::from_source(input, width, height)
+    }
+}
+
+impl<C: FungeCell> BefungeField<C> {
+    /// A field of `width * height` spaces holding cells of type `C`.
+    pub fn blank(width: usize, height: usize) -> Self {
         Self {
             width,
             height,
-            cells: vec![BefungeCommand::NO_OP as u8; width * height],
+            cells: vec![C::SPACE; width * height],
         }
     }
 
     fn load_str(&mut self, input: &str) {
-        let mut y = 0;
-        for line in input.lines() {
-            if y >= self.height {
-                break;
-            }
-
-            let mut x = 0;
+        for (y, line) in input.lines().take(self.height).enumerate() {
             let y_offset = y * self.width;
 
-            for c in line.chars() {
-                if x >= self.width || c.len_utf8() > 1 {
+            for (x, c) in line.chars().take(self.width).enumerate() {
+                if c.len_utf8() > 1 {
                     break;
                 }
-                self.cells[x + y_offset] = c as u8;
-                x += 1;
+                self.cells[x + y_offset] = C::from_value(c as i32);
             }
-            y += 1;
         }
     }
 
-    pub fn from_str(input: &str, width: usize, height: usize) -> Self {
-        let mut field = BefungeField::new(width, height);
+    /// Loads a program into a field holding cells of type `C`.
+    pub fn from_source(input: &str, width: usize, height: usize) -> Self {
+        let mut field = Self::blank(width, height);
         field.load_str(input);
 
         field
     }
 
+    /// Loads a program like `from_source`, reporting every part of it the
+    /// field leaves out. With `options.strict`, leaving anything out is an
+    /// error; with `options.auto_size`, the field grows to fit the program
+    /// rather than cutting it off.
+    pub fn load(
+        input: &str,
+        width: usize,
+        height: usize,
+        options: LoadOptions,
+    ) -> Result<(Self, LoadReport), LoadError> {
+        let (width, height) = if options.auto_size {
+            load::fitted_size(input, width, height)
+        } else {
+            (width, height)
+        };
+        let report = load::check(input, width, height);
+        if options.strict && !report.is_clean() {
+            return Err(LoadError { report });
+        }
+
+        Ok((Self::from_source(input, width, height), report))
+    }
+
     pub fn width(&self) -> usize {
         self.width
     }
@@ -98,7 +194,7 @@ impl BefungeField {
         self.height
     }
 
-    pub fn get(&self, x: usize, y: usize) -> Option<BefungeCell> {
+    pub fn get(&self, x: usize, y: usize) -> Option<C> {
         if x < self.width && y < self.height {
             Some(self.cells[x + y * self.width])
         } else {
@@ -106,550 +202,2695 @@ impl BefungeField {
         }
     }
 
-    pub fn set(&mut self, x: usize, y: usize, value: u8) {
+    pub fn set(&mut self, x: usize, y: usize, value: C) {
         if x < self.width && y < self.height {
             self.cells[x + y * self.width] = value;
         }
     }
-}
-
-#[derive(Clone, Copy, Debug, PartialEq)]
-enum Delta {
-    Right,
-    Left,
-    Down,
-    Up,
-}
-
-#[derive(Clone, Debug)]
-pub struct BefungeExecution {
-    pc_x: usize,
-    pc_y: usize,
-    pc_delta: Delta,
-    string_mode: bool,
-    field: BefungeField,
-    stack: Vec<i32>,
-    active: bool,
-}
 
-impl BefungeExecution {
-    pub fn new(field: BefungeField) -> Self {
-        Self {
-            pc_x: 0,
-            pc_y: 0,
-            pc_delta: Delta::Right,
-            string_mode: false,
-            field,
-            stack: Vec::new(),
-            active: true,
+    fn index(&self, x: i32, y: i32) -> Option<(usize, usize)> {
+        match (usize::try_from(x), usize::try_from(y)) {
+            (Ok(x), Ok(y)) if x < self.width && y < self.height => Some((x, y)),
+            _ => None,
         }
     }
+}
 
-    fn pc(&self) -> (usize, usize, Delta) {
-        (self.pc_x, self.pc_y, self.pc_delta)
+impl<C: FungeCell> FungeSpace for BefungeField<C> {
+    fn cell(&self, x: i32, y: i32) -> i32 {
+        self.index(x, y)
+            .and_then(|(x, y)| self.get(x, y))
+            .unwrap_or(C::SPACE)
+            .to_value()
     }
 
-    pub fn stack(&self) -> Vec<i32> {
-        self.stack.clone()
+    fn set_cell(&mut self, x: i32, y: i32, value: i32) -> bool {
+        match self.index(x, y) {
+            Some((x, y)) => {
+                self.set(x, y, C::from_value(value));
+                true
+            }
+            None => false,
+        }
     }
-
-    fn get(&self, x: usize, y: usize) -> Option<BefungeCell> {
-        self.field.get(x, y)
+    fn contains(&self, x: i32, y: i32) -> bool {
+        self.index(x, y).is_some()
     }
 
-    pub fn move_pc(&mut self) {
-        match self.pc_delta {
-            Delta::Right => {
-                self.pc_x = if self.pc_x < self.field.width() - 1 {
-                    self.pc_x + 1
-                } else {
-                    0
-                }
-            }
-            Delta::Left => {
-                self.pc_x = if self.pc_x > 0 {
-                    self.pc_x - 1
-                } else {
-                    self.field.width() - 1
-                }
-            }
-            Delta::Down => {
-                self.pc_y = if self.pc_y < self.field.height() - 1 {
-                    self.pc_y + 1
-                } else {
-                    0
-                }
-            }
-            Delta::Up => {
-                self.pc_y = if self.pc_y > 0 {
-                    self.pc_y - 1
-                } else {
-                    self.field.height() - 1
-                }
-            }
+    fn bounds(&self) -> Bounds {
+        Bounds {
+            min_x: 0,
+            min_y: 0,
+            max_x: self.width as i32 - 1,
+            max_y: self.height as i32 - 1,
         }
     }
 
-    pub fn run(&mut self) {
-        while self.active {
-            self.step();
-        }
+    fn advance(&self, x: i32, y: i32, delta: Delta, distance: i64) -> (i32, i32) {
+        let wrap = |pos: i32, d: i32, size: usize| {
+            (pos as i64 + d as i64 * distance).rem_euclid(size as i64) as i32
+        };
+
+        (
+            wrap(x, delta.dx, self.width),
+            wrap(y, delta.dy, self.height),
+        )
     }
 
-    pub fn step(&mut self) {
-        if self.active {
-            if let Some(curr) = self.field.get(self.pc_x, self.pc_y) {
-                if self.string_mode {
-                    if curr == BefungeCommand::TOGGLE_STRING_MODE {
-                        self.string_mode = false;
-                    } else {
-                        self.stack.push(curr as i32);
-                    }
-                } else {
-                    match curr {
-                        BefungeCommand::NO_OP => {}
-                        BefungeCommand::NEGATE => {
-                            let top = self.stack.pop().unwrap_or_default();
-
-                            self.stack.push(if top > 0 { 0 } else { 1 })
-                        }
-                        BefungeCommand::TOGGLE_STRING_MODE => self.string_mode = true,
-                        BefungeCommand::BRIDGE => {
-                            self.move_pc();
-                        }
-                        BefungeCommand::DISCARD => {
-                            let _top = self.stack.pop();
-                        }
-                        BefungeCommand::MODULO => {
-                            let top = self.stack.pop().unwrap_or_default();
-                            let second = self.stack.pop().unwrap_or_default();
-
-                            self.stack.push(top % second);
-                        }
-                        BefungeCommand::READ_INT => {
-                            let mut input = String::new();
-                            io::stdin()
-                                .read_line(&mut input)
-                                .expect("Error reading integer");
-
-                            let i = input.parse::<i32>().unwrap();
-                            self.stack.push(i);
-                        }
-                        BefungeCommand::MULTIPLY => {
-                            let top = self.stack.pop().unwrap_or_default();
-                            let second = self.stack.pop().unwrap_or_default();
-
-                            self.stack.push(top * second);
-                        }
-                        BefungeCommand::ADD => {
-                            let top = self.stack.pop().unwrap_or_default();
-                            let second = self.stack.pop().unwrap_or_default();
-
-                            self.stack.push(top + second);
-                        }
-                        BefungeCommand::WRITE_CHAR => {
-                            let top = self.stack.pop().unwrap_or_default();
-                            let u: u32 = top.try_into().unwrap();
-                            let c: char = u.try_into().unwrap();
-
-                            print!("{}", c);
-                        }
-                        BefungeCommand::SUBTRACT => {
-                            let top = self.stack.pop().unwrap_or_default();
-                            let second = self.stack.pop().unwrap_or_default();
-
-                            self.stack.push(top - second);
-                        }
-                        BefungeCommand::WRITE_INT => {
-                            let top = self.stack.pop().unwrap_or_default();
-
-                            print!("{}", top);
-                        }
-                        BefungeCommand::DIVIDE => {
-                            let top = self.stack.pop().unwrap_or_default();
-                            let second = self.stack.pop().unwrap_or_default();
-
-                            self.stack.push(top / second);
-                        }
-                        BefungeCommand::DUPLICATE => {
-                            let top = self.stack.pop().unwrap_or_default();
-
-                            self.stack.push(top);
-                            self.stack.push(top);
-                        }
-                        BefungeCommand::LEFT => {
-                            self.pc_delta = Delta::Left;
-                        }
-                        BefungeCommand::RIGHT => {
-                            self.pc_delta = Delta::Right;
-                        }
-                        BefungeCommand::RANDOM => {}
-                        BefungeCommand::STOP => {
-                            self.active = false;
-                        }
-                        BefungeCommand::SWAP => {
-                            let top = self.stack.pop().unwrap_or_default();
-                            let second = self.stack.pop().unwrap_or_default();
-
-                            self.stack.push(top);
-                            self.stack.push(second);
-                        }
-                        BefungeCommand::UP => {
-                            self.pc_delta = Delta::Up;
-                        }
-                        BefungeCommand::IF_LEFT_RIGHT => {
-                            let top = self.stack.pop().unwrap_or_default();
-
-                            self.pc_delta = if top > 0 { Delta::Left } else { Delta::Right };
-                        }
-                        BefungeCommand::COMPARE => {
-                            let top = self.stack.pop().unwrap_or_default();
-                            let second = self.stack.pop().unwrap_or_default();
-
-                            self.stack.push(if top > second { 1 } else { 0 })
-                        }
-                        BefungeCommand::READ_CELL => {
-                            let top: usize =
-                                self.stack.pop().unwrap_or_default().try_into().unwrap();
-                            let second = self.stack.pop().unwrap_or_default().try_into().unwrap();
-
-                            if let Some(val) = self.field.get(second, top) {
-                                self.stack.push(val as i32)
-                            }
-                        }
-                        BefungeCommand::WRITE_CELL => {
-                            let top = self.stack.pop().unwrap_or_default().try_into().unwrap();
-                            let second = self.stack.pop().unwrap_or_default().try_into().unwrap();
-                            let value = self.stack.pop().unwrap_or_default().try_into().unwrap();
-
-                            self.field.set(second, top, value);
-                        }
-                        BefungeCommand::DOWN => {
-                            self.pc_delta = Delta::Down;
-                        }
-                        BefungeCommand::IF_UP_DOWN => {
-                            let top = self.stack.pop().unwrap_or_default();
-
-                            self.pc_delta = if top > 0 { Delta::Up } else { Delta::Down };
-                        }
-                        BefungeCommand::READ_CHAR => {
-                            let mut input = String::new();
-                            print!("Enter a character: ");
-                            io::stdin()
-                                .read_line(&mut input)
-                                .expect("Error reading character");
-
-                            let c = input.as_bytes()[0];
-                            self.stack.push(c as i32);
-                        }
-                        b'0'..=b'9' => self.stack.push((curr - 48) as i32),
-                        _ => self.stack.push(curr as i32),
-                    }
-                }
-                if self.active {
-                    self.move_pc();
-                }
-            }
-        }
+    fn path_length(&self, _x: i32, _y: i32, _delta: Delta) -> i64 {
+        (self.width * self.height) as i64
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    #[test]
-    fn test_empty_field() {
-        let field = BefungeField::new(80, 25);
-        assert_eq!(field.get(0, 0), Some(BefungeCommand::NO_OP));
-        assert_eq!(field.get(79, 24), Some(BefungeCommand::NO_OP));
-        assert_eq!(field.get(80, 0), None);
-    }
+/// The direction an instruction pointer travels in.
+///
+/// Befunge-93 only uses the four cardinal directions; Funge-98's `x` can set
+/// any vector.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Delta {
+    pub dx: i32,
+    pub dy: i32,
+}
 
-    #[test]
-    fn test_string_field() {
-        let field = BefungeField::from_str("0\n1\n", 80, 25);
-        assert_eq!(field.get(0, 0), Some(b'0'));
-        assert_eq!(field.get(1, 0), Some(b' '));
-        assert_eq!(field.get(0, 1), Some(b'1'));
-        assert_eq!(field.get(0, 2), Some(b' '));
-        assert_eq!(field.get(79, 24), Some(b' '));
-    }
+impl Delta {
+    pub const RIGHT: Delta = Delta::new(1, 0);
+    pub const LEFT: Delta = Delta::new(-1, 0);
+    pub const DOWN: Delta = Delta::new(0, 1);
+    pub const UP: Delta = Delta::new(0, -1);
+    pub const ALL: [Delta; 4] = [Delta::RIGHT, Delta::LEFT, Delta::DOWN, Delta::UP];
 
-    #[test]
-    fn test_truncate() {
-        let field = BefungeField::from_str("012\n01\n01", 2, 2);
-        assert_eq!(field.get(0, 0), Some(b'0'));
-        assert_eq!(field.get(1, 0), Some(b'1'));
-        assert_eq!(field.get(2, 0), None);
-        assert_eq!(field.get(0, 1), Some(b'0'));
-        assert_eq!(field.get(1, 1), Some(b'1'));
-        assert_eq!(field.get(0, 2), None);
+    pub const fn new(dx: i32, dy: i32) -> Self {
+        Self { dx, dy }
     }
 
-    #[test]
-    fn test_horizontal_wrap_right() {
-        let mut exec = BefungeExecution::new(BefungeField::new(2, 1));
-        exec.step();
-        let (x, _y, _delta) = exec.pc();
-        assert_eq!(x, 1);
-        exec.step();
-        let (x, _y, _delta) = exec.pc();
-        assert_eq!(x, 0);
-        exec.step();
-        let (x, _y, _delta) = exec.pc();
-        assert_eq!(x, 1);
+    pub fn reverse(self) -> Self {
+        Self::new(self.dx.wrapping_neg(), self.dy.wrapping_neg())
     }
 
-    #[test]
-    fn test_horizontal_wrap_left() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("<", 3, 1));
-        exec.step();
-        let (x, _y, _delta) = exec.pc();
-        assert_eq!(x, 2);
-        exec.step();
-        let (x, _y, _delta) = exec.pc();
-        assert_eq!(x, 1);
-        exec.step();
-        let (x, _y, _delta) = exec.pc();
-        assert_eq!(x, 0);
+    pub fn turn_left(self) -> Self {
+        Self::new(self.dy, self.dx.wrapping_neg())
     }
 
-    #[test]
-    fn test_vertical_wrap_down() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("v", 1, 2));
-        exec.step();
-        let (_x, y, _delta) = exec.pc();
-        assert_eq!(y, 1);
-        exec.step();
-        let (_x, y, _delta) = exec.pc();
-        assert_eq!(y, 0);
-        exec.step();
-        let (_x, y, _delta) = exec.pc();
-        assert_eq!(y, 1);
+    pub fn turn_right(self) -> Self {
+        Self::new(self.dy.wrapping_neg(), self.dx)
     }
 
-    #[test]
-    fn test_vertical_wrap_up() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("^", 1, 2));
-        exec.step();
-        let (_x, y, _delta) = exec.pc();
-        assert_eq!(y, 1);
-        exec.step();
-        let (_x, y, _delta) = exec.pc();
-        assert_eq!(y, 0);
-        exec.step();
-        let (_x, y, _delta) = exec.pc();
-        assert_eq!(y, 1);
+    /// `right`, `left`, `down` or `up`, or `None` for any other delta.
+    pub fn name(self) -> Option<&'static str> {
+        match self {
+            Delta::RIGHT => Some("right"),
+            Delta::LEFT => Some("left"),
+            Delta::DOWN => Some("down"),
+            Delta::UP => Some("up"),
+            _ => None,
+        }
     }
+}
 
-    #[test]
-    fn test_push_digits() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("0123456789", 10, 1));
+/// The language variant an execution follows.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dialect {
+    /// Befunge-93 as specified.
+    #[default]
+    Befunge93,
+    /// Concurrent Funge-98 in two dimensions, with fingerprints but without
+    /// file I/O. Unknown instructions reflect the IP.
+    Funge98,
+    /// The semantics of earlier befuddle releases, kept for existing
+    /// programs: `-`, `/`, `%` and `` ` `` apply the top value to the one
+    /// below it, only positive values are true, `.` writes no separating
+    /// space and unknown instructions push their own value.
+    Legacy,
+}
 
-        for _i in 0..10 {
-            exec.step()
-        }
+/// The result of a successful `step` or `run`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StepOutcome {
+    Continue,
+    Halted,
+    /// The program ended with Funge-98 `q` and this exit code.
+    Quit(i32),
+    /// `run` stopped because the next IP to take its turn met this
+    /// breakpoint.
+    Breakpoint(Breakpoint),
+    /// The instruction just executed changed a watched cell. The program can
+    /// carry on.
+    Watchpoint(Watch),
+    /// `run` stopped at one of the execution's limits. The execution is left
+    /// as it was at that point, and can carry on if the limit is raised.
+    LimitExceeded(Limit),
+}
 
-        assert_eq!(exec.stack(), vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9])
+#[derive(Debug)]
+pub struct BefungeExecution<I = StdIo, F = BefungeField> {
+    ips: Vec<InstructionPointer>,
+    /// Index of the IP that executes next.
+    current: usize,
+    next_ip_id: i32,
+    /// Set when the current IP executes `@`.
+    stopped: bool,
+    field: F,
+    active: bool,
+    exit_code: Option<i32>,
+    io: I,
+    rng: FungeRng,
+    dialect: Dialect,
+    arguments: Vec<String>,
+    fingerprints: FingerprintRegistry,
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: HashSet<(i32, i32)>,
+    /// The watched change made by the instruction being executed.
+    watch_hit: Option<Watch>,
+    history: Option<History>,
+    limits: Limits,
+    /// The number of steps taken.
+    steps: u64,
+    tracer: Option<Box<dyn Tracer>>,
+    profile: Option<Profile>,
+}
+
+/// The field as `p`, `s` and fingerprint instructions write to it: each
+/// write is noted in the undo history and the profile, and a change to a
+/// watched cell is kept as the execution's watch hit.
+#[derive(Debug)]
+struct StoringSpace<'a, F: ?Sized> {
+    field: &'a mut F,
+    writes: Option<&'a mut Writes>,
+    profile: Option<&'a mut Profile>,
+    watchpoints: &'a HashSet<(i32, i32)>,
+    watch_hit: &'a mut Option<Watch>,
+}
+
+impl<F: FungeSpace + ?Sized> FungeSpace for StoringSpace<'_, F> {
+    fn cell(&self, x: i32, y: i32) -> i32 {
+        self.field.cell(x, y)
     }
 
-    #[test]
-    fn test_string_mode() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("\"0123456789\"0", 13, 1));
+    fn set_cell(&mut self, x: i32, y: i32, value: i32) -> bool {
+        if !self.field.contains(x, y) {
+            return self.field.set_cell(x, y, value);
+        }
+        if let Some(writes) = &mut self.writes {
+            writes.write(&*self.field, x, y);
+        }
+        if let Some(profile) = &mut self.profile {
+            profile.write(x, y);
+        }
 
-        for _i in 0..13 {
-            exec.step()
+        let old = self.field.cell(x, y);
+        let stored = self.field.set_cell(x, y, value);
+        let new = self.field.cell(x, y);
+        if old != new && self.watchpoints.contains(&(x, y)) {
+            *self.watch_hit = Some(Watch { x, y, old, new });
         }
+        stored
+    }
 
-        assert_eq!(
-            exec.stack(),
-            vec![48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 0]
-        )
+    fn contains(&self, x: i32, y: i32) -> bool {
+        self.field.contains(x, y)
     }
 
-    #[test]
-    fn test_read_cell() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("g", 1, 1));
+    fn bounds(&self) -> Bounds {
+        self.field.bounds()
+    }
 
-        exec.step();
+    fn advance(&self, x: i32, y: i32, delta: Delta, distance: i64) -> (i32, i32) {
+        self.field.advance(x, y, delta, distance)
+    }
 
-        assert_eq!(exec.stack(), vec![103])
+    fn path_length(&self, x: i32, y: i32, delta: Delta) -> i64 {
+        self.field.path_length(x, y, delta)
     }
+}
 
-    #[test]
-    fn test_write_cell() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("p", 1, 1));
+/// Configures a `BefungeExecution` before it starts.
+#[derive(Debug)]
+pub struct ExecutionBuilder<I = StdIo, F = BefungeField> {
+    field: F,
+    io: I,
+    rng: Option<FungeRng>,
+    dialect: Dialect,
+    arguments: Vec<String>,
+    fingerprints: FingerprintRegistry,
+    history: Option<usize>,
+    limits: Limits,
+    tracer: Option<Box<dyn Tracer>>,
+    profile: bool,
+}
 
-        exec.step();
+impl<I: FungeIo, F: FungeSpace> ExecutionBuilder<I, F> {
+    pub fn with_io(field: F, io: I) -> Self {
+        Self {
+            field,
+            io,
+            rng: None,
+            dialect: Dialect::default(),
+            arguments: Vec::new(),
+            fingerprints: FingerprintRegistry::standard(),
+            history: None,
+            limits: Limits::default(),
+            tracer: None,
+            profile: false,
+        }
+    }
 
-        assert_eq!(exec.get(0, 0), Some(0));
+    pub fn io<J: FungeIo>(self, io: J) -> ExecutionBuilder<J, F> {
+        ExecutionBuilder {
+            field: self.field,
+            io,
+            rng: self.rng,
+            dialect: self.dialect,
+            arguments: self.arguments,
+            fingerprints: self.fingerprints,
+            history: self.history,
+            limits: self.limits,
+            tracer: self.tracer,
+            profile: self.profile,
+        }
     }
 
-    #[test]
-    fn test_negate() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("!!", 2, 1));
+    /// Seeds the generator used by `?`, making runs reproducible.
+    pub fn seed(self, seed: u64) -> Self {
+        self.rng(FungeRng::seeded(seed))
+    }
 
-        exec.step();
-        assert_eq!(exec.stack(), vec![1]);
-        exec.step();
-        assert_eq!(exec.stack(), vec![0]);
+    pub fn rng(mut self, rng: FungeRng) -> Self {
+        self.rng = Some(rng);
+        self
     }
 
-    #[test]
-    fn test_swap() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("01\\", 3, 1));
+    pub fn dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
 
-        exec.step();
-        exec.step();
-        exec.step();
-        assert_eq!(exec.stack(), vec![1, 0]);
+    /// Command-line arguments reported by Funge-98 `y`, starting with the
+    /// program name.
+    pub fn args<S: Into<String>>(mut self, arguments: impl IntoIterator<Item = S>) -> Self {
+        self.arguments = arguments.into_iter().map(Into::into).collect();
+        self
     }
 
-    #[test]
-    fn test_add() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("12+", 3, 1));
+    /// Replaces the fingerprints available to `(`, which default to
+    /// `FingerprintRegistry::standard`.
+    pub fn fingerprints(mut self, fingerprints: FingerprintRegistry) -> Self {
+        self.fingerprints = fingerprints;
+        self
+    }
 
-        exec.step();
-        exec.step();
-        exec.step();
-        assert_eq!(exec.stack(), vec![3]);
+    /// Makes `fingerprint` available to `(` alongside the others.
+    pub fn fingerprint<P: Fingerprint + 'static>(mut self, fingerprint: P) -> Self {
+        self.fingerprints.register(fingerprint);
+        self
     }
 
-    #[test]
-    fn test_subtract() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("12-", 3, 1));
+    /// Records an undo log of each step so that `step_back` can rewind the
+    /// execution, forgetting the oldest steps once the log takes up more
+    /// than `budget` bytes.
+    pub fn history(mut self, budget: usize) -> Self {
+        self.history = Some(budget);
+        self
+    }
 
-        exec.step();
-        exec.step();
-        exec.step();
-        assert_eq!(exec.stack(), vec![1]);
+    /// Bounds how far `run` may go.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
     }
 
-    #[test]
-    fn test_multiply() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("12*", 3, 1));
+    /// Hands every step the execution takes to `tracer`.
+    pub fn trace<T: Tracer + 'static>(mut self, tracer: T) -> Self {
+        self.tracer = Some(Box::new(tracer));
+        self
+    }
 
-        exec.step();
-        exec.step();
-        exec.step();
-        assert_eq!(exec.stack(), vec![2]);
+    /// Counts the cells and instructions the execution runs.
+    pub fn profile(mut self) -> Self {
+        self.profile = true;
+        self
     }
 
-    #[test]
-    fn test_divide() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("12/", 3, 1));
+    pub fn build(self) -> BefungeExecution<I, F> {
+        BefungeExecution {
+            ips: vec![InstructionPointer::new(0)],
+            current: 0,
+            next_ip_id: 1,
+            stopped: false,
+            field: self.field,
+            active: true,
+            exit_code: None,
+            io: self.io,
+            rng: self.rng.unwrap_or_default(),
+            dialect: self.dialect,
+            arguments: self.arguments,
+            fingerprints: self.fingerprints,
+            breakpoints: Vec::new(),
+            watchpoints: HashSet::new(),
+            watch_hit: None,
+            history: self.history.map(History::new),
+            limits: self.limits,
+            steps: 0,
+            tracer: self.tracer,
+            profile: if self.profile {
+                Some(Profile::new())
+            } else {
+                None
+            },
+        }
+    }
+}
 
-        exec.step();
-        exec.step();
-        exec.step();
-        assert_eq!(exec.stack(), vec![2]);
+impl<F: FungeSpace> BefungeExecution<StdIo, F> {
+    pub fn new(field: F) -> Self {
+        Self::builder(field).build()
     }
 
-    #[test]
-    fn test_modulo() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("23%", 3, 1));
+    pub fn builder(field: F) -> ExecutionBuilder<StdIo, F> {
+        ExecutionBuilder::with_io(field, StdIo::default())
+    }
+}
 
-        exec.step();
-        exec.step();
-        exec.step();
-        assert_eq!(exec.stack(), vec![1]);
+impl<I: FungeIo, F: FungeSpace> BefungeExecution<I, F> {
+    pub fn with_io(field: F, io: I) -> Self {
+        ExecutionBuilder::with_io(field, io).build()
     }
 
-    #[test]
-    fn test_compare() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("12`", 3, 1));
+    pub fn io(&self) -> &I {
+        &self.io
+    }
 
-        exec.step();
-        exec.step();
-        exec.step();
-        assert_eq!(exec.stack(), vec![1]);
+    pub fn io_mut(&mut self) -> &mut I {
+        &mut self.io
+    }
 
-        let mut exec = BefungeExecution::new(BefungeField::from_str("21`", 3, 1));
+    pub fn into_io(self) -> I {
+        self.io
+    }
 
-        exec.step();
-        exec.step();
-        exec.step();
-        assert_eq!(exec.stack(), vec![0]);
+    /// The live IPs, in the order they are scheduled.
+    pub fn ips(&self) -> &[InstructionPointer] {
+        &self.ips
     }
 
-    #[test]
-    fn test_duplicate() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("1:", 2, 1));
+    /// The IP that executes on the next `step`.
+    pub fn ip(&self) -> &InstructionPointer {
+        &self.ips[self.current]
+    }
 
-        exec.step();
-        exec.step();
-        assert_eq!(exec.stack(), vec![1, 1]);
+    fn ip_mut(&mut self) -> &mut InstructionPointer {
+        &mut self.ips[self.current]
     }
 
-    #[test]
-    fn test_discard() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("1$", 2, 1));
+    pub fn pc(&self) -> (i32, i32, Delta) {
+        (self.ip().x, self.ip().y, self.ip().delta)
+    }
 
-        exec.step();
-        assert_eq!(exec.stack(), vec![1]);
-        exec.step();
-        assert_eq!(exec.stack(), vec![]);
+    /// The top of the current IP's stack stack.
+    pub fn stack(&self) -> Vec<i32> {
+        self.ip().stacks.toss().clone()
     }
 
-    #[test]
-    fn test_if_left_right() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("1_", 2, 1));
+    pub fn stacks(&self) -> &StackStack {
+        &self.ip().stacks
+    }
 
-        exec.step();
-        assert_eq!(exec.stack(), vec![1]);
-        exec.step();
-        assert_eq!(exec.stack(), vec![]);
-        let (x, _y, delta) = exec.pc();
-        assert_eq!(x, 0);
-        assert_eq!(delta, Delta::Left);
+    pub fn storage_offset(&self) -> (i32, i32) {
+        self.ip().storage_offset
+    }
 
-        let mut exec = BefungeExecution::new(BefungeField::from_str("0_", 2, 1));
-        exec.step();
-        assert_eq!(exec.stack(), vec![0]);
-        exec.step();
-        assert_eq!(exec.stack(), vec![]);
-        let (x, _y, delta) = exec.pc();
-        assert_eq!(x, 0);
-        assert_eq!(delta, Delta::Right);
+    pub fn dialect(&self) -> Dialect {
+        self.dialect
     }
 
-    #[test]
-    fn test_if_up_down() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("1|", 2, 2));
+    pub fn fingerprints(&self) -> &FingerprintRegistry {
+        &self.fingerprints
+    }
 
-        exec.step();
-        assert_eq!(exec.stack(), vec![1]);
-        exec.step();
-        assert_eq!(exec.stack(), vec![]);
-        let (_x, y, delta) = exec.pc();
-        assert_eq!(y, 1);
-        assert_eq!(delta, Delta::Up);
+    pub fn fingerprints_mut(&mut self) -> &mut FingerprintRegistry {
+        &mut self.fingerprints
+    }
 
-        let mut exec = BefungeExecution::new(BefungeField::from_str("0|", 2, 2));
-        exec.step();
-        assert_eq!(exec.stack(), vec![0]);
-        exec.step();
-        assert_eq!(exec.stack(), vec![]);
-        let (_x, y, delta) = exec.pc();
-        assert_eq!(y, 1);
-        assert_eq!(delta, Delta::Down);
+    pub fn field(&self) -> &F {
+        &self.field
     }
 
-    #[test]
-    fn test_write_int() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("12..", 4, 1));
-        exec.step();
-        exec.step();
-        exec.step();
-        exec.step();
+    /// The cell at `(x, y)`, or `None` if the field does not extend there.
+    pub fn get(&self, x: i32, y: i32) -> Option<i32> {
+        if self.field.contains(x, y) {
+            Some(self.field.cell(x, y))
+        } else {
+            None
+        }
     }
 
-    #[test]
-    fn test_write_char() {
-        let mut exec = BefungeExecution::new(BefungeField::from_str("\"a\",", 4, 1));
-        exec.step();
-        exec.step();
-        exec.step();
-        exec.step();
+    /// Makes `run` stop before any IP meets `breakpoint`.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    /// Removes `breakpoint`, returning whether it was set.
+    pub fn remove_breakpoint(&mut self, breakpoint: Breakpoint) -> bool {
+        let count = self.breakpoints.len();
+        self.breakpoints.retain(|&b| b != breakpoint);
+        self.breakpoints.len() != count
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    /// Makes `step` and `run` report when `p` or `s` changes the cell at
+    /// `(x, y)`.
+    pub fn watch(&mut self, x: i32, y: i32) {
+        self.watchpoints.insert((x, y));
+    }
+
+    /// Stops watching `(x, y)`, returning whether it was watched.
+    pub fn unwatch(&mut self, x: i32, y: i32) -> bool {
+        self.watchpoints.remove(&(x, y))
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.watchpoints.iter().copied()
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Replaces the tracer steps are handed to, returning the old one.
+    pub fn set_tracer(&mut self, tracer: Option<Box<dyn Tracer>>) -> Option<Box<dyn Tracer>> {
+        mem::replace(&mut self.tracer, tracer)
+    }
+
+    /// What the execution has run so far, if it was built to profile.
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// The number of steps taken so far, less any stepped back.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// The first of the limits `run` has reached, if any.
+    fn exceeded(&self, started: Instant) -> Option<Limit> {
+        let limits = &self.limits;
+        if let Some(steps) = limits.steps {
+            if self.steps >= steps {
+                return Some(Limit::Steps(steps));
+            }
+        }
+        if let Some(stack) = limits.stack {
+            if self.ips.iter().any(|ip| ip.stacks.len() > stack) {
+                return Some(Limit::Stack(stack));
+            }
+        }
+        if let Some(time) = limits.time {
+            // Reading the clock every step would slow `run` down noticeably.
+            if self.steps & 0xff == 0 && started.elapsed() >= time {
+                return Some(Limit::Time(time));
+            }
+        }
+        None
+    }
+
+    /// The first breakpoint the IP about to take its turn meets, if any.
+    ///
+    /// A Funge-98 IP is taken to stand past any spaces and `;` markers, as
+    /// it will when its turn moves it onto the instruction it executes. The
+    /// IP itself is not moved.
+    pub fn hit_breakpoint(&self) -> Option<Breakpoint> {
+        if self.breakpoints.is_empty() || !self.active {
+            return None;
+        }
+        let (x, y) = if self.is_funge98() && !self.ip().string_mode {
+            self.past_markers()
+        } else {
+            (self.ip().x, self.ip().y)
+        };
+
+        let instruction = self.field.cell(x, y);
+        let ip = self.ip();
+        self.breakpoints
+            .iter()
+            .copied()
+            .find(|b| b.matches(ip, (x, y), instruction))
+    }
+
+    pub fn move_pc(&mut self) {
+        let (x, y) = self
+            .field
+            .advance(self.ip().x, self.ip().y, self.ip().delta, 1);
+        self.ip_mut().x = x;
+        self.ip_mut().y = y;
+    }
+
+    fn current(&self) -> i32 {
+        self.field.cell(self.ip().x, self.ip().y)
+    }
+
+    /// Where moving from `(x, y)` along the IP's path while the cell
+    /// there matches `skip` ends up. Gives up after a full lap of the field.
+    fn moved_while(&self, (mut x, mut y): (i32, i32), skip: impl Fn(i32) -> bool) -> (i32, i32) {
+        let delta = self.ip().delta;
+        let mut budget = self.field.path_length(x, y, delta);
+
+        while budget > 0 && skip(self.field.cell(x, y)) {
+            let (next_x, next_y) = self.field.advance(x, y, delta, 1);
+            x = next_x;
+            y = next_y;
+            budget -= 1;
+        }
+        (x, y)
+    }
+
+    /// Moves the IP along its path while the cell under it matches `skip`.
+    /// Gives up after a full lap of the field.
+    fn move_while(&mut self, skip: impl Fn(i32) -> bool) {
+        let (x, y) = self.moved_while((self.ip().x, self.ip().y), skip);
+        self.ip_mut().x = x;
+        self.ip_mut().y = y;
+    }
+
+    /// Where the IP stands once past spaces and `;` comments, on the next
+    /// instruction. Gives up on a path with no instructions on it.
+    fn past_markers(&self) -> (i32, i32) {
+        let delta = self.ip().delta;
+        let mut position = (self.ip().x, self.ip().y);
+        let laps = self.field.path_length(position.0, position.1, delta);
+        for _lap in 0..laps {
+            position = self.moved_while(position, |c| c == BefungeCommand::NO_OP as i32);
+            let (x, y) = position;
+            if self.field.cell(x, y) != BefungeCommand::JUMP_OVER as i32 {
+                break;
+            }
+
+            let (x, y) = self.field.advance(x, y, delta, 1);
+            let (x, y) = self.moved_while((x, y), |c| c != BefungeCommand::JUMP_OVER as i32);
+            position = self.field.advance(x, y, delta, 1);
+        }
+        position
+    }
+
+    /// Moves the IP over spaces and `;` comments onto the next instruction,
+    /// as Funge-98 does in zero ticks.
+    fn skip_markers(&mut self) {
+        let (x, y) = self.past_markers();
+        self.ip_mut().x = x;
+        self.ip_mut().y = y;
+    }
+
+    /// Runs until the program ends, an IP meets a breakpoint, a watched
+    /// cell changes or a limit is reached. A breakpoint the current IP
+    /// already stands on does not count, so calling `run` again carries on
+    /// past it.
+    pub fn run(&mut self) -> Result<StepOutcome, BefungeError> {
+        let started = Instant::now();
+        let outcome = loop {
+            if let Some(limit) = self.exceeded(started) {
+                break Ok(StepOutcome::LimitExceeded(limit));
+            }
+            match self.step() {
+                Ok(StepOutcome::Continue) => {}
+                other => break other,
+            }
+            if let Some(breakpoint) = self.hit_breakpoint() {
+                break Ok(StepOutcome::Breakpoint(breakpoint));
+            }
+        };
+
+        self.io
+            .flush()
+            .map_err(|e| self.fault(BefungeErrorKind::Io(e)))?;
+        if let Some(tracer) = &mut self.tracer {
+            let flushed = tracer.flush();
+            flushed.map_err(|e| self.fault(BefungeErrorKind::Io(e)))?;
+        }
+        outcome
+    }
+
+    fn fault(&self, kind: BefungeErrorKind) -> BefungeError {
+        BefungeError::new(
+            kind,
+            (self.ip().x, self.ip().y),
+            self.current(),
+            self.stack(),
+        )
+    }
+
+    fn peek(&self, depth: usize) -> i32 {
+        self.ip().stacks.peek(depth)
+    }
+
+    fn pop(&mut self) -> i32 {
+        let ip = &mut self.ips[self.current];
+        if let Some(undo) = self.history.as_mut().and_then(History::last_mut) {
+            undo.pop(ip.stacks.toss());
+        }
+        ip.stacks.pop()
+    }
+
+    fn push(&mut self, value: i32) {
+        self.ip_mut().stacks.push(value);
+    }
+
+    fn pop_vector(&mut self) -> (i32, i32) {
+        let y = self.pop();
+        let x = self.pop();
+        (x, y)
+    }
+
+    fn is_funge98(&self) -> bool {
+        self.dialect == Dialect::Funge98
+    }
+
+    fn reflect(&mut self) {
+        self.ip_mut().delta = self.ip().delta.reverse();
+    }
+
+    /// The operands of a binary instruction, in the order they are applied,
+    /// without popping them.
+    fn peek_operands(&self) -> (i32, i32) {
+        match self.dialect {
+            Dialect::Befunge93 | Dialect::Funge98 => (self.peek(1), self.peek(0)),
+            Dialect::Legacy => (self.peek(0), self.peek(1)),
+        }
+    }
+
+    fn pop_operands(&mut self) -> (i32, i32) {
+        let top = self.pop();
+        let second = self.pop();
+
+        match self.dialect {
+            Dialect::Befunge93 | Dialect::Funge98 => (second, top),
+            Dialect::Legacy => (top, second),
+        }
+    }
+
+    fn is_true(&self, value: i32) -> bool {
+        match self.dialect {
+            Dialect::Befunge93 | Dialect::Funge98 => value != 0,
+            Dialect::Legacy => value > 0,
+        }
+    }
+
+    /// The field cell addressed by the vector on top of the stack, relative
+    /// to the storage offset, or `None` if it lies outside the field.
+    fn cell_position(&self) -> Option<(i32, i32)> {
+        let x = self.peek(1).wrapping_add(self.ip().storage_offset.0);
+        let y = self.peek(0).wrapping_add(self.ip().storage_offset.1);
+
+        if self.field.contains(x, y) {
+            Some((x, y))
+        } else {
+            None
+        }
+    }
+
+    fn out_of_bounds(&self) -> BefungeError {
+        self.fault(BefungeErrorKind::OutOfBounds {
+            x: self.peek(1).wrapping_add(self.ip().storage_offset.0),
+            y: self.peek(0).wrapping_add(self.ip().storage_offset.1),
+        })
+    }
+
+    fn outcome(&self) -> StepOutcome {
+        match (self.active, self.exit_code) {
+            (true, _) => StepOutcome::Continue,
+            (false, Some(code)) => StepOutcome::Quit(code),
+            (false, None) => StepOutcome::Halted,
+        }
+    }
+
+    pub fn step(&mut self) -> Result<StepOutcome, BefungeError> {
+        if !self.active {
+            return Ok(self.outcome());
+        }
+
+        self.steps += 1;
+        if let Some(history) = &mut self.history {
+            history.begin(Undo::new(
+                &self.ips,
+                self.current,
+                self.next_ip_id,
+                self.active,
+                self.exit_code,
+                &self.rng,
+            ));
+        }
+
+        if self.is_funge98() && !self.ip().string_mode {
+            self.skip_markers();
+        }
+
+        let curr = self.current();
+        let traced = self.tracer.is_some().then(|| self.trace_event(curr));
+        if let Some(profile) = &mut self.profile {
+            let ip = &self.ips[self.current];
+            let instruction = !ip.string_mode || curr == BefungeCommand::TOGGLE_STRING_MODE as i32;
+            profile.execute(ip.x, ip.y, instruction.then_some(curr));
+        }
+        if !self.ip().string_mode && self.reshapes(curr) {
+            if let Some(undo) = self.history.as_mut().and_then(History::last_mut) {
+                undo.snapshot(&self.ips);
+            }
+        }
+        let mut moves = true;
+        if self.ip().string_mode {
+            if curr == BefungeCommand::TOGGLE_STRING_MODE as i32 {
+                self.ip_mut().string_mode = false;
+            } else {
+                self.push(curr);
+                if self.is_funge98() && curr == BefungeCommand::NO_OP as i32 {
+                    // Funge-98 collapses a run of spaces in a string to one.
+                    self.move_while(|c| c == BefungeCommand::NO_OP as i32);
+                    moves = false;
+                }
+            }
+        } else {
+            self.execute(curr)?;
+        }
+
+        if let Some(mut event) = traced {
+            event.stack_after = self.stack();
+            if let Some(tracer) = &mut self.tracer {
+                let traced = tracer.trace(&event);
+                traced.map_err(|e| self.fault(BefungeErrorKind::Io(e)))?;
+            }
+        }
+        self.end_turn(moves);
+        match self.watch_hit.take() {
+            Some(watch) if self.active => Ok(StepOutcome::Watchpoint(watch)),
+            _ => Ok(self.outcome()),
+        }
+    }
+
+    /// The trace of the step about to execute `instruction`, less the stack
+    /// it leaves behind.
+    fn trace_event(&self, instruction: i32) -> TraceEvent {
+        let ip = self.ip();
+        TraceEvent {
+            step: self.steps,
+            ip: ip.id,
+            x: ip.x,
+            y: ip.y,
+            instruction,
+            delta: ip.delta,
+            string_mode: ip.string_mode,
+            stack_before: self.stack(),
+            stack_after: Vec::new(),
+        }
+    }
+
+    /// Moves the current IP past its instruction, or drops it if it
+    /// stopped, and hands over to the next IP in turn.
+    fn end_turn(&mut self, moves: bool) {
+        if !self.active {
+            return;
+        }
+
+        if mem::take(&mut self.stopped) {
+            self.ips.remove(self.current);
+        } else {
+            if moves {
+                self.move_pc();
+            }
+            self.current += 1;
+        }
+        if self.current >= self.ips.len() {
+            self.current = 0;
+        }
+    }
+
+    /// Whether undoing `instruction` takes a copy of every IP, rather than
+    /// the changes to the current one.
+    fn reshapes(&self, instruction: i32) -> bool {
+        self.is_funge98()
+            && matches!(
+                u8::try_from(instruction),
+                Ok(BefungeCommand::BEGIN_BLOCK
+                    | BefungeCommand::END_BLOCK
+                    | BefungeCommand::STACK_UNDER_STACK
+                    | BefungeCommand::CLEAR_STACK
+                    | BefungeCommand::SPLIT
+                    | BefungeCommand::STOP
+                    | BefungeCommand::LOAD_SEMANTICS
+                    | BefungeCommand::UNLOAD_SEMANTICS
+                    | BefungeCommand::ITERATE
+                    | BefungeCommand::SYSINFO
+                    | b'A'..=b'Z')
+            )
+    }
+
+    /// Rewinds the last recorded step, returning `false` if there is none.
+    ///
+    /// Input the step read is read again when it is redone, and output it
+    /// wrote is not written twice. Fingerprints keep any state of their own.
+    pub fn step_back(&mut self) -> bool {
+        let undo = match self.history.as_mut().and_then(History::undo) {
+            Some(undo) => undo,
+            None => return false,
+        };
+
+        self.current = undo.current;
+        self.next_ip_id = undo.next_ip_id;
+        self.active = undo.active;
+        self.exit_code = undo.exit_code;
+        self.rng = undo.rng.clone();
+        self.stopped = false;
+        self.watch_hit = None;
+        self.steps -= 1;
+        undo.apply(&mut self.ips, &mut self.field);
+        true
+    }
+
+    /// Steps back until the IP about to take its turn meets a breakpoint or
+    /// the step just undone changed a watched cell. Returns `None` when the
+    /// recorded history runs out first.
+    pub fn run_back_to_breakpoint(&mut self) -> Option<StepOutcome> {
+        loop {
+            let watched: Vec<Watch> = self
+                .watchpoints
+                .iter()
+                .map(|&(x, y)| Watch {
+                    x,
+                    y,
+                    old: 0,
+                    new: self.field.cell(x, y),
+                })
+                .collect();
+            if !self.step_back() {
+                return None;
+            }
+
+            for watch in watched {
+                let old = self.field.cell(watch.x, watch.y);
+                if old != watch.new {
+                    return Some(StepOutcome::Watchpoint(Watch { old, ..watch }));
+                }
+            }
+            if let Some(breakpoint) = self.hit_breakpoint() {
+                return Some(StepOutcome::Breakpoint(breakpoint));
+            }
+        }
+    }
+
+    /// The number of steps `step_back` can rewind.
+    pub fn history_len(&self) -> usize {
+        self.history.as_ref().map_or(0, History::len)
+    }
+
+    /// Output that stepped-back steps wrote, which is not written again
+    /// when they are redone. It is at the end of everything written so far.
+    pub fn rewound_output(&self) -> &str {
+        self.history.as_ref().map_or("", History::rewritten)
+    }
+
+    /// The state of the execution, for saving and resuming later.
+    pub fn snapshot(&self) -> Snapshot {
+        let ips = self
+            .ips
+            .iter()
+            .map(|ip| IpSnapshot {
+                id: ip.id,
+                x: ip.x,
+                y: ip.y,
+                delta: ip.delta,
+                string_mode: ip.string_mode,
+                stacks: ip.stacks.to_vecs(),
+                storage_offset: ip.storage_offset,
+                semantics: ip
+                    .semantics
+                    .iter()
+                    .map(|loaded| {
+                        loaded
+                            .iter()
+                            .map(|&index| self.fingerprints.get(index).name().to_string())
+                            .collect()
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let mut cells = self.field.cells();
+        cells.sort_unstable_by_key(|&(x, y, _)| (y, x));
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            dialect: self.dialect,
+            bounds: self.field.bounds(),
+            cells,
+            ips,
+            current: self.current,
+            next_ip_id: self.next_ip_id,
+            active: self.active,
+            exit_code: self.exit_code,
+            rng: self.rng.state(),
+            steps: self.steps,
+            input_position: self.io.input_position(),
+            arguments: self.arguments.clone(),
+        }
+    }
+
+    /// Puts the execution back in the state `snapshot` was taken in,
+    /// skipping the input it had already read. Breakpoints, watchpoints and
+    /// limits are kept; the undo history starts afresh.
+    ///
+    /// The execution is left untouched if the snapshot does not fit it or
+    /// the input cannot be skipped, though in the latter case some input
+    /// may have been read.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), SnapshotError> {
+        let mut ips = Vec::with_capacity(snapshot.ips.len());
+        for saved in &snapshot.ips {
+            if saved.semantics.len() != 26 {
+                return Err(SnapshotError::Malformed(format!(
+                    "IP {} does not list semantics for A to Z",
+                    saved.id
+                )));
+            }
+            let mut semantics = Vec::with_capacity(26);
+            for loaded in &saved.semantics {
+                let indices = loaded
+                    .iter()
+                    .map(|name| {
+                        self.fingerprints
+                            .find(fingerprint_id(name))
+                            .ok_or_else(|| SnapshotError::UnknownFingerprint(name.clone()))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                semantics.push(indices);
+            }
+
+            ips.push(InstructionPointer {
+                id: saved.id,
+                x: saved.x,
+                y: saved.y,
+                delta: saved.delta,
+                string_mode: saved.string_mode,
+                stacks: StackStack::from_vecs(saved.stacks.clone()),
+                storage_offset: saved.storage_offset,
+                semantics,
+            });
+        }
+        if snapshot.current >= ips.len() {
+            return Err(SnapshotError::Malformed(String::from(
+                "the current IP does not exist",
+            )));
+        }
+        if let Some(&(x, y, _)) = snapshot
+            .cells
+            .iter()
+            .find(|&&(x, y, _)| !self.field.contains(x, y))
+        {
+            return Err(SnapshotError::OutOfBounds { x, y });
+        }
+        self.io.skip_input(snapshot.input_position)?;
+
+        for (x, y, _) in self.field.cells() {
+            self.field.set_cell(x, y, BefungeCommand::NO_OP as i32);
+        }
+        for &(x, y, value) in &snapshot.cells {
+            self.field.set_cell(x, y, value);
+        }
+        self.field.restore_bounds(snapshot.bounds);
+
+        self.ips = ips;
+        self.current = snapshot.current;
+        self.next_ip_id = snapshot.next_ip_id;
+        self.stopped = false;
+        self.active = snapshot.active;
+        self.exit_code = snapshot.exit_code;
+        self.rng = FungeRng::seeded(snapshot.rng);
+        self.dialect = snapshot.dialect;
+        self.arguments = snapshot.arguments.clone();
+        self.watch_hit = None;
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+        self.steps = snapshot.steps;
+        Ok(())
+    }
+
+    /// Writes a snapshot of the execution to `writer`.
+    pub fn save_snapshot<W: Write>(
+        &self,
+        writer: W,
+        format: SnapshotFormat,
+    ) -> Result<(), SnapshotError> {
+        self.snapshot().write(writer, format)
+    }
+
+    /// Reads a snapshot in either format from `reader` and restores it.
+    pub fn load_snapshot<R: Read>(&mut self, reader: R) -> Result<(), SnapshotError> {
+        self.restore(&Snapshot::read(reader)?)
+    }
+
+    /// The I/O as the instructions see it, logged to the history.
+    fn logged_io(&mut self) -> LoggedIo<'_, I> {
+        LoggedIo {
+            io: &mut self.io,
+            history: self.history.as_mut(),
+        }
+    }
+
+    /// Writes a cell for `p` or `s`, noting the change if the cell is
+    /// watched or profiled.
+    fn store(&mut self, x: i32, y: i32, value: i32) {
+        let mut space = StoringSpace {
+            field: &mut self.field,
+            writes: self
+                .history
+                .as_mut()
+                .and_then(History::last_mut)
+                .map(|undo| &mut undo.writes),
+            profile: self.profile.as_mut(),
+            watchpoints: &self.watchpoints,
+            watch_hit: &mut self.watch_hit,
+        };
+        space.set_cell(x, y, value);
+    }
+
+    /// `t`: adds a copy of the current IP travelling the opposite way,
+    /// scheduled to run before the current one's next turn.
+    fn split(&mut self) {
+        let mut child = self.ip().clone();
+        child.id = self.next_ip_id;
+        child.delta = child.delta.reverse();
+        let (x, y) = self.field.advance(child.x, child.y, child.delta, 1);
+        child.x = x;
+        child.y = y;
+
+        self.next_ip_id = self.next_ip_id.wrapping_add(1);
+        self.ips.insert(self.current, child);
+        self.current += 1;
+    }
+
+    fn next_position(&self) -> (i32, i32) {
+        self.field
+            .advance(self.ip().x, self.ip().y, self.ip().delta, 1)
+    }
+
+    /// Executes one instruction at the current position of the IP, without
+    /// moving past it.
+    fn execute(&mut self, curr: i32) -> Result<(), BefungeError> {
+        let op = match u8::try_from(curr) {
+            Ok(op) => op,
+            Err(_) => {
+                self.unknown(curr);
+                return Ok(());
+            }
+        };
+
+        match op {
+            BefungeCommand::NO_OP => {}
+            BefungeCommand::NEGATE => {
+                let top = self.pop();
+
+                self.push(if self.is_true(top) { 0 } else { 1 })
+            }
+            BefungeCommand::TOGGLE_STRING_MODE => self.ip_mut().string_mode = true,
+            BefungeCommand::BRIDGE => {
+                self.move_pc();
+            }
+            BefungeCommand::DISCARD => {
+                let _top = self.pop();
+            }
+            BefungeCommand::MODULO => {
+                if self.peek_operands().1 == 0 && !self.is_funge98() {
+                    return Err(self.fault(BefungeErrorKind::DivisionByZero));
+                }
+                let (a, b) = self.pop_operands();
+
+                self.push(if b == 0 { 0 } else { a.wrapping_rem(b) });
+            }
+            BefungeCommand::READ_INT => {
+                let i = self
+                    .logged_io()
+                    .read_int()
+                    .map_err(|e| self.fault(BefungeErrorKind::Io(e)))?;
+
+                match i {
+                    Some(i) => self.push(i),
+                    None if self.is_funge98() => self.reflect(),
+                    None => self.push(-1),
+                }
+            }
+            BefungeCommand::MULTIPLY => {
+                let top = self.pop();
+                let second = self.pop();
+
+                self.push(top.wrapping_mul(second));
+            }
+            BefungeCommand::ADD => {
+                let top = self.pop();
+                let second = self.pop();
+
+                self.push(top.wrapping_add(second));
+            }
+            BefungeCommand::WRITE_CHAR => {
+                let top = self.peek(0);
+                let c = u32::try_from(top).ok().and_then(char::from_u32);
+
+                match c {
+                    Some(c) => {
+                        self.logged_io()
+                            .write_char(c)
+                            .map_err(|e| self.fault(BefungeErrorKind::Io(e)))?;
+                        self.pop();
+                    }
+                    None if self.is_funge98() => {
+                        self.pop();
+                        self.reflect();
+                    }
+                    None => return Err(self.fault(BefungeErrorKind::InvalidCharacter(top))),
+                }
+            }
+            BefungeCommand::SUBTRACT => {
+                let (a, b) = self.pop_operands();
+
+                self.push(a.wrapping_sub(b));
+            }
+            BefungeCommand::WRITE_INT => {
+                let top = self.peek(0);
+                let separator = match self.dialect {
+                    Dialect::Befunge93 | Dialect::Funge98 => Some(' '),
+                    Dialect::Legacy => None,
+                };
+                let mut io = self.logged_io();
+                io.write_int(top)
+                    .and_then(|_| separator.map_or(Ok(()), |c| io.write_char(c)))
+                    .map_err(|e| self.fault(BefungeErrorKind::Io(e)))?;
+                self.pop();
+            }
+            BefungeCommand::DIVIDE => {
+                if self.peek_operands().1 == 0 && !self.is_funge98() {
+                    return Err(self.fault(BefungeErrorKind::DivisionByZero));
+                }
+                let (a, b) = self.pop_operands();
+
+                self.push(if b == 0 { 0 } else { a.wrapping_div(b) });
+            }
+            BefungeCommand::DUPLICATE => {
+                let top = self.pop();
+
+                self.push(top);
+                self.push(top);
+            }
+            BefungeCommand::LEFT => {
+                self.ip_mut().delta = Delta::LEFT;
+            }
+            BefungeCommand::RIGHT => {
+                self.ip_mut().delta = Delta::RIGHT;
+            }
+            BefungeCommand::RANDOM => {
+                self.ip_mut().delta = Delta::ALL[self.rng.below(4) as usize];
+            }
+            BefungeCommand::STOP => {
+                // The last IP is kept so that its final state can be inspected.
+                if self.ips.len() > 1 {
+                    self.stopped = true;
+                } else {
+                    self.active = false;
+                }
+            }
+            BefungeCommand::SWAP => {
+                let top = self.pop();
+                let second = self.pop();
+
+                self.push(top);
+                self.push(second);
+            }
+            BefungeCommand::UP => {
+                self.ip_mut().delta = Delta::UP;
+            }
+            BefungeCommand::IF_LEFT_RIGHT => {
+                let top = self.pop();
+
+                self.ip_mut().delta = if self.is_true(top) {
+                    Delta::LEFT
+                } else {
+                    Delta::RIGHT
+                };
+            }
+            BefungeCommand::COMPARE => {
+                let (a, b) = self.pop_operands();
+
+                self.push(if a > b { 1 } else { 0 })
+            }
+            BefungeCommand::READ_CELL => {
+                let val = match self.cell_position() {
+                    Some((x, y)) => self.field.cell(x, y),
+                    None if self.is_funge98() => BefungeCommand::NO_OP as i32,
+                    None => return Err(self.out_of_bounds()),
+                };
+                self.pop_vector();
+
+                self.push(val)
+            }
+            BefungeCommand::WRITE_CELL => {
+                let (x, y) = self.cell_position().ok_or_else(|| self.out_of_bounds())?;
+                self.pop_vector();
+                let value = self.pop();
+
+                self.store(x, y, value);
+            }
+            BefungeCommand::DOWN => {
+                self.ip_mut().delta = Delta::DOWN;
+            }
+            BefungeCommand::IF_UP_DOWN => {
+                let top = self.pop();
+
+                self.ip_mut().delta = if self.is_true(top) {
+                    Delta::UP
+                } else {
+                    Delta::DOWN
+                };
+            }
+            BefungeCommand::READ_CHAR => {
+                let c = self
+                    .logged_io()
+                    .read_char()
+                    .map_err(|e| self.fault(BefungeErrorKind::Io(e)))?;
+
+                match c {
+                    Some(c) => self.push(c as i32),
+                    None if self.is_funge98() => self.reflect(),
+                    None => self.push(-1),
+                }
+            }
+            b'0'..=b'9' => self.push((op - b'0') as i32),
+            _ if self.is_funge98() => self.execute_funge98(op)?,
+            _ => self.unknown(curr),
+        }
+
+        Ok(())
+    }
+
+    fn unknown(&mut self, curr: i32) {
+        match self.dialect {
+            Dialect::Befunge93 => {}
+            Dialect::Funge98 => self.reflect(),
+            Dialect::Legacy => self.push(curr),
+        }
+    }
+
+    fn execute_funge98(&mut self, op: u8) -> Result<(), BefungeError> {
+        match op {
+            b'a'..=b'f' => self.push((op - b'a') as i32 + 10),
+            BefungeCommand::FETCH_CHAR => {
+                self.move_pc();
+                self.push(self.current());
+            }
+            BefungeCommand::STORE_CHAR => {
+                self.move_pc();
+                let value = self.pop();
+                self.store(self.ip().x, self.ip().y, value);
+            }
+            BefungeCommand::JUMP_OVER => {
+                self.move_pc();
+                self.move_while(|c| c != BefungeCommand::JUMP_OVER as i32);
+            }
+            BefungeCommand::JUMP => {
+                let n = self.pop();
+                let (x, y) =
+                    self.field
+                        .advance(self.ip().x, self.ip().y, self.ip().delta, n as i64);
+                self.ip_mut().x = x;
+                self.ip_mut().y = y;
+            }
+            BefungeCommand::ITERATE => self.iterate()?,
+            BefungeCommand::ABSOLUTE_DELTA => {
+                let (dx, dy) = self.pop_vector();
+                self.ip_mut().delta = Delta::new(dx, dy);
+            }
+            BefungeCommand::TURN_LEFT => self.ip_mut().delta = self.ip().delta.turn_left(),
+            BefungeCommand::TURN_RIGHT => self.ip_mut().delta = self.ip().delta.turn_right(),
+            BefungeCommand::COMPARE_TURN => {
+                let b = self.pop();
+                let a = self.pop();
+                if a < b {
+                    self.ip_mut().delta = self.ip().delta.turn_left();
+                } else if a > b {
+                    self.ip_mut().delta = self.ip().delta.turn_right();
+                }
+            }
+            BefungeCommand::REFLECT => self.reflect(),
+            BefungeCommand::CLEAR_STACK => self.ip_mut().stacks.clear(),
+            BefungeCommand::NOP => {}
+            BefungeCommand::BEGIN_BLOCK => {
+                let n = self.pop();
+                let (x, y) = self.next_position();
+                let ip = self.ip_mut();
+                ip.stacks.begin_block(n, ip.storage_offset);
+                ip.storage_offset = (x, y);
+            }
+            BefungeCommand::END_BLOCK => {
+                let n = self.pop();
+                match self.ip_mut().stacks.end_block(n) {
+                    Some(offset) => self.ip_mut().storage_offset = offset,
+                    None => self.reflect(),
+                }
+            }
+            BefungeCommand::STACK_UNDER_STACK => {
+                let n = self.pop();
+                if !self.ip_mut().stacks.transfer(n) {
+                    self.reflect();
+                }
+            }
+            BefungeCommand::SYSINFO => self.sysinfo(),
+            BefungeCommand::SPLIT => self.split(),
+            BefungeCommand::QUIT => {
+                self.exit_code = Some(self.pop());
+                self.active = false;
+            }
+            BefungeCommand::LOAD_SEMANTICS | BefungeCommand::UNLOAD_SEMANTICS => {
+                self.semantics(op == BefungeCommand::LOAD_SEMANTICS)
+            }
+            b'A'..=b'Z' => self.execute_fingerprint(op)?,
+            _ => self.unknown(op as i32),
+        }
+
+        Ok(())
+    }
+
+    /// `(` and `)`: loads or unloads the semantics of the fingerprint whose
+    /// id is on the stack, reflecting if it is not registered.
+    fn semantics(&mut self, load: bool) {
+        let n = self.pop();
+        let mut id = 0i32;
+        for _i in 0..n.max(0) {
+            id = id.wrapping_mul(256).wrapping_add(self.pop());
+        }
+
+        let index = match self.fingerprints.find(id) {
+            Some(index) => index,
+            None => return self.reflect(),
+        };
+        let instructions = self.fingerprints.get(index).instructions().to_owned();
+        let ip = self.ip_mut();
+        for op in instructions.bytes().filter(u8::is_ascii_uppercase) {
+            let semantics = &mut ip.semantics[(op - b'A') as usize];
+            if load {
+                semantics.push(index);
+            } else {
+                semantics.pop();
+            }
+        }
+
+        if load {
+            self.push(id);
+            self.push(1);
+        }
+    }
+
+    /// Executes `A`-`Z` with the fingerprint most recently loaded for it,
+    /// reflecting if there is none.
+    fn execute_fingerprint(&mut self, op: u8) -> Result<(), BefungeError> {
+        let index = match self.ip().semantics[(op - b'A') as usize].last() {
+            Some(&index) => index,
+            None => {
+                self.reflect();
+                return Ok(());
+            }
+        };
+
+        let mut writes = self.history.as_ref().map(|_| Writes::default());
+        // The undo entry is borrowed for the I/O, so the writes are
+        // gathered apart and added to it afterwards.
+        let mut space = StoringSpace {
+            field: &mut self.field,
+            writes: writes.as_mut(),
+            profile: self.profile.as_mut(),
+            watchpoints: &self.watchpoints,
+            watch_hit: &mut self.watch_hit,
+        };
+        let mut io = LoggedIo {
+            io: &mut self.io,
+            history: self.history.as_mut(),
+        };
+        let mut context = Context {
+            ip: &mut self.ips[self.current],
+            space: &mut space,
+            io: &mut io,
+            rng: &mut self.rng,
+        };
+        let result = self.fingerprints.get_mut(index).execute(op, &mut context);
+
+        if let (Some(writes), Some(undo)) =
+            (writes, self.history.as_mut().and_then(History::last_mut))
+        {
+            undo.writes.append(writes);
+        }
+        result.map_err(|e| self.fault(BefungeErrorKind::Io(e)))
+    }
+
+    /// `k`: executes the next instruction in the IP's path `n` times from
+    /// the position of the `k`, or skips it if `n` is zero.
+    fn iterate(&mut self) -> Result<(), BefungeError> {
+        let n = self.pop();
+        if n < 0 {
+            self.reflect();
+            return Ok(());
+        }
+
+        let origin = self.pc();
+        self.move_pc();
+        self.skip_markers();
+        if n == 0 {
+            return Ok(());
+        }
+
+        let target = (self.ip().x, self.ip().y);
+        let instruction = self.current();
+        self.ip_mut().x = origin.0;
+        self.ip_mut().y = origin.1;
+
+        for _i in 0..n {
+            self.execute(instruction)?;
+            if !self.active || self.stopped {
+                break;
+            }
+        }
+
+        if self.pc() == origin {
+            self.ip_mut().x = target.0;
+            self.ip_mut().y = target.1;
+        }
+
+        Ok(())
+    }
+
+    /// `y`: pushes the Funge-98 system information, or only its `n`th cell
+    /// when `n` is positive.
+    fn sysinfo(&mut self) {
+        let n = self.pop();
+        let sizes = self.ip().stacks.sizes();
+        let (date, time) = sysinfo::date_and_time();
+        let bounds = self.field.bounds();
+        let base = self.ip().stacks.toss().len();
+
+        let mut cells = Vec::new();
+        let env: Vec<String> = env::vars().map(|(k, v)| format!("{}={}", k, v)).collect();
+        sysinfo::push_strings(&mut cells, env.iter().map(String::as_str));
+        sysinfo::push_strings(&mut cells, self.arguments.iter().map(String::as_str));
+        cells.extend(sizes.iter().rev().map(|&size| size as i32));
+        cells.push(sizes.len() as i32);
+        cells.push(time);
+        cells.push(date);
+        cells.extend(&[
+            bounds.max_x.wrapping_sub(bounds.min_x),
+            bounds.max_y.wrapping_sub(bounds.min_y),
+        ]);
+        cells.extend(&[bounds.min_x, bounds.min_y]);
+        cells.extend(&[self.ip().storage_offset.0, self.ip().storage_offset.1]);
+        cells.extend(&[self.ip().delta.dx, self.ip().delta.dy]);
+        cells.extend(&[self.ip().x, self.ip().y]);
+        // Team number, IP id and number of dimensions.
+        cells.extend(&[0, self.ip().id, 2]);
+        cells.push(MAIN_SEPARATOR as i32);
+        // Operating paradigm: `=` is unavailable.
+        cells.push(0);
+        cells.push(sysinfo::version());
+        cells.push(sysinfo::HANDPRINT);
+        cells.push(mem::size_of::<i32>() as i32);
+        // Flags: `t` but no `i`, `o` or `=`, and buffered output.
+        cells.push(1);
+
+        for &cell in cells.iter() {
+            self.push(cell);
+        }
+
+        if n > 0 {
+            let value = self.peek(n as usize - 1);
+            self.ip_mut().stacks.toss_mut().truncate(base);
+            self.push(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    #[test]
+    fn test_empty_field() {
+        let field = BefungeField::new(80, 25);
+        assert_eq!(field.get(0, 0), Some(BefungeCommand::NO_OP));
+        assert_eq!(field.get(79, 24), Some(BefungeCommand::NO_OP));
+        assert_eq!(field.get(80, 0), None);
+    }
+
+    #[test]
+    fn test_string_field() {
+        let field = BefungeField::from_str("0\n1\n", 80, 25);
+        assert_eq!(field.get(0, 0), Some(b'0'));
+        assert_eq!(field.get(1, 0), Some(b' '));
+        assert_eq!(field.get(0, 1), Some(b'1'));
+        assert_eq!(field.get(0, 2), Some(b' '));
+        assert_eq!(field.get(79, 24), Some(b' '));
+    }
+
+    #[test]
+    fn test_truncate() {
+        let field = BefungeField::from_str("012\n01\n01", 2, 2);
+        assert_eq!(field.get(0, 0), Some(b'0'));
+        assert_eq!(field.get(1, 0), Some(b'1'));
+        assert_eq!(field.get(2, 0), None);
+        assert_eq!(field.get(0, 1), Some(b'0'));
+        assert_eq!(field.get(1, 1), Some(b'1'));
+        assert_eq!(field.get(0, 2), None);
+    }
+
+    #[test]
+    fn test_horizontal_wrap_right() {
+        let mut exec = BefungeExecution::new(BefungeField::new(2, 1));
+        exec.step().unwrap();
+        let (x, _y, _delta) = exec.pc();
+        assert_eq!(x, 1);
+        exec.step().unwrap();
+        let (x, _y, _delta) = exec.pc();
+        assert_eq!(x, 0);
+        exec.step().unwrap();
+        let (x, _y, _delta) = exec.pc();
+        assert_eq!(x, 1);
+    }
+
+    #[test]
+    fn test_horizontal_wrap_left() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("<", 3, 1));
+        exec.step().unwrap();
+        let (x, _y, _delta) = exec.pc();
+        assert_eq!(x, 2);
+        exec.step().unwrap();
+        let (x, _y, _delta) = exec.pc();
+        assert_eq!(x, 1);
+        exec.step().unwrap();
+        let (x, _y, _delta) = exec.pc();
+        assert_eq!(x, 0);
+    }
+
+    #[test]
+    fn test_vertical_wrap_down() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("v", 1, 2));
+        exec.step().unwrap();
+        let (_x, y, _delta) = exec.pc();
+        assert_eq!(y, 1);
+        exec.step().unwrap();
+        let (_x, y, _delta) = exec.pc();
+        assert_eq!(y, 0);
+        exec.step().unwrap();
+        let (_x, y, _delta) = exec.pc();
+        assert_eq!(y, 1);
+    }
+
+    #[test]
+    fn test_vertical_wrap_up() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("^", 1, 2));
+        exec.step().unwrap();
+        let (_x, y, _delta) = exec.pc();
+        assert_eq!(y, 1);
+        exec.step().unwrap();
+        let (_x, y, _delta) = exec.pc();
+        assert_eq!(y, 0);
+        exec.step().unwrap();
+        let (_x, y, _delta) = exec.pc();
+        assert_eq!(y, 1);
+    }
+
+    #[test]
+    fn test_push_digits() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("0123456789", 10, 1));
+
+        for _i in 0..10 {
+            exec.step().unwrap();
+        }
+
+        assert_eq!(exec.stack(), vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9])
+    }
+
+    #[test]
+    fn test_string_mode() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("\"0123456789\"0", 13, 1));
+
+        for _i in 0..13 {
+            exec.step().unwrap();
+        }
+
+        assert_eq!(
+            exec.stack(),
+            vec![48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 0]
+        )
+    }
+
+    #[test]
+    fn test_read_cell() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("g", 1, 1));
+
+        exec.step().unwrap();
+
+        assert_eq!(exec.stack(), vec![103])
+    }
+
+    #[test]
+    fn test_write_cell() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("p", 1, 1));
+
+        exec.step().unwrap();
+
+        assert_eq!(exec.get(0, 0), Some(0));
+    }
+
+    #[test]
+    fn test_narrow_cells() {
+        let mut exec = BefungeExecution::with_io(
+            BefungeField::from_str("88*5*00p00g01-10p10g@", 80, 1),
+            MemoryIo::default(),
+        );
+        exec.run().unwrap();
+        assert_eq!(exec.stack(), vec![64, 255]);
+    }
+
+    #[test]
+    fn test_wide_cells() {
+        let field = BefungeField::<i32>::from_source("88*5*00p00g01-10p10g@", 80, 1);
+        let mut exec = BefungeExecution::with_io(field, MemoryIo::default());
+        exec.run().unwrap();
+        assert_eq!(exec.stack(), vec![320, -1]);
+        assert_eq!(exec.field().get(1, 0), Some(-1));
+
+        let field = BefungeField::<i64>::from_source("01-00p00g@", 80, 1);
+        let mut exec = BefungeExecution::with_io(field, MemoryIo::default());
+        exec.run().unwrap();
+        assert_eq!(exec.stack(), vec![-1]);
+    }
+
+    #[test]
+    fn test_negate() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("!!", 2, 1));
+
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), vec![1]);
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), vec![0]);
+    }
+
+    #[test]
+    fn test_swap() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("01\\", 3, 1));
+
+        exec.step().unwrap();
+        exec.step().unwrap();
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), vec![1, 0]);
+    }
+
+    #[test]
+    fn test_add() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("12+", 3, 1));
+
+        exec.step().unwrap();
+        exec.step().unwrap();
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), vec![3]);
+    }
+
+    #[test]
+    fn test_subtract() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("12-", 3, 1));
+
+        exec.step().unwrap();
+        exec.step().unwrap();
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), vec![-1]);
+    }
+
+    #[test]
+    fn test_multiply() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("12*", 3, 1));
+
+        exec.step().unwrap();
+        exec.step().unwrap();
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), vec![2]);
+    }
+
+    #[test]
+    fn test_divide() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("72/", 3, 1));
+
+        exec.step().unwrap();
+        exec.step().unwrap();
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), vec![3]);
+    }
+
+    #[test]
+    fn test_modulo() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("23%", 3, 1));
+
+        exec.step().unwrap();
+        exec.step().unwrap();
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), vec![2]);
+    }
+
+    #[test]
+    fn test_compare() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("12`", 3, 1));
+
+        exec.step().unwrap();
+        exec.step().unwrap();
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), vec![0]);
+
+        let mut exec = BefungeExecution::new(BefungeField::from_str("21`", 3, 1));
+
+        exec.step().unwrap();
+        exec.step().unwrap();
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), vec![1]);
+    }
+
+    #[test]
+    fn test_duplicate() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("1:", 2, 1));
+
+        exec.step().unwrap();
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), vec![1, 1]);
+    }
+
+    #[test]
+    fn test_discard() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("1$", 2, 1));
+
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), vec![1]);
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn test_if_left_right() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("1_", 2, 1));
+
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), vec![1]);
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), Vec::<i32>::new());
+        let (x, _y, delta) = exec.pc();
+        assert_eq!(x, 0);
+        assert_eq!(delta, Delta::LEFT);
+
+        let mut exec = BefungeExecution::new(BefungeField::from_str("0_", 2, 1));
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), vec![0]);
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), Vec::<i32>::new());
+        let (x, _y, delta) = exec.pc();
+        assert_eq!(x, 0);
+        assert_eq!(delta, Delta::RIGHT);
+    }
+
+    #[test]
+    fn test_if_up_down() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("1|", 2, 2));
+
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), vec![1]);
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), Vec::<i32>::new());
+        let (_x, y, delta) = exec.pc();
+        assert_eq!(y, 1);
+        assert_eq!(delta, Delta::UP);
+
+        let mut exec = BefungeExecution::new(BefungeField::from_str("0|", 2, 2));
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), vec![0]);
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), Vec::<i32>::new());
+        let (_x, y, delta) = exec.pc();
+        assert_eq!(y, 1);
+        assert_eq!(delta, Delta::DOWN);
+    }
+
+    #[test]
+    fn test_write_int() {
+        let mut exec =
+            BefungeExecution::with_io(BefungeField::from_str("12..", 4, 1), MemoryIo::default());
+        exec.step().unwrap();
+        exec.step().unwrap();
+        exec.step().unwrap();
+        exec.step().unwrap();
+        assert_eq!(exec.io().output_string(), "2 1 ");
+    }
+
+    #[test]
+    fn test_write_char() {
+        let mut exec =
+            BefungeExecution::with_io(BefungeField::from_str("\"a\",", 4, 1), MemoryIo::default());
+        exec.step().unwrap();
+        exec.step().unwrap();
+        exec.step().unwrap();
+        exec.step().unwrap();
+        assert_eq!(exec.io().output_string(), "a");
+    }
+
+    #[test]
+    fn test_read_int() {
+        let mut exec = BefungeExecution::with_io(
+            BefungeField::from_str("&&&", 3, 1),
+            MemoryIo::from_input("12\n-3\n"),
+        );
+        exec.step().unwrap();
+        exec.step().unwrap();
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), vec![12, -3, -1]);
+    }
+
+    #[test]
+    fn test_read_char() {
+        let mut exec = BefungeExecution::with_io(
+            BefungeField::from_str("~~", 2, 1),
+            MemoryIo::from_input("a"),
+        );
+        exec.step().unwrap();
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), vec![97, -1]);
+    }
+
+    #[test]
+    fn test_run_to_memory() {
+        let mut exec = BefungeExecution::with_io(
+            BefungeField::from_str("\"!ih\",,,@", 80, 25),
+            MemoryIo::default(),
+        );
+        assert_eq!(exec.run().unwrap(), StepOutcome::Halted);
+        assert_eq!(exec.into_io().output_string(), "hi!");
+    }
+
+    #[test]
+    fn test_divide_by_zero() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("50/", 3, 1));
+
+        exec.step().unwrap();
+        exec.step().unwrap();
+        let err = exec.step().unwrap_err();
+        assert!(matches!(err.kind(), BefungeErrorKind::DivisionByZero));
+        assert_eq!(err.position(), (2, 0));
+        assert_eq!(err.instruction(), b'/' as i32);
+        assert_eq!(err.stack(), &[5, 0]);
+    }
+
+    #[test]
+    fn test_invalid_character() {
+        let mut exec =
+            BefungeExecution::with_io(BefungeField::from_str("01-,", 4, 1), MemoryIo::default());
+
+        exec.step().unwrap();
+        exec.step().unwrap();
+        exec.step().unwrap();
+        let err = exec.step().unwrap_err();
+        assert!(matches!(err.kind(), BefungeErrorKind::InvalidCharacter(-1)));
+        assert_eq!(err.stack(), &[-1]);
+    }
+
+    #[test]
+    fn test_cell_out_of_bounds() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("01-0g", 5, 1));
+
+        for _i in 0..4 {
+            exec.step().unwrap();
+        }
+        let err = exec.step().unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            BefungeErrorKind::OutOfBounds { x: -1, y: 0 }
+        ));
+
+        let mut exec = BefungeExecution::new(BefungeField::from_str("105p", 4, 1));
+
+        for _i in 0..3 {
+            exec.step().unwrap();
+        }
+        assert!(exec.step().is_err());
+    }
+
+    #[test]
+    fn test_step_after_halt() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("@", 1, 1));
+
+        assert_eq!(exec.step().unwrap(), StepOutcome::Halted);
+        assert_eq!(exec.step().unwrap(), StepOutcome::Halted);
+    }
+
+    #[test]
+    fn test_random() {
+        let run = |seed| {
+            let mut exec = BefungeExecution::builder(BefungeField::from_str("?", 1, 1))
+                .seed(seed)
+                .build();
+            (0..64)
+                .map(|_i| {
+                    exec.step().unwrap();
+                    exec.pc().2
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let deltas = run(1);
+        assert_eq!(deltas, run(1));
+        for delta in Delta::ALL.iter() {
+            assert!(deltas.contains(delta));
+        }
+    }
+
+    #[test]
+    fn test_negative_is_true() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("01-!", 4, 1));
+        for _i in 0..4 {
+            exec.step().unwrap();
+        }
+        assert_eq!(exec.stack(), vec![0]);
+
+        let mut exec = BefungeExecution::new(BefungeField::from_str("01-_", 4, 1));
+        for _i in 0..4 {
+            exec.step().unwrap();
+        }
+        assert_eq!(exec.pc().2, Delta::LEFT);
+
+        let mut exec = BefungeExecution::new(BefungeField::from_str("01-|", 4, 2));
+        for _i in 0..4 {
+            exec.step().unwrap();
+        }
+        assert_eq!(exec.pc().2, Delta::UP);
+    }
+
+    #[test]
+    fn test_unknown_instruction() {
+        let mut exec = BefungeExecution::new(BefungeField::from_str("1x", 2, 1));
+        exec.step().unwrap();
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), vec![1]);
+    }
+
+    fn legacy(program: &str) -> BefungeExecution<MemoryIo> {
+        BefungeExecution::builder(BefungeField::from_str(program, 80, 1))
+            .io(MemoryIo::default())
+            .dialect(Dialect::Legacy)
+            .build()
+    }
+
+    #[test]
+    fn test_legacy_operand_order() {
+        for (program, expected) in
+            [("12-", 1), ("12/", 2), ("23%", 1), ("12`", 1), ("21`", 0)].iter()
+        {
+            let mut exec = legacy(program);
+            for _i in 0..3 {
+                exec.step().unwrap();
+            }
+            assert_eq!(exec.stack(), vec![*expected], "{}", program);
+        }
+    }
+
+    #[test]
+    fn test_legacy_truthiness() {
+        let mut exec = legacy("10-!");
+        for _i in 0..4 {
+            exec.step().unwrap();
+        }
+        assert_eq!(exec.stack(), vec![1]);
+
+        let mut exec = legacy("10-_");
+        for _i in 0..4 {
+            exec.step().unwrap();
+        }
+        assert_eq!(exec.pc().2, Delta::RIGHT);
+    }
+
+    #[test]
+    fn test_legacy_output_and_unknown() {
+        let mut exec = legacy("12..x@");
+        exec.run().unwrap();
+        assert_eq!(exec.stack(), vec![120]);
+        assert_eq!(exec.io().output_string(), "21");
+    }
+
+    fn funge98(program: &str) -> BefungeExecution<MemoryIo, SparseSpace> {
+        BefungeExecution::builder(SparseSpace::from(program))
+            .io(MemoryIo::default())
+            .dialect(Dialect::Funge98)
+            .build()
+    }
+
+    fn run_funge98(program: &str) -> BefungeExecution<MemoryIo, SparseSpace> {
+        let mut exec = funge98(program);
+        exec.run().unwrap();
+        exec
+    }
+
+    #[test]
+    fn test_funge98_hex_digits() {
+        assert_eq!(run_funge98("abcdef@").stack(), vec![10, 11, 12, 13, 14, 15]);
+    }
+
+    #[test]
+    fn test_funge98_fetch_and_store() {
+        assert_eq!(run_funge98("'A,@").io().output_string(), "A");
+        assert_eq!(run_funge98("'vs @").stack(), Vec::<i32>::new());
+
+        let exec = run_funge98("'Xs 30g@");
+        assert_eq!(exec.stack(), vec![b'X' as i32]);
+    }
+
+    #[test]
+    fn test_funge98_jump_over() {
+        assert_eq!(run_funge98(";123;4@").stack(), vec![4]);
+    }
+
+    #[test]
+    fn test_funge98_spaces_take_no_time() {
+        let mut exec = funge98("1    2@");
+        exec.step().unwrap();
+        exec.step().unwrap();
+        assert_eq!(exec.stack(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_funge98_string_spaces() {
+        assert_eq!(run_funge98("\"a   b\"@").stack(), vec![97, 32, 98]);
+    }
+
+    #[test]
+    fn test_funge98_jump() {
+        assert_eq!(run_funge98("2j345@").stack(), vec![5]);
+        assert_eq!(run_funge98("v\n>01-j@\n>  7@").stack(), Vec::<i32>::new());
+    }
+
+    #[test]
+    fn test_funge98_iterate() {
+        assert_eq!(run_funge98("3k1@").stack(), vec![1, 1, 1]);
+        assert_eq!(run_funge98("0k1@").stack(), Vec::<i32>::new());
+        assert_eq!(run_funge98("2k 5@").stack(), vec![5, 5]);
+    }
+
+    #[test]
+    fn test_funge98_turns() {
+        let mut exec = funge98("[");
+        exec.step().unwrap();
+        assert_eq!(exec.pc().2, Delta::UP);
+
+        let mut exec = funge98("]");
+        exec.step().unwrap();
+        assert_eq!(exec.pc().2, Delta::DOWN);
+
+        let mut exec = funge98("r");
+        exec.step().unwrap();
+        assert_eq!(exec.pc().2, Delta::LEFT);
+
+        let mut exec = funge98("12w");
+        for _i in 0..3 {
+            exec.step().unwrap();
+        }
+        assert_eq!(exec.pc().2, Delta::UP);
+
+        let mut exec = funge98("23 1-x");
+        for _i in 0..5 {
+            exec.step().unwrap();
+        }
+        assert_eq!(exec.pc().2, Delta::new(2, 2));
+    }
+
+    #[test]
+    fn test_funge98_clear_stack() {
+        assert_eq!(run_funge98("123n4@").stack(), vec![4]);
+    }
+
+    #[test]
+    fn test_funge98_stack_stack() {
+        let mut exec = funge98("1232{@");
+        for _i in 0..5 {
+            exec.step().unwrap();
+        }
+        assert_eq!(exec.stack(), vec![2, 3]);
+        assert_eq!(exec.stacks().sizes(), vec![2, 3]);
+        assert_eq!(exec.storage_offset(), (5, 0));
+
+        let exec = run_funge98("120{71}@");
+        assert_eq!(exec.stack(), vec![1, 2, 7]);
+        assert_eq!(exec.storage_offset(), (0, 0));
+
+        let exec = run_funge98("120{2u@");
+        assert_eq!(exec.stack(), vec![0, 0]);
+    }
+
+    #[test]
+    fn test_funge98_storage_offset() {
+        // `{` sets the offset to (4, 0), so `g` at (0, 0) reads the `@`.
+        let exec = run_funge98("0{00g@");
+        assert_eq!(exec.stack(), vec![b'0' as i32]);
+    }
+
+    #[test]
+    fn test_funge98_sysinfo() {
+        assert_eq!(run_funge98("2y@").stack(), vec![4]);
+        assert_eq!(run_funge98("7y@").stack(), vec![2]);
+        assert_eq!(run_funge98("5y@").stack(), vec![0]);
+
+        let exec = run_funge98("0y@");
+        let stack = exec.stack();
+        assert_eq!(stack[stack.len() - 1], 1);
+        assert_eq!(stack[stack.len() - 3], sysinfo::HANDPRINT);
+    }
+
+    #[test]
+    fn test_funge98_split() {
+        let mut exec = funge98("0t5.@");
+        exec.step().unwrap();
+        exec.step().unwrap();
+
+        let ids: Vec<i32> = exec.ips().iter().map(InstructionPointer::id).collect();
+        assert_eq!(ids, vec![1, 0]);
+        assert_eq!(exec.ip().id(), 1);
+        assert_eq!(exec.ip().delta(), Delta::LEFT);
+        assert_eq!(exec.ip().position(), (0, 0));
+        assert_eq!(exec.ips()[1].position(), (2, 0));
+
+        // The child pushes its 0 and dies on the `@` it wraps onto, leaving
+        // the parent to finish alone.
+        exec.run().unwrap();
+        assert_eq!(exec.ips().len(), 1);
+        assert_eq!(exec.ip().id(), 0);
+        assert_eq!(exec.stack(), vec![0]);
+        assert_eq!(exec.io().output_string(), "5 ");
+    }
+
+    #[test]
+    fn test_funge98_round_robin() {
+        // The parent jumps onto the `t`; the child heads left printing `a`
+        // and `c` while the parent heads right printing `b` and `d`.
+        let exec = run_funge98("9j@,c',a'  t'b,'d,@");
+        assert_eq!(exec.io().output_string(), "abcd");
+    }
+
+    #[test]
+    fn test_funge98_quit() {
+        let mut exec = funge98("7q");
+        assert_eq!(exec.run().unwrap(), StepOutcome::Quit(7));
+        assert_eq!(exec.step().unwrap(), StepOutcome::Quit(7));
+    }
+
+    #[test]
+    fn test_funge98_reflects() {
+        let mut exec = funge98("X");
+        exec.step().unwrap();
+        assert_eq!(exec.pc().2, Delta::LEFT);
+
+        let mut exec = funge98("~");
+        exec.step().unwrap();
+        assert_eq!(exec.pc().2, Delta::LEFT);
+
+        let mut exec = funge98("0(");
+        exec.step().unwrap();
+        exec.step().unwrap();
+        assert_eq!(exec.pc().2, Delta::LEFT);
+    }
+
+    #[test]
+    fn test_funge98_fingerprint_semantics() {
+        // `I` is ROMA's 1 until NULL is loaded over it, and again once NULL
+        // is unloaded.
+        let exec = run_funge98("\"AMOR\"4($$I\"LLUN\"4($$\"LLUN\"4)I@");
+        assert_eq!(exec.stack(), vec![1, 1]);
+
+        let mut exec = funge98("\"AMOR\"4($$\"AMOR\"4)I");
+        for _i in 0..19 {
+            exec.step().unwrap();
+        }
+        assert_eq!(exec.pc().2, Delta::LEFT);
+    }
+
+    #[test]
+    fn test_funge98_division_by_zero() {
+        assert_eq!(run_funge98("50/50%@").stack(), vec![0, 0]);
+    }
+
+    #[test]
+    fn test_funge98_reads_past_edge() {
+        assert_eq!(run_funge98("01-0g@").stack(), vec![32]);
+    }
+
+    #[test]
+    fn test_funge98_unbounded_space() {
+        let exec = run_funge98("5 01-0p 01-0g 5a*:*0p 5a*:*0g@");
+        assert_eq!(exec.stack(), vec![5]);
+        assert_eq!(exec.get(-1, 0), Some(5));
+        assert_eq!(exec.get(2500, 0), Some(5));
+        assert_eq!(exec.get(2500, 1), Some(32));
+        assert_eq!(exec.field().bounds().max_x, 2500);
+    }
+
+    #[test]
+    fn test_funge98_lahey_wrap() {
+        // Moving left off the origin wraps to the far end of the line.
+        assert_eq!(run_funge98("<@1").stack(), vec![1]);
+
+        let exec = run_funge98("<@2\n\n  v\n  5");
+        assert_eq!(exec.stack(), vec![2]);
+    }
+
+    #[test]
+    fn test_funge98_sysinfo_bounds() {
+        let exec = run_funge98("a9+y98+y@");
+        assert_eq!(exec.stack(), vec![8, 0]);
+
+        let exec = run_funge98("801-pa9+y98+y99+y88+y@");
+        assert_eq!(exec.stack(), vec![21, 0, 1, -1]);
+    }
+
+    #[test]
+    fn test_position_breakpoint() {
+        let mut exec = BefungeExecution::builder(BefungeField::from_str("123@", 80, 1))
+            .io(MemoryIo::default())
+            .build();
+        exec.add_breakpoint(Breakpoint::Position(2, 0));
+        assert_eq!(
+            exec.run().unwrap(),
+            StepOutcome::Breakpoint(Breakpoint::Position(2, 0))
+        );
+        assert_eq!(exec.stack(), vec![1, 2]);
+
+        // Running again carries on past the breakpoint.
+        assert_eq!(exec.run().unwrap(), StepOutcome::Halted);
+        assert_eq!(exec.stack(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_instruction_and_stack_breakpoints() {
+        let mut exec = BefungeExecution::builder(BefungeField::from_str("\"+\"9+5.@", 80, 1))
+            .io(MemoryIo::default())
+            .build();
+        exec.add_breakpoint(Breakpoint::Instruction(b'+' as i32));
+        exec.add_breakpoint(Breakpoint::StackTop(5));
+        assert!(!exec.remove_breakpoint(Breakpoint::StackDepth(1)));
+
+        // The `+` in string mode does not count.
+        assert_eq!(
+            exec.run().unwrap(),
+            StepOutcome::Breakpoint(Breakpoint::Instruction(b'+' as i32))
+        );
+        assert_eq!(exec.pc().0, 4);
+        assert_eq!(
+            exec.run().unwrap(),
+            StepOutcome::Breakpoint(Breakpoint::StackTop(5))
+        );
+
+        assert!(exec.remove_breakpoint(Breakpoint::StackTop(5)));
+        exec.add_breakpoint(Breakpoint::StackDepth(0));
+        assert_eq!(
+            exec.run().unwrap(),
+            StepOutcome::Breakpoint(Breakpoint::StackDepth(0))
+        );
+        assert_eq!(exec.breakpoints().len(), 2);
+    }
+
+    #[test]
+    fn test_funge98_breakpoint_skips_markers() {
+        let mut exec = funge98("1  ;2;3@");
+        exec.add_breakpoint(Breakpoint::Instruction(b'3' as i32));
+        assert_eq!(
+            exec.run().unwrap(),
+            StepOutcome::Breakpoint(Breakpoint::Instruction(b'3' as i32))
+        );
+        assert_eq!(exec.stack(), vec![1]);
+        // Only its turn moves the IP past the markers.
+        assert_eq!(exec.pc().0, 1);
+        assert_eq!(
+            exec.hit_breakpoint(),
+            Some(Breakpoint::Instruction(b'3' as i32))
+        );
+        assert_eq!(exec.pc().0, 1);
+
+        assert_eq!(exec.run().unwrap(), StepOutcome::Halted);
+        assert_eq!(exec.stack(), vec![1, 3]);
+    }
+
+    #[test]
+    fn test_watchpoint() {
+        let mut exec = BefungeExecution::builder(BefungeField::from_str("500p700p@", 80, 1))
+            .io(MemoryIo::default())
+            .build();
+        exec.watch(0, 0);
+        assert_eq!(exec.watchpoints().collect::<Vec<_>>(), vec![(0, 0)]);
+        assert_eq!(
+            exec.run().unwrap(),
+            StepOutcome::Watchpoint(Watch {
+                x: 0,
+                y: 0,
+                old: b'5' as i32,
+                new: 5,
+            })
+        );
+        assert_eq!(exec.pc().0, 4);
+
+        assert!(exec.unwatch(0, 0));
+        assert_eq!(exec.run().unwrap(), StepOutcome::Halted);
+        assert_eq!(exec.get(0, 0), Some(7));
+    }
+
+    #[test]
+    fn test_fingerprint_write_watched() {
+        // ORTH's `P` writes 5 over the second `$`.
+        let program = "\"HTRO\"4($$509P@";
+        let mut exec = BefungeExecution::builder(SparseSpace::from(program))
+            .io(MemoryIo::default())
+            .dialect(Dialect::Funge98)
+            .profile()
+            .build();
+        exec.watch(9, 0);
+        assert_eq!(
+            exec.run().unwrap(),
+            StepOutcome::Watchpoint(Watch {
+                x: 9,
+                y: 0,
+                old: b'$' as i32,
+                new: 5,
+            })
+        );
+        assert_eq!(exec.profile().unwrap().writes(9, 0), 1);
+    }
+
+    type State = (
+        Vec<InstructionPointer>,
+        Bounds,
+        Vec<i32>,
+        String,
+        StepOutcome,
+    );
+
+    /// Everything `step_back` restores, with only the output that has not
+    /// been rewound.
+    fn state<F: FungeSpace>(exec: &BefungeExecution<MemoryIo, F>) -> State {
+        let bounds = exec.field().bounds();
+        let cells = (bounds.min_y..=bounds.max_y)
+            .flat_map(|y| (bounds.min_x..=bounds.max_x).map(move |x| (x, y)))
+            .map(|(x, y)| exec.field().cell(x, y))
+            .collect();
+        let output = exec.io().output_string();
+        let shown = output[..output.len() - exec.rewound_output().len()].to_string();
+        (exec.ips().to_vec(), bounds, cells, shown, exec.outcome())
+    }
+
+    /// Runs `exec` to the end, then checks that stepping back passes
+    /// through every earlier state and that running again ends the same way.
+    fn assert_rewinds<F: FungeSpace>(mut exec: BefungeExecution<MemoryIo, F>) {
+        let mut states = vec![state(&exec)];
+        while exec.step().unwrap() == StepOutcome::Continue {
+            states.push(state(&exec));
+        }
+        let end = state(&exec);
+        assert_eq!(exec.history_len(), states.len());
+
+        for expected in states.iter().rev() {
+            assert!(exec.step_back());
+            assert_eq!(&state(&exec), expected);
+        }
+        assert!(!exec.step_back());
+
+        exec.run().unwrap();
+        assert_eq!(state(&exec), end);
+        assert_eq!(exec.rewound_output(), "");
+    }
+
+    #[test]
+    fn test_step_back() {
+        let program = "&&+:.~,\"a\"00p@";
+        let exec = BefungeExecution::builder(BefungeField::from_str(program, 80, 1))
+            .io(MemoryIo::from_input("3 4\nz"))
+            .history(1 << 20)
+            .build();
+        assert_rewinds(exec);
+    }
+
+    #[test]
+    fn test_funge98_step_back() {
+        let program = "t\"AMOR\"4($$X12{3}n'a,2k4 99a*9p~,@";
+        let exec = BefungeExecution::builder(SparseSpace::from(program))
+            .io(MemoryIo::from_input("Z"))
+            .dialect(Dialect::Funge98)
+            .seed(1)
+            .history(1 << 20)
+            .build();
+        assert_rewinds(exec);
+    }
+
+    #[test]
+    fn test_history_budget() {
+        let mut exec = BefungeExecution::builder(BefungeField::from_str("1>:1+v\n ^   <", 80, 2))
+            .io(MemoryIo::default())
+            .history(4096)
+            .build();
+        for _i in 0..1000 {
+            exec.step().unwrap();
+        }
+        let len = exec.history_len();
+        assert!(len > 10 && len < 1000, "{}", len);
+
+        for _i in 0..len {
+            assert!(exec.step_back());
+        }
+        assert!(!exec.step_back());
+        assert_eq!(
+            BefungeExecution::new(BefungeField::new(1, 1)).history_len(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_run_back_to_breakpoint() {
+        let mut exec = BefungeExecution::builder(BefungeField::from_str("500p600p1@", 80, 1))
+            .io(MemoryIo::default())
+            .history(1 << 20)
+            .build();
+        exec.run().unwrap();
+        exec.watch(0, 0);
+        exec.add_breakpoint(Breakpoint::Instruction(b'5' as i32));
+
+        // Back across the second `p`, which wrote 6 over 5.
+        assert_eq!(
+            exec.run_back_to_breakpoint(),
+            Some(StepOutcome::Watchpoint(Watch {
+                x: 0,
+                y: 0,
+                old: 5,
+                new: 6,
+            }))
+        );
+        assert_eq!(exec.pc().0, 7);
+
+        assert!(exec.unwatch(0, 0));
+        assert!(exec.remove_breakpoint(Breakpoint::Instruction(b'5' as i32)));
+        exec.add_breakpoint(Breakpoint::Position(4, 0));
+        assert_eq!(
+            exec.run_back_to_breakpoint(),
+            Some(StepOutcome::Breakpoint(Breakpoint::Position(4, 0)))
+        );
+        assert_eq!(exec.stack(), Vec::<i32>::new());
+        assert_eq!(exec.run_back_to_breakpoint(), None);
+        assert_eq!(exec.pc().0, 0);
+    }
+
+    /// Checks that a snapshot taken after any number of steps, restored
+    /// into an execution built around a blank space, carries on exactly as
+    /// the original does.
+    fn assert_resumes<F: FungeSpace>(build: impl Fn(bool) -> BefungeExecution<MemoryIo, F>) {
+        let mut reference = build(true);
+        while reference.step().unwrap() == StepOutcome::Continue {}
+        let (ips, bounds, cells, output, outcome) = state(&reference);
+
+        let mut original = build(true);
+        loop {
+            let snapshot = original.snapshot();
+            let mut resumed = build(false);
+            resumed.restore(&snapshot).unwrap();
+            assert_eq!(resumed.snapshot(), snapshot);
+
+            while resumed.step().unwrap() == StepOutcome::Continue {}
+            let end = state(&resumed);
+            assert_eq!(
+                (&end.0, end.1, &end.2, end.4),
+                (&ips, bounds, &cells, outcome)
+            );
+            assert_eq!(original.io().output_string() + &end.3, output);
+
+            if original.step().unwrap() != StepOutcome::Continue {
+                break;
+            }
+        }
+    }
+
+    #[test]
+    fn test_snapshot_resume() {
+        let program = "&&+:.~,\"a\"00p?@";
+        assert_resumes(|loaded| {
+            BefungeExecution::builder(BefungeField::from_str(
+                if loaded { program } else { "" },
+                80,
+                1,
+            ))
+            .io(MemoryIo::from_input("3 4\nz"))
+            .seed(5)
+            .build()
+        });
+    }
+
+    #[test]
+    fn test_funge98_snapshot_resume() {
+        let program = "t\"AMOR\"4($$X12{3}n'a,2k4 99a*9p~,@";
+        assert_resumes(|loaded| {
+            BefungeExecution::builder(SparseSpace::from(if loaded { program } else { "" }))
+                .io(MemoryIo::from_input("Z"))
+                .dialect(Dialect::Funge98)
+                .seed(1)
+                .build()
+        });
+    }
+
+    #[test]
+    fn test_restore_failures() {
+        let mut exec = funge98("\"LLUN\"4(@");
+        exec.run().unwrap();
+        let snapshot = exec.snapshot();
+        assert_eq!(snapshot.ips[0].semantics[0], vec!["NULL"]);
+
+        let mut without = BefungeExecution::builder(SparseSpace::new())
+            .io(MemoryIo::default())
+            .fingerprints(FingerprintRegistry::new())
+            .build();
+        assert!(matches!(
+            without.restore(&snapshot),
+            Err(SnapshotError::UnknownFingerprint(name)) if name == "NULL"
+        ));
+        assert_eq!(without.steps(), 0);
+
+        let mut small = BefungeExecution::builder(BefungeField::new(2, 1))
+            .io(MemoryIo::default())
+            .build();
+        assert!(matches!(
+            small.restore(&snapshot),
+            Err(SnapshotError::OutOfBounds { x: 2, y: 0 })
+        ));
+    }
+
+    /// Input that fails to read.
+    struct BrokenInput;
+
+    fn broken() -> std::io::Error {
+        std::io::Error::new(std::io::ErrorKind::BrokenPipe, "broken")
+    }
+
+    impl Read for BrokenInput {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(broken())
+        }
+    }
+
+    impl std::io::BufRead for BrokenInput {
+        fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+            Err(broken())
+        }
+
+        fn consume(&mut self, _amt: usize) {}
+    }
+
+    #[test]
+    fn test_restore_skip_failure() {
+        let mut exec = BefungeExecution::builder(BefungeField::from_str("~1@", 80, 1))
+            .io(MemoryIo::from_input("x"))
+            .build();
+        exec.step().unwrap();
+        let snapshot = exec.snapshot();
+        assert_eq!(snapshot.input_position, 1);
+
+        let mut broken = BefungeExecution::builder(BefungeField::from_str(">", 80, 1))
+            .io(StreamIo::new(BrokenInput, Vec::new()))
+            .build();
+        assert!(matches!(
+            broken.restore(&snapshot),
+            Err(SnapshotError::Io(_))
+        ));
+        assert_eq!(broken.field().cell(0, 0), '>' as i32);
+        assert_eq!(broken.field().cell(1, 0), ' ' as i32);
+        assert_eq!(broken.steps(), 0);
+        assert_eq!(broken.pc().0, 0);
+    }
+
+    fn limited(program: &str, limits: Limits) -> BefungeExecution<MemoryIo> {
+        BefungeExecution::builder(BefungeField::from_str(program, 80, 1))
+            .io(MemoryIo::default())
+            .limits(limits)
+            .build()
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let mut exec = limited("1.@", Limits::default().steps(2));
+        assert_eq!(
+            exec.run().unwrap(),
+            StepOutcome::LimitExceeded(Limit::Steps(2))
+        );
+        assert_eq!(exec.steps(), 2);
+        assert_eq!(exec.pc().0, 2);
+        assert_eq!(exec.io().output_string(), "1 ");
+
+        // Running again stops straight away until the limit is raised.
+        assert_eq!(
+            exec.run().unwrap(),
+            StepOutcome::LimitExceeded(Limit::Steps(2))
+        );
+        exec.set_limits(Limits::default().steps(3));
+        assert_eq!(exec.run().unwrap(), StepOutcome::Halted);
+        assert_eq!(exec.steps(), 3);
+    }
+
+    #[test]
+    fn test_stack_limit() {
+        let mut exec = limited("1", Limits::default().stack(10).steps(1000));
+        assert_eq!(
+            exec.run().unwrap(),
+            StepOutcome::LimitExceeded(Limit::Stack(10))
+        );
+        assert_eq!(exec.stack(), vec![1; 11]);
+    }
+
+    #[test]
+    fn test_time_limit() {
+        let time = Duration::from_millis(10);
+        let mut exec = limited(">", Limits::default().time(time));
+        assert_eq!(
+            exec.run().unwrap(),
+            StepOutcome::LimitExceeded(Limit::Time(time))
+        );
+        assert!(exec.steps() > 0);
+    }
+
+    #[test]
+    fn test_step_back_counts_steps() {
+        let mut exec = BefungeExecution::builder(BefungeField::from_str("12@", 80, 1))
+            .io(MemoryIo::default())
+            .history(1 << 20)
+            .build();
+        exec.run().unwrap();
+        assert_eq!(exec.steps(), 3);
+        assert!(exec.step_back());
+        assert_eq!(exec.steps(), 2);
     }
 }
@@ -0,0 +1,50 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Bounds on how far `BefungeExecution::run` may go. Every limit is off by
+/// default.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Limits {
+    /// The most instructions the execution may execute in total.
+    pub steps: Option<u64>,
+    /// The longest each call to `run` may take. It is checked every few
+    /// hundred instructions, so it can be overshot slightly.
+    pub time: Option<Duration>,
+    /// The most values any IP may hold across its stack stack.
+    pub stack: Option<usize>,
+}
+
+impl Limits {
+    pub fn steps(mut self, steps: u64) -> Self {
+        self.steps = Some(steps);
+        self
+    }
+
+    pub fn time(mut self, time: Duration) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    pub fn stack(mut self, stack: usize) -> Self {
+        self.stack = Some(stack);
+        self
+    }
+}
+
+/// The limit `run` stopped at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Limit {
+    Steps(u64),
+    Time(Duration),
+    Stack(usize),
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Limit::Steps(steps) => write!(f, "limit of {} steps", steps),
+            Limit::Time(time) => write!(f, "time limit of {:?}", time),
+            Limit::Stack(stack) => write!(f, "stack limit of {} values", stack),
+        }
+    }
+}
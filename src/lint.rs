@@ -0,0 +1,501 @@
+//! Warnings about Befunge-93 programs, found without running them.
+//!
+//! The checks walk the same reachable states as the control-flow graph, so
+//! they share its blind spot: `p` is taken to change nothing.
+
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::cfg::{ControlFlowGraph, EdgeKind, StateGraph};
+use crate::load;
+use crate::{BefungeCommand, BefungeField, Delta, FungeSpace, LoadIssue};
+
+/// What a `Lint` warns about.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LintKind {
+    /// Cells holding something no path reaches, with their contents.
+    Unreachable(String),
+    /// No path on from here, starting out this way, reaches `@`.
+    NeverStops(Delta),
+    /// A string whose only closing `"` is the one that opened it, reached
+    /// again by wrapping round the field.
+    UnclosedString,
+    /// `p` stores to a cell outside the field.
+    PutOutOfBounds { x: i32, y: i32 },
+    /// The rest of the line is past the field's right edge.
+    TooWide { width: usize },
+    /// The first character on a line that does not fit in a cell, where
+    /// the line is cut off.
+    Multibyte(char),
+    /// The line is below the field's bottom edge.
+    TooTall { height: usize },
+    /// A loop that never ends and never does I/O or touches the stack.
+    IdleLoop,
+}
+
+impl fmt::Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LintKind::Unreachable(code) => write!(f, "`{}` is never reached", code),
+            LintKind::NeverStops(delta) => write!(
+                f,
+                "no path going {} from here reaches `@`",
+                delta.name().unwrap_or("on")
+            ),
+            LintKind::UnclosedString => write!(
+                f,
+                "string is never closed, so it wraps round to end at its own `\"`"
+            ),
+            LintKind::PutOutOfBounds { x, y } => {
+                write!(f, "`p` stores to ({}, {}), outside the field", x, y)
+            }
+            LintKind::TooWide { width } => {
+                write!(f, "line is cut off at the field's width of {} cells", width)
+            }
+            LintKind::Multibyte(c) => write!(
+                f,
+                "line is cut off at `{}`, which does not fit in a cell",
+                c
+            ),
+            LintKind::TooTall { height } => {
+                write!(f, "line is left out, below the field's {} rows", height)
+            }
+            LintKind::IdleLoop => write!(
+                f,
+                "loop runs forever without doing I/O or touching the stack"
+            ),
+        }
+    }
+}
+
+/// A warning about the cell at `(x, y)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lint {
+    pub x: i32,
+    pub y: i32,
+    pub kind: LintKind,
+}
+
+impl Lint {
+    fn new(x: i32, y: i32, kind: LintKind) -> Self {
+        Self { x, y, kind }
+    }
+}
+
+/// Checks the program in `source` on a field of `width` by `height`,
+/// returning its warnings ordered by where they are.
+pub fn lint(source: &str, width: usize, height: usize) -> Vec<Lint> {
+    let field = BefungeField::<u8>::from_source(source, width, height);
+    let graph = StateGraph::new(&field);
+
+    let mut lints = truncations(source, width, height);
+    lints.extend(unreachable(&field, &graph));
+    lints.extend(never_stops(&graph));
+    lints.extend(unclosed_strings(&field, &graph));
+    lints.extend(puts_out_of_bounds(&field));
+    lints.extend(idle_loops(&field, &graph));
+    lints.sort_by_key(|lint| (lint.y, lint.x));
+    lints
+}
+
+/// The parts of `source` the field leaves out, as the loader reports them.
+fn truncations(source: &str, width: usize, height: usize) -> Vec<Lint> {
+    // A row ends at its first character that does not fit, so later ones
+    // on the same row are already gone.
+    let mut cut_rows = HashSet::new();
+    load::check(source, width, height)
+        .issues
+        .into_iter()
+        .filter(|issue| match *issue {
+            LoadIssue::NonAscii { y, .. } => cut_rows.insert(y),
+            _ => true,
+        })
+        .map(|issue| {
+            let (x, y) = issue.position();
+            let kind = match issue {
+                LoadIssue::TruncatedRow { width, .. } => LintKind::TooWide { width },
+                LoadIssue::DroppedLine { height, .. } => LintKind::TooTall { height },
+                LoadIssue::NonAscii { c, .. } => LintKind::Multibyte(c),
+            };
+            Lint::new(x as i32, y as i32, kind)
+        })
+        .collect()
+}
+
+fn unreachable(field: &BefungeField<u8>, graph: &StateGraph) -> Vec<Lint> {
+    let reached: HashSet<_> = graph.states.iter().map(|s| (s.x, s.y)).collect();
+    let mut lints = Vec::new();
+    for y in 0..field.height() as i32 {
+        let mut run: Option<(i32, String)> = None;
+        for x in 0..=field.width() as i32 {
+            let cell = field.get(x as usize, y as usize);
+            match (cell, &mut run) {
+                (Some(c), Some((_, code))) if !reached.contains(&(x, y)) => code.push(c as char),
+                (Some(c), None) if !reached.contains(&(x, y)) && c != b' ' => {
+                    run = Some((x, String::from(c as char)))
+                }
+                (_, run) => {
+                    if let Some((start, code)) = run.take() {
+                        let code = String::from(code.trim_end());
+                        lints.push(Lint::new(start, y, LintKind::Unreachable(code)));
+                    }
+                }
+            }
+        }
+    }
+    lints
+}
+
+/// Warns where paths first go that can never reach `@`.
+fn never_stops(graph: &StateGraph) -> Vec<Lint> {
+    let mut entered_by = vec![Vec::new(); graph.states.len()];
+    for (from, exit) in graph.exits.iter().enumerate() {
+        for &(to, _) in exit {
+            entered_by[to].push(from);
+        }
+    }
+    let mut stops = vec![false; graph.states.len()];
+    let mut pending: Vec<_> = (0..graph.states.len())
+        .filter(|&s| graph.exits[s].is_empty())
+        .collect();
+    for &s in &pending {
+        stops[s] = true;
+    }
+    while let Some(s) = pending.pop() {
+        for &from in &entered_by[s] {
+            if !stops[from] {
+                stops[from] = true;
+                pending.push(from);
+            }
+        }
+    }
+
+    let mut doomed = Vec::new();
+    if !stops[0] {
+        doomed.push(0);
+    }
+    for (from, exit) in graph.exits.iter().enumerate() {
+        for &(to, _) in exit {
+            if stops[from] && !stops[to] && !doomed.contains(&to) {
+                doomed.push(to);
+            }
+        }
+    }
+    doomed
+        .into_iter()
+        .map(|s| {
+            let state = graph.states[s];
+            Lint::new(state.x, state.y, LintKind::NeverStops(state.delta))
+        })
+        .collect()
+}
+
+fn is_quote(value: i32) -> bool {
+    value == BefungeCommand::TOGGLE_STRING_MODE as i32
+}
+
+fn unclosed_strings(field: &BefungeField<u8>, graph: &StateGraph) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    for state in &graph.states {
+        if state.string_mode || !is_quote(field.cell(state.x, state.y)) {
+            continue;
+        }
+        let (mut x, mut y) = (state.x, state.y);
+        for _ in 0..field.path_length(x, y, state.delta) {
+            let (next_x, next_y) = field.advance(x, y, state.delta, 1);
+            x = next_x;
+            y = next_y;
+            if is_quote(field.cell(x, y)) {
+                break;
+            }
+        }
+        let lint = Lint::new(state.x, state.y, LintKind::UnclosedString);
+        if (x, y) == (state.x, state.y) && !lints.contains(&lint) {
+            lints.push(lint);
+        }
+    }
+    lints
+}
+
+/// The values a straight run of code is known to leave on the stack.
+#[derive(Clone, Debug)]
+struct KnownStack {
+    /// The top of the stack, with `None` for values that depend on input
+    /// or on the field.
+    values: Vec<Option<i32>>,
+    /// Whether nothing is under `values`, so popping more gives 0.
+    bottom: bool,
+}
+
+impl KnownStack {
+    fn pop(&mut self) -> Option<i32> {
+        match self.values.pop() {
+            Some(value) => value,
+            None if self.bottom => Some(0),
+            None => None,
+        }
+    }
+
+    fn push(&mut self, value: Option<i32>) {
+        self.values.push(value);
+    }
+
+    fn binary(&mut self, apply: impl Fn(i32, i32) -> Option<i32>) {
+        let top = self.pop();
+        let second = self.pop();
+        self.push(second.and_then(|a| top.and_then(|b| apply(a, b))));
+    }
+}
+
+/// Warns about `p` wherever the values it pops are known from the code
+/// before it and point outside the field. What a segment entered from
+/// several places starts with is unknown.
+fn puts_out_of_bounds(field: &BefungeField<u8>) -> Vec<Lint> {
+    let graph = ControlFlowGraph::new(field);
+    let mut entries = vec![0; graph.segments.len()];
+    for edge in &graph.edges {
+        entries[edge.to] += 1;
+    }
+
+    let mut lints = Vec::new();
+    let mut seen = vec![false; graph.segments.len()];
+    let unknown = KnownStack {
+        values: Vec::new(),
+        bottom: false,
+    };
+    let mut pending: Vec<_> = (1..graph.segments.len())
+        .filter(|&s| entries[s] != 1)
+        .map(|s| (s, unknown.clone()))
+        .collect();
+    pending.push((
+        0,
+        KnownStack {
+            values: Vec::new(),
+            bottom: entries[0] == 0,
+        },
+    ));
+
+    while let Some((index, mut stack)) = pending.pop() {
+        if seen[index] {
+            continue;
+        }
+        seen[index] = true;
+        let segment = &graph.segments[index];
+        let mut string_mode = segment.start.string_mode;
+        for &(x, y) in &segment.cells {
+            let value = field.cell(x, y);
+            if string_mode {
+                if is_quote(value) {
+                    string_mode = false;
+                } else {
+                    stack.push(Some(value));
+                }
+                continue;
+            }
+            match u8::try_from(value).unwrap_or(0) {
+                BefungeCommand::TOGGLE_STRING_MODE => string_mode = true,
+                digit @ b'0'..=b'9' => stack.push(Some((digit - b'0') as i32)),
+                BefungeCommand::ADD => stack.binary(|a, b| Some(a.wrapping_add(b))),
+                BefungeCommand::SUBTRACT => stack.binary(|a, b| Some(a.wrapping_sub(b))),
+                BefungeCommand::MULTIPLY => stack.binary(|a, b| Some(a.wrapping_mul(b))),
+                BefungeCommand::DIVIDE => stack.binary(|a, b| a.checked_div(b)),
+                BefungeCommand::MODULO => stack.binary(|a, b| a.checked_rem(b)),
+                BefungeCommand::COMPARE => stack.binary(|a, b| Some((a > b) as i32)),
+                BefungeCommand::NEGATE => {
+                    let top = stack.pop();
+                    stack.push(top.map(|top| (top == 0) as i32));
+                }
+                BefungeCommand::DUPLICATE => {
+                    let top = stack.pop();
+                    stack.push(top);
+                    stack.push(top);
+                }
+                BefungeCommand::SWAP => {
+                    let top = stack.pop();
+                    let second = stack.pop();
+                    stack.push(top);
+                    stack.push(second);
+                }
+                BefungeCommand::DISCARD
+                | BefungeCommand::WRITE_INT
+                | BefungeCommand::WRITE_CHAR
+                | BefungeCommand::IF_LEFT_RIGHT
+                | BefungeCommand::IF_UP_DOWN => {
+                    stack.pop();
+                }
+                BefungeCommand::READ_INT | BefungeCommand::READ_CHAR => stack.push(None),
+                BefungeCommand::READ_CELL => {
+                    stack.pop();
+                    stack.pop();
+                    stack.push(None);
+                }
+                BefungeCommand::WRITE_CELL => {
+                    let put_y = stack.pop();
+                    let put_x = stack.pop();
+                    stack.pop();
+                    if let (Some(put_x), Some(put_y)) = (put_x, put_y) {
+                        if !field.contains(put_x, put_y) {
+                            let kind = LintKind::PutOutOfBounds { x: put_x, y: put_y };
+                            lints.push(Lint::new(x, y, kind));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for edge in graph.edges.iter().filter(|edge| edge.from == index) {
+            if entries[edge.to] == 1 {
+                pending.push((edge.to, stack.clone()));
+            }
+        }
+    }
+    lints
+}
+
+/// Whether executing `value` can do no more than move the IP.
+fn only_moves(value: i32, string_mode: bool) -> bool {
+    if string_mode {
+        return is_quote(value);
+    }
+    match u8::try_from(value) {
+        Ok(b'0'..=b'9') => false,
+        Ok(op) => !b"+-*/%!`:\\$.,&~gp_|?@".contains(&op),
+        Err(_) => true,
+    }
+}
+
+/// Warns once for every loop made only of cells that move the IP.
+fn idle_loops(field: &BefungeField<u8>, graph: &StateGraph) -> Vec<Lint> {
+    // Each state that only moves the IP, and only one way, has a single
+    // next state, so its loops are found by following that.
+    let next: Vec<_> = graph
+        .states
+        .iter()
+        .zip(&graph.exits)
+        .map(|(state, exit)| match exit[..] {
+            [(next, EdgeKind::Next)] | [(next, EdgeKind::Jump)]
+                if only_moves(field.cell(state.x, state.y), state.string_mode) =>
+            {
+                Some(next)
+            }
+            _ => None,
+        })
+        .collect();
+
+    const UNSEEN: usize = usize::MAX;
+    let mut walk_of = vec![UNSEEN; graph.states.len()];
+    let mut lints = Vec::new();
+    for first in 0..graph.states.len() {
+        let mut path: Vec<usize> = Vec::new();
+        let mut at = Some(first);
+        while let Some(s) = at {
+            if walk_of[s] != UNSEEN {
+                if walk_of[s] == first {
+                    // The loop is the part of the path from `s` on.
+                    let start = path.iter().position(|&p| p == s).expect("s is on the path");
+                    let first_found = path[start..].iter().min().expect("loops are not empty");
+                    let state = graph.states[*first_found];
+                    lints.push(Lint::new(state.x, state.y, LintKind::IdleLoop));
+                }
+                break;
+            }
+            walk_of[s] = first;
+            path.push(s);
+            at = next[s];
+        }
+    }
+    lints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(source: &str) -> Vec<(i32, i32, LintKind)> {
+        lint(source, 80, 25)
+            .into_iter()
+            .map(|lint| (lint.x, lint.y, lint.kind))
+            .collect()
+    }
+
+    #[test]
+    fn test_clean() {
+        assert_eq!(kinds("\"!dlroW ,olleH\">:#,_@"), vec![]);
+        assert_eq!(kinds("&:55+`#v_.@\n       >1.@"), vec![]);
+    }
+
+    #[test]
+    fn test_unreachable() {
+        assert_eq!(
+            kinds("1.@ 2.\n\n v  x"),
+            vec![
+                (4, 0, LintKind::Unreachable(String::from("2."))),
+                (1, 2, LintKind::Unreachable(String::from("v  x"))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_never_stops() {
+        assert_eq!(
+            kinds(">v\n^<"),
+            vec![
+                (0, 0, LintKind::NeverStops(Delta::RIGHT)),
+                (1, 0, LintKind::IdleLoop),
+            ]
+        );
+        // Once `_` sends it left, it prints in the loop below for ever.
+        assert_eq!(
+            kinds("~:#v_@\n   >.v\n   ^ <"),
+            vec![(3, 0, LintKind::NeverStops(Delta::LEFT))]
+        );
+    }
+
+    #[test]
+    fn test_unclosed_string() {
+        assert_eq!(
+            kinds("\"olleh,,,,,@"),
+            vec![(0, 0, LintKind::UnclosedString)]
+        );
+    }
+
+    #[test]
+    fn test_put_out_of_bounds() {
+        assert_eq!(
+            kinds("19 9*0p 1 0# 9-p@"),
+            vec![
+                (6, 0, LintKind::PutOutOfBounds { x: 81, y: 0 }),
+                (15, 0, LintKind::PutOutOfBounds { x: 1, y: -9 }),
+            ]
+        );
+        // What `&` reads is not known.
+        assert_eq!(kinds("1&p@"), vec![]);
+    }
+
+    #[test]
+    fn test_truncations() {
+        let source = format!("@{}x\n@ é→\n@{}\n", " ".repeat(79), " ".repeat(90));
+        assert_eq!(
+            truncations(&source, 80, 25),
+            vec![
+                Lint::new(80, 0, LintKind::TooWide { width: 80 }),
+                Lint::new(2, 1, LintKind::Multibyte('é')),
+            ]
+        );
+        // The `→` goes with the rest of the line cut off at the `é`.
+        assert_eq!(
+            lint("@ é→", 80, 1),
+            vec![Lint::new(2, 0, LintKind::Multibyte('é'))]
+        );
+        assert_eq!(
+            lint("@\n\nx", 80, 2),
+            vec![Lint::new(0, 2, LintKind::TooTall { height: 2 })]
+        );
+        assert_eq!(
+            LintKind::TooTall { height: 2 }.to_string(),
+            "line is left out, below the field's 2 rows"
+        );
+    }
+}
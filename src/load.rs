@@ -0,0 +1,232 @@
+//! What loading a program into a fixed-size field leaves out.
+//!
+//! A Befunge-93 field holds one byte-sized character per cell, so
+//! `BefungeField` cuts each row off at the field's width or at its first
+//! character that is not ASCII, and drops the lines below its height.
+//! `BefungeField::load` reports each of these, and can grow the field to fit
+//! the program instead.
+
+use std::error::Error;
+use std::fmt;
+
+/// How `BefungeField::load` fits a program into the field.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LoadOptions {
+    /// Fail with a `LoadError` instead of leaving anything out.
+    pub strict: bool,
+    /// Grow the field to fit every line, keeping the size given as the
+    /// least it can be.
+    pub auto_size: bool,
+}
+
+impl LoadOptions {
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn auto_size(mut self, auto_size: bool) -> Self {
+        self.auto_size = auto_size;
+        self
+    }
+}
+
+/// Something in a program that did not make it into the field.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoadIssue {
+    /// Row `y` holds more than the field's `width`; from there on it is
+    /// cut off.
+    TruncatedRow { y: usize, width: usize },
+    /// Line `y` is below the field's `height` and is dropped.
+    DroppedLine { y: usize, height: usize },
+    /// The character `c` at `(x, y)` is not ASCII. Its row is cut off at
+    /// the first such character.
+    NonAscii { x: usize, y: usize, c: char },
+}
+
+impl LoadIssue {
+    /// The position in the source, in cells from the top left, where
+    /// something was left out.
+    pub fn position(&self) -> (usize, usize) {
+        match *self {
+            LoadIssue::TruncatedRow { y, width } => (width, y),
+            LoadIssue::DroppedLine { y, .. } => (0, y),
+            LoadIssue::NonAscii { x, y, .. } => (x, y),
+        }
+    }
+}
+
+impl fmt::Display for LoadIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadIssue::TruncatedRow { y, width } => write!(
+                f,
+                "row {} is cut off at the field's width of {} cells",
+                y, width
+            ),
+            LoadIssue::DroppedLine { y, height } => write!(
+                f,
+                "line {} is dropped, below the field's {} rows",
+                y, height
+            ),
+            LoadIssue::NonAscii { x, y, c } => write!(
+                f,
+                "{:?} at ({}, {}) is not ASCII and does not fit in a cell",
+                c, x, y
+            ),
+        }
+    }
+}
+
+/// Everything `BefungeField::load` left out, in the order it appears in
+/// the source.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LoadReport {
+    pub issues: Vec<LoadIssue>,
+}
+
+impl LoadReport {
+    /// Whether the whole program made it into the field.
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// Why a strict `BefungeField::load` failed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoadError {
+    pub report: LoadReport,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the program does not fit the field")?;
+        for issue in &self.report.issues {
+            write!(f, "\n  {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for LoadError {}
+
+/// The size a field needs to hold every line of `input` up to its first
+/// non-ASCII character.
+pub(crate) fn fitted_size(input: &str, width: usize, height: usize) -> (usize, usize) {
+    let longest = input
+        .lines()
+        .map(|line| line.chars().take_while(char::is_ascii).count())
+        .max()
+        .unwrap_or(0);
+    (width.max(longest), height.max(input.lines().count()))
+}
+
+/// What loading `input` into a field of `width` by `height` leaves out.
+pub(crate) fn check(input: &str, width: usize, height: usize) -> LoadReport {
+    let mut issues = Vec::new();
+    for (y, line) in input.lines().enumerate() {
+        if y >= height {
+            if !line.trim().is_empty() {
+                issues.push(LoadIssue::DroppedLine { y, height });
+            }
+            continue;
+        }
+        // Once a row is cut at its first non-ASCII character, the width no
+        // longer decides where it ends.
+        let mut truncated = false;
+        for (x, c) in line.chars().enumerate() {
+            if !c.is_ascii() {
+                issues.push(LoadIssue::NonAscii { x, y, c });
+                truncated = true;
+            } else if x >= width && c != ' ' && !truncated {
+                truncated = true;
+                issues.push(LoadIssue::TruncatedRow { y, width });
+            }
+        }
+    }
+    // Keep each row's issues in the order they appear along it.
+    issues.sort_by_key(|issue| {
+        let (x, y) = issue.position();
+        (y, x)
+    });
+    LoadReport { issues }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BefungeField, FungeSpace};
+
+    #[test]
+    fn test_report() {
+        let source = "12345\nab\u{e9}c\u{2192}\n\n   \nx";
+        let report = check(source, 3, 3);
+        assert_eq!(
+            report.issues,
+            vec![
+                LoadIssue::TruncatedRow { y: 0, width: 3 },
+                LoadIssue::NonAscii {
+                    x: 2,
+                    y: 1,
+                    c: '\u{e9}'
+                },
+                LoadIssue::NonAscii {
+                    x: 4,
+                    y: 1,
+                    c: '\u{2192}'
+                },
+                LoadIssue::DroppedLine { y: 4, height: 3 },
+            ]
+        );
+        assert_eq!(
+            report.issues[1].to_string(),
+            "'\u{e9}' at (2, 1) is not ASCII and does not fit in a cell"
+        );
+
+        // A row past the width whose first non-ASCII character comes
+        // earlier is cut there, not at the width.
+        assert_eq!(
+            check("1\u{e9}23", 2, 1).issues,
+            vec![LoadIssue::NonAscii {
+                x: 1,
+                y: 0,
+                c: '\u{e9}'
+            }]
+        );
+        assert_eq!(check("123\u{e9}", 2, 1).issues.len(), 2);
+
+        // Spaces past the edge and blank lines below it lose nothing.
+        assert!(check("1  \n\n  ", 1, 1).is_clean());
+    }
+
+    #[test]
+    fn test_load() {
+        let options = LoadOptions::default();
+        let (field, report) = BefungeField::<u8>::load("123\n4", 2, 1, options).unwrap();
+        assert_eq!((field.width(), field.height()), (2, 1));
+        assert_eq!(report.issues.len(), 2);
+
+        let error = BefungeField::<u8>::load("123\n4", 2, 1, options.strict(true)).unwrap_err();
+        assert_eq!(error.report, report);
+        assert_eq!(
+            error.to_string(),
+            "the program does not fit the field\n  \
+             row 0 is cut off at the field's width of 2 cells\n  \
+             line 1 is dropped, below the field's 1 rows"
+        );
+
+        let options = options.strict(true).auto_size(true);
+        let (field, report) = BefungeField::<u8>::load("123\n4", 2, 1, options).unwrap();
+        assert_eq!((field.width(), field.height()), (3, 2));
+        assert!(report.is_clean());
+        assert_eq!(field.cell(2, 0), '3' as i32);
+        assert_eq!(field.cell(0, 1), '4' as i32);
+
+        // Growing the field does not make room for characters that are
+        // not ASCII.
+        let options = LoadOptions::default().auto_size(true);
+        let (field, report) = BefungeField::<u8>::load("1\u{e9}23", 80, 25, options).unwrap();
+        assert_eq!((field.width(), field.height()), (80, 25));
+        assert_eq!(report.issues.len(), 1);
+    }
+}
@@ -1,15 +1,281 @@
-use std::env;
+use befuddle::golden::{self, GoldenTest};
+use befuddle::tui::{self, Debugger};
+use befuddle::{
+    read_program, BefungeExecution, CfgOptions, CliOptions, Command, CompileOptions, Coverage,
+    CoverageOptions, ExecOptions, ExecutionBuilder, FungeIo, FungeSpace, LintOptions, LoadReport,
+    MemoryIo, StepOutcome, TestOptions,
+};
+use crossterm::tty::IsTty;
+use std::cell::Cell;
+use std::error::Error;
 use std::fs;
-use befuddle::{BefungeExecution,BefungeField};
+use std::io::{self, Write};
+use std::process;
+use structopt::StructOpt;
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+/// The number of cells `--profile` lists as hotspots.
+const HOTSPOTS: usize = 10;
+
+/// Applies the options every execution shares, whatever its I/O. With
+/// `append_trace`, the execution adds to the trace an earlier one wrote.
+fn configure<I: FungeIo, F: FungeSpace>(
+    builder: ExecutionBuilder<I, F>,
+    options: &ExecOptions,
+    append_trace: bool,
+) -> io::Result<ExecutionBuilder<I, F>> {
+    let mut builder = builder
+        .dialect(options.dialect())
+        .args(vec![options.program().display().to_string()])
+        .limits(options.limits());
+    if let Some(seed) = options.seed {
+        builder = builder.seed(seed);
+    }
+    if options.profile {
+        builder = builder.profile();
+    }
+    if let Some(tracer) = options.open_trace(append_trace)? {
+        builder = builder.trace(tracer);
+    }
+    Ok(builder)
+}
+
+/// Warns about what loading the program left out of the field.
+fn warn_load(report: &LoadReport) {
+    for issue in &report.issues {
+        eprintln!("befuddle: warning: {}", issue);
+    }
+}
+
+fn run(options: &ExecOptions) -> Result<i32, Box<dyn Error>> {
+    let snapshot = options.read_resume()?;
+    let field = match &snapshot {
+        Some(snapshot) => options.resume_space(snapshot),
+        None => {
+            let (field, report) = options.load_space(&read_program(options.program())?)?;
+            warn_load(&report);
+            field
+        }
+    };
+    let builder = BefungeExecution::builder(field).io(options.open_io()?);
+    let mut exec = configure(builder, options, false)?.build();
+    if let Some(snapshot) = &snapshot {
+        exec.restore(snapshot)?;
+    }
+
+    let outcome = if options.fast {
+        exec.run_fast()
+    } else {
+        exec.run()
+    };
+    if options.writes_stdout() {
+        println!();
+    }
+    options.write_snapshot(&exec.snapshot())?;
+    if let Some(profile) = exec.profile() {
+        let stderr = io::stderr();
+        let mut stderr = stderr.lock();
+        profile.write_report(&mut stderr, exec.field(), HOTSPOTS)?;
+        writeln!(stderr)?;
+        // Colour only a terminal; a log or pipe gets plain characters.
+        let color = io::stderr().is_tty();
+        profile.write_heatmap(&mut stderr, exec.field(), color)?;
+    }
+    let outcome = outcome?;
+
+    match outcome {
+        StepOutcome::Quit(code) => Ok(code),
+        StepOutcome::LimitExceeded(limit) => {
+            let (x, y) = exec.ip().position();
+            Err(format!(
+                "{} exceeded at ({}, {}) with stack {:?}",
+                limit,
+                x,
+                y,
+                exec.stack()
+            )
+            .into())
+        }
+        _ => Ok(0),
+    }
+}
+
+fn debug(options: ExecOptions) -> Result<i32, Box<dyn Error>> {
+    let snapshot = options.read_resume()?;
+    let contents = match snapshot {
+        Some(_) => String::new(),
+        None => read_program(options.program())?,
+    };
+    let input = match &options.input_file {
+        Some(path) => fs::read(path)?,
+        None => Vec::new(),
+    };
+
+    // Every reset loads the execution again; only the first starts the
+    // trace afresh.
+    let traced = Cell::new(false);
+    let load = move || -> Result<_, Box<dyn Error>> {
+        let field = match &snapshot {
+            Some(snapshot) => options.resume_space(snapshot),
+            None => options.load_space(&contents)?.0,
+        };
+        let builder = BefungeExecution::builder(field)
+            .io(MemoryIo::from_input(input.clone()))
+            .history(tui::HISTORY_BUDGET)
+            .profile();
+        let mut exec = configure(builder, &options, traced.replace(true))?.build();
+        if let Some(snapshot) = &snapshot {
+            exec.restore(snapshot)?;
+        }
+        Ok(exec)
+    };
+    let debugger = Debugger::new(load)?;
+    tui::debug(debugger)?;
+    Ok(0)
+}
+
+fn coverage(options: &CoverageOptions) -> Result<i32, Box<dyn Error>> {
+    let source = read_program(options.exec.program())?;
+    let (field, report) = options.exec.load_space(&source)?;
+    warn_load(&report);
+    let mut coverage = Coverage::new(&field);
 
-    let path = &args[1];
-    let contents = fs::read_to_string(path).expect("Failed to read program");
+    for (run, (name, input)) in options.read_runs()?.into_iter().enumerate() {
+        let builder = BefungeExecution::builder(options.exec.load_space(&source)?.0)
+            .io(MemoryIo::from_input(input))
+            .profile();
+        let mut exec = configure(builder, &options.exec, run > 0)?.build();
+        // A run that fails still reached the cells it ran, so report it and
+        // carry on.
+        match exec.run() {
+            Ok(StepOutcome::LimitExceeded(limit)) => {
+                eprintln!("befuddle: {}: {} exceeded", name, limit)
+            }
+            Err(e) => eprintln!("befuddle: {}: {}", name, e),
+            Ok(_) => {}
+        }
+        coverage.add_run(exec.profile().expect("coverage runs are profiled"));
+    }
 
-    let mut exec = BefungeExecution::new(BefungeField::from_str(&contents, 80,25));
+    coverage.write_listing(io::stdout().lock())?;
+    options.write_summary(&coverage.summary())?;
+    Ok(0)
+}
+
+fn cfg(options: &CfgOptions) -> Result<i32, Box<dyn Error>> {
+    let (graph, report) = options.graph(&read_program(&options.program)?)?;
+    warn_load(&report);
+    options.write_graph(&graph)?;
+    Ok(0)
+}
+
+fn lint(options: &LintOptions) -> Result<i32, Box<dyn Error>> {
+    let lints = options.lint(&read_program(&options.program)?);
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for lint in &lints {
+        writeln!(
+            stdout,
+            "{}:{}:{}: warning: {}",
+            options.program.display(),
+            lint.y + 1,
+            lint.x + 1,
+            lint.kind
+        )?;
+    }
+    Ok(if lints.is_empty() { 0 } else { 1 })
+}
+
+fn compile(options: &CompileOptions) -> Result<i32, Box<dyn Error>> {
+    let (compiled, report) = options.compile(&read_program(&options.program)?)?;
+    warn_load(&report);
+    options.write_program(&compiled)?;
+    eprintln!(
+        "befuddle: compiled {} instructions from {} cells into {} blocks",
+        compiled.instructions, compiled.code_cells, compiled.blocks
+    );
+    if compiled.puts > 0 {
+        eprintln!(
+            "befuddle: {} `p` instructions fall back to interpreting if they change the code",
+            compiled.puts
+        );
+    }
+    Ok(0)
+}
+
+fn test(options: &TestOptions) -> Result<i32, Box<dyn Error>> {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let (mut passed, mut ignored) = (0, 0);
+    let mut failures = Vec::new();
+
+    for program in golden::discover(&options.paths)? {
+        write!(stdout, "test {} ... ", program.display())?;
+        stdout.flush()?;
+        let test = match GoldenTest::load(&program) {
+            Ok(Some(test)) => test,
+            Ok(None) => {
+                writeln!(stdout, "ignored, no expected output")?;
+                ignored += 1;
+                continue;
+            }
+            Err(e) => {
+                writeln!(stdout, "FAILED")?;
+                failures.push((program, format!("{}\n", e)));
+                continue;
+            }
+        };
+
+        let run = test.run(options.limits());
+        if test.passed(&run) {
+            writeln!(stdout, "ok")?;
+            passed += 1;
+        } else {
+            writeln!(stdout, "FAILED")?;
+            let mut diff = Vec::new();
+            test.write_diff(&mut diff, &run)?;
+            failures.push((program, String::from_utf8_lossy(&diff).into_owned()));
+        }
+    }
+
+    if !failures.is_empty() {
+        writeln!(stdout, "\nfailures:")?;
+        for (program, diff) in &failures {
+            writeln!(
+                stdout,
+                "\n---- {} ----\n{}",
+                program.display(),
+                diff.trim_end()
+            )?;
+        }
+    }
+    writeln!(
+        stdout,
+        "\ntest result: {}. {} passed; {} failed; {} ignored",
+        if failures.is_empty() { "ok" } else { "FAILED" },
+        passed,
+        failures.len(),
+        ignored
+    )?;
+    Ok(if failures.is_empty() { 0 } else { 1 })
+}
+
+fn main() {
+    let cli = CliOptions::from_args();
 
-    exec.run();
-    println!();
-}
\ No newline at end of file
+    let result = match cli.command {
+        Some(Command::Debug(options)) => debug(options),
+        Some(Command::Coverage(options)) => coverage(&options),
+        Some(Command::Test(options)) => test(&options),
+        Some(Command::Compile(options)) => compile(&options),
+        Some(Command::Cfg(options)) => cfg(&options),
+        Some(Command::Lint(options)) => lint(&options),
+        None => run(&cli.exec),
+    };
+    match result {
+        Ok(code) => process::exit(code),
+        Err(e) => {
+            eprintln!("befuddle: {}", e);
+            process::exit(1);
+        }
+    }
+}
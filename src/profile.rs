@@ -0,0 +1,308 @@
+//! Counting where an execution spends its steps.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crossterm::style::{style, Attribute, Color};
+
+use crate::FungeSpace;
+
+/// The number of shades a heatmap is drawn in.
+pub const HEAT_LEVELS: usize = 6;
+
+/// The 256-colour palette index of each heat level, coldest first.
+pub const HEAT_COLORS: [u8; HEAT_LEVELS] = [19, 30, 34, 178, 202, 160];
+
+/// The character each heat level is drawn with where there is no colour,
+/// coldest first.
+pub const HEAT_GLYPHS: [char; HEAT_LEVELS] = ['.', ':', '-', '=', '+', '#'];
+
+/// The most rows and columns `write_heatmap` draws.
+const HEATMAP_LIMIT: i32 = 256;
+
+/// Counts of the cells and instructions an execution ran, and the cells
+/// `p` and `s` wrote. Steps that are later stepped back still count.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Profile {
+    executions: HashMap<(i32, i32), u64>,
+    instructions: HashMap<i32, u64>,
+    writes: HashMap<(i32, i32), u64>,
+    steps: u64,
+}
+
+/// `counts` sorted from the highest count down, then by key.
+fn ranked<K: Copy + Ord>(counts: &HashMap<K, u64>) -> Vec<(K, u64)> {
+    let mut ranked: Vec<_> = counts.iter().map(|(&k, &n)| (k, n)).collect();
+    ranked.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    ranked
+}
+
+/// How an instruction is shown in a report.
+fn describe(instruction: i32) -> String {
+    match instruction {
+        0x20..=0x7e => format!("'{}'", instruction as u8 as char),
+        _ => instruction.to_string(),
+    }
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a step taken at `(x, y)`. `instruction` is `None` for a
+    /// character pushed in string mode.
+    pub(crate) fn execute(&mut self, x: i32, y: i32, instruction: Option<i32>) {
+        self.steps += 1;
+        *self.executions.entry((x, y)).or_default() += 1;
+        if let Some(instruction) = instruction {
+            *self.instructions.entry(instruction).or_default() += 1;
+        }
+    }
+
+    /// Counts a write to `(x, y)` by `p` or `s`.
+    pub(crate) fn write(&mut self, x: i32, y: i32) {
+        *self.writes.entry((x, y)).or_default() += 1;
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// How many steps were taken at `(x, y)`.
+    pub fn executions(&self, x: i32, y: i32) -> u64 {
+        self.executions.get(&(x, y)).copied().unwrap_or_default()
+    }
+
+    /// Every cell a step was taken at, in no particular order.
+    pub fn executed(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.executions.keys().copied()
+    }
+
+    /// How many times `p` or `s` wrote to `(x, y)`.
+    pub fn writes(&self, x: i32, y: i32) -> u64 {
+        self.writes.get(&(x, y)).copied().unwrap_or_default()
+    }
+
+    /// The `n` cells most steps were taken at, most first.
+    pub fn hotspots(&self, n: usize) -> Vec<((i32, i32), u64)> {
+        let mut hotspots = ranked(&self.executions);
+        hotspots.truncate(n);
+        hotspots
+    }
+
+    /// How often each instruction ran outside string mode, most first.
+    pub fn histogram(&self) -> Vec<(i32, u64)> {
+        ranked(&self.instructions)
+    }
+
+    /// The cells `p` and `s` wrote, most written first.
+    pub fn written(&self) -> Vec<((i32, i32), u64)> {
+        ranked(&self.writes)
+    }
+
+    /// How hot `(x, y)` is, from 0 to `HEAT_LEVELS - 1` on a logarithmic
+    /// scale up to the hottest cell, or `None` if no step was taken there.
+    pub fn heat(&self, x: i32, y: i32) -> Option<usize> {
+        let count = self.executions(x, y);
+        if count == 0 {
+            return None;
+        }
+
+        let max = self.executions.values().copied().max().unwrap_or_default();
+        let top = HEAT_LEVELS - 1;
+        if max <= 1 {
+            return Some(top);
+        }
+        let level = (count as f64).ln() / (max as f64).ln() * top as f64;
+        Some((level.round() as usize).min(top))
+    }
+
+    /// Writes the hottest `hotspots` cells, the instruction histogram and
+    /// the written cells as text.
+    pub fn write_report<W: Write, S: FungeSpace + ?Sized>(
+        &self,
+        mut writer: W,
+        space: &S,
+        hotspots: usize,
+    ) -> io::Result<()> {
+        let share = |n: u64| 100.0 * n as f64 / self.steps.max(1) as f64;
+
+        writeln!(writer, "Steps {}", self.steps)?;
+        writeln!(writer)?;
+        writeln!(writer, "Hotspots")?;
+        for ((x, y), n) in self.hotspots(hotspots) {
+            let cell = format!("({}, {})", x, y);
+            let instruction = describe(space.cell(x, y));
+            writeln!(
+                writer,
+                "  {:<14} {:<6} {:>10} {:>6.1}%",
+                cell,
+                instruction,
+                n,
+                share(n)
+            )?;
+        }
+
+        writeln!(writer)?;
+        writeln!(writer, "Instructions")?;
+        for (instruction, n) in self.histogram() {
+            writeln!(
+                writer,
+                "  {:<6} {:>10} {:>6.1}%",
+                describe(instruction),
+                n,
+                share(n)
+            )?;
+        }
+
+        let written = self.written();
+        if !written.is_empty() {
+            writeln!(writer)?;
+            writeln!(writer, "Writes")?;
+            for ((x, y), n) in written {
+                writeln!(writer, "  {:<14} {:>10}", format!("({}, {})", x, y), n)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Draws `space` with each executed cell coloured by its heat and each
+    /// written cell underlined, using ANSI escapes. Without `color`, each
+    /// executed cell is drawn as its `HEAT_GLYPHS` character instead and
+    /// every other cell is left blank. The drawing runs from the top left
+    /// of the bounds to the last cell holding or running anything, and
+    /// stops after 256 rows and columns.
+    pub fn write_heatmap<W: Write, S: FungeSpace + ?Sized>(
+        &self,
+        mut writer: W,
+        space: &S,
+        color: bool,
+    ) -> io::Result<()> {
+        let bounds = space.bounds();
+        let (used_x, used_y) = space
+            .cells()
+            .into_iter()
+            .map(|(x, y, _)| (x, y))
+            .chain(self.executions.keys().copied())
+            .fold((bounds.min_x, bounds.min_y), |(mx, my), (x, y)| {
+                (mx.max(x), my.max(y))
+            });
+        let max_x = used_x
+            .min(bounds.max_x)
+            .min(bounds.min_x.saturating_add(HEATMAP_LIMIT - 1));
+        let max_y = used_y
+            .min(bounds.max_y)
+            .min(bounds.min_y.saturating_add(HEATMAP_LIMIT - 1));
+
+        for y in bounds.min_y..=max_y {
+            if !color {
+                let row: String = (bounds.min_x..=max_x)
+                    .map(|x| self.heat(x, y).map_or(' ', |level| HEAT_GLYPHS[level]))
+                    .collect();
+                writeln!(writer, "{}", row.trim_end())?;
+                continue;
+            }
+            for x in bounds.min_x..=max_x {
+                let glyph = match space.cell(x, y) {
+                    value @ 0x20..=0x7e => value as u8 as char,
+                    _ => '.',
+                };
+                let mut styled = style(glyph);
+                if let Some(level) = self.heat(x, y) {
+                    styled = styled
+                        .with(Color::White)
+                        .on(Color::AnsiValue(HEAT_COLORS[level]));
+                }
+                if self.writes(x, y) > 0 {
+                    styled = styled.attribute(Attribute::Underlined);
+                }
+                write!(writer, "{}", styled)?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BefungeExecution, BefungeField, MemoryIo};
+
+    fn profile(program: &str, width: usize, height: usize) -> (Profile, BefungeField) {
+        let mut exec = BefungeExecution::builder(BefungeField::from_str(program, width, height))
+            .io(MemoryIo::default())
+            .profile()
+            .build();
+        exec.run().unwrap();
+        (exec.profile().unwrap().clone(), exec.field().clone())
+    }
+
+    #[test]
+    fn test_counts() {
+        // Counts 3 down to 0, writing the counter into (0, 2) each time.
+        let (profile, _) = profile("3>:02p1-:v\n@^       _", 10, 3);
+        assert_eq!(profile.executions(0, 0), 1);
+        assert_eq!(profile.executions(2, 0), 3);
+        assert_eq!(profile.executions(9, 1), 3);
+        assert_eq!(profile.executions(5, 1), 2);
+        assert_eq!(profile.writes(0, 2), 3);
+        assert_eq!(profile.written(), vec![((0, 2), 3)]);
+        assert_eq!(profile.hotspots(2), vec![((1, 0), 3), ((2, 0), 3)]);
+
+        let histogram = profile.histogram();
+        assert_eq!(histogram[0], (b' ' as i32, 14));
+        assert!(histogram.contains(&(b':' as i32, 6)));
+        assert_eq!(profile.steps(), profile.executions.values().sum::<u64>());
+
+        assert_eq!(profile.heat(0, 0), Some(0));
+        assert_eq!(profile.heat(2, 0), Some(HEAT_LEVELS - 1));
+        assert_eq!(profile.heat(5, 2), None);
+    }
+
+    #[test]
+    fn test_string_mode() {
+        let (profile, _) = profile("\"ab\"@", 5, 1);
+        assert_eq!(profile.executions(1, 0), 1);
+        assert_eq!(
+            profile.histogram(),
+            vec![(b'"' as i32, 2), (b'@' as i32, 1)]
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let (profile, field) = profile("11+@", 4, 1);
+        let mut report = Vec::new();
+        profile.write_report(&mut report, &field, 2).unwrap();
+        let report = String::from_utf8(report).unwrap();
+
+        assert!(report.starts_with("Steps 4\n\nHotspots\n"), "{}", report);
+        assert!(report.contains("  (0, 0)         '1'             1   25.0%\n"));
+        assert!(!report.contains("(2, 0)         '+'"));
+        assert!(report.contains("\nInstructions\n  '1'             2   50.0%\n"));
+        assert!(!report.contains("Writes"));
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let (profile, field) = profile("1@\n\n", 4, 3);
+        let mut heatmap = Vec::new();
+        profile.write_heatmap(&mut heatmap, &field, true).unwrap();
+        let heatmap = String::from_utf8(heatmap).unwrap();
+
+        assert!(heatmap.contains("\u{1b}[48;5;160m"), "{:?}", heatmap);
+        // Nothing ran or is stored past the `@`.
+        assert_eq!(heatmap.lines().count(), 1, "{:?}", heatmap);
+        assert!(
+            heatmap.ends_with("@\u{1b}[49m\u{1b}[39m\n"),
+            "{:?}",
+            heatmap
+        );
+
+        let mut plain = Vec::new();
+        profile.write_heatmap(&mut plain, &field, false).unwrap();
+        assert_eq!(String::from_utf8(plain).unwrap(), "##\n");
+    }
+}
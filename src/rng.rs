@@ -0,0 +1,86 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The random number generator behind `?`.
+///
+/// A SplitMix64 generator: small, fast and fully determined by its seed, so
+/// that runs can be reproduced.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FungeRng {
+    state: u64,
+}
+
+impl FungeRng {
+    pub fn seeded(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// A generator seeded from the clock and the process' hash keys.
+    pub fn from_entropy() -> Self {
+        let mut hasher = RandomState::new().build_hasher();
+        if let Ok(elapsed) = SystemTime::now().duration_since(UNIX_EPOCH) {
+            hasher.write_u128(elapsed.as_nanos());
+        }
+
+        Self::seeded(hasher.finish())
+    }
+
+    /// The current state, which `seeded` picks up from.
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// A uniformly distributed value in `0..bound`.
+    pub fn below(&mut self, bound: u32) -> u32 {
+        assert!(bound > 0, "bound must be positive");
+
+        let bound = bound as u64;
+        let zone = u64::MAX - u64::MAX % bound;
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return (value % bound) as u32;
+            }
+        }
+    }
+}
+
+impl Default for FungeRng {
+    fn default() -> Self {
+        Self::from_entropy()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeded_is_reproducible() {
+        let mut a = FungeRng::seeded(42);
+        let mut b = FungeRng::seeded(42);
+        for _i in 0..100 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+    }
+
+    #[test]
+    fn test_below_covers_range() {
+        let mut rng = FungeRng::seeded(7);
+        let mut seen = [0; 4];
+        for _i in 0..4000 {
+            seen[rng.below(4) as usize] += 1;
+        }
+        assert!(seen.iter().all(|&n| n > 800), "{:?}", seen);
+    }
+}
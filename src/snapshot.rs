@@ -0,0 +1,286 @@
+//! Saving an execution to a file and picking it up again later.
+
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Bounds, Delta, Dialect};
+
+/// The snapshot format this build writes. Snapshots written by later
+/// versions are refused rather than misread.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// The bytes a binary snapshot starts with, followed by its version as a
+/// little-endian `u32`.
+const MAGIC: &[u8; 8] = b"BEFUDDLE";
+
+/// How a snapshot is written out. Reading tells the two apart by itself.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SnapshotFormat {
+    /// Pretty-printed JSON, for reading and diffing.
+    Json,
+    /// A compact binary encoding.
+    Binary,
+}
+
+impl SnapshotFormat {
+    /// JSON for paths ending in `.json`, binary for anything else.
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(extension) if extension == "json" => SnapshotFormat::Json,
+            _ => SnapshotFormat::Binary,
+        }
+    }
+}
+
+/// The state of an execution at one point, as saved by
+/// `BefungeExecution::snapshot`.
+///
+/// Breakpoints, limits, the undo history and any state fingerprints keep
+/// internally are not part of it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub dialect: Dialect,
+    pub bounds: Bounds,
+    /// Every non-space cell as `(x, y, value)`.
+    pub cells: Vec<(i32, i32, i32)>,
+    pub ips: Vec<IpSnapshot>,
+    /// Index into `ips` of the IP that executes next.
+    pub current: usize,
+    pub next_ip_id: i32,
+    pub active: bool,
+    pub exit_code: Option<i32>,
+    /// The state of the `?` generator.
+    pub rng: u64,
+    pub steps: u64,
+    /// The number of input bytes already read.
+    pub input_position: u64,
+    pub arguments: Vec<String>,
+}
+
+/// One instruction pointer in a `Snapshot`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IpSnapshot {
+    pub id: i32,
+    pub x: i32,
+    pub y: i32,
+    pub delta: Delta,
+    pub string_mode: bool,
+    /// Every stack from the bottom of the stack stack up to the TOSS, each
+    /// listed bottom first.
+    pub stacks: Vec<Vec<i32>>,
+    pub storage_offset: (i32, i32),
+    /// For each of `A` to `Z`, the names of the fingerprints loaded for it,
+    /// the most recent last.
+    pub semantics: Vec<Vec<String>>,
+}
+
+/// Just enough of a JSON snapshot to check its version before reading the
+/// rest.
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+impl Snapshot {
+    pub fn write<W: Write>(
+        &self,
+        mut writer: W,
+        format: SnapshotFormat,
+    ) -> Result<(), SnapshotError> {
+        match format {
+            SnapshotFormat::Json => {
+                serde_json::to_writer_pretty(&mut writer, self)?;
+                writeln!(writer)?;
+            }
+            SnapshotFormat::Binary => {
+                writer.write_all(MAGIC)?;
+                writer.write_all(&self.version.to_le_bytes())?;
+                bincode::serialize_into(&mut writer, self)?;
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads a snapshot in either format.
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, SnapshotError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+
+        if let Some(rest) = bytes.strip_prefix(MAGIC) {
+            let version = rest
+                .get(..4)
+                .and_then(|version| version.try_into().ok())
+                .map(u32::from_le_bytes)
+                .ok_or_else(|| SnapshotError::Malformed(String::from("truncated header")))?;
+            check_version(version)?;
+            bincode::deserialize(&rest[4..]).map_err(|e| SnapshotError::Malformed(e.to_string()))
+        } else {
+            let header: Header = serde_json::from_slice(&bytes)?;
+            check_version(header.version)?;
+            Ok(serde_json::from_slice(&bytes)?)
+        }
+    }
+}
+
+fn check_version(version: u32) -> Result<(), SnapshotError> {
+    if version > SNAPSHOT_VERSION {
+        Err(SnapshotError::UnsupportedVersion(version))
+    } else {
+        Ok(())
+    }
+}
+
+/// A failure to save, read or restore a snapshot.
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    /// The data is not a snapshot in either format.
+    Malformed(String),
+    /// The snapshot was written by a later version of befuddle.
+    UnsupportedVersion(u32),
+    /// The snapshot loads a fingerprint the execution does not have.
+    UnknownFingerprint(String),
+    /// The snapshot has a cell outside the execution's space.
+    OutOfBounds {
+        x: i32,
+        y: i32,
+    },
+}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(e: serde_json::Error) -> Self {
+        if e.is_io() {
+            SnapshotError::Io(e.into())
+        } else {
+            SnapshotError::Malformed(e.to_string())
+        }
+    }
+}
+
+impl From<bincode::Error> for SnapshotError {
+    fn from(e: bincode::Error) -> Self {
+        match *e {
+            bincode::ErrorKind::Io(e) => SnapshotError::Io(e),
+            e => SnapshotError::Malformed(e.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "I/O error: {}", e),
+            SnapshotError::Malformed(e) => write!(f, "malformed snapshot: {}", e),
+            SnapshotError::UnsupportedVersion(version) => write!(
+                f,
+                "snapshot version {} is newer than the supported version {}",
+                version, SNAPSHOT_VERSION
+            ),
+            SnapshotError::UnknownFingerprint(name) => {
+                write!(f, "snapshot uses unknown fingerprint {}", name)
+            }
+            SnapshotError::OutOfBounds { x, y } => {
+                write!(f, "snapshot cell ({}, {}) is outside the field", x, y)
+            }
+        }
+    }
+}
+
+impl Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SnapshotError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BefungeExecution, BefungeField, MemoryIo};
+
+    fn snapshot() -> Snapshot {
+        let mut exec = BefungeExecution::builder(BefungeField::from_str("12p3@", 10, 2))
+            .io(MemoryIo::default())
+            .seed(3)
+            .build();
+        exec.step().unwrap();
+        exec.snapshot()
+    }
+
+    fn round_trip(snapshot: &Snapshot, format: SnapshotFormat) -> Snapshot {
+        let mut bytes = Vec::new();
+        snapshot.write(&mut bytes, format).unwrap();
+        Snapshot::read(&bytes[..]).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let snapshot = snapshot();
+        assert_eq!(snapshot.steps, 1);
+        assert_eq!(snapshot.ips[0].stacks, vec![vec![1]]);
+        assert_eq!(round_trip(&snapshot, SnapshotFormat::Json), snapshot);
+        assert_eq!(round_trip(&snapshot, SnapshotFormat::Binary), snapshot);
+    }
+
+    #[test]
+    fn test_json_is_readable() {
+        let mut bytes = Vec::new();
+        snapshot().write(&mut bytes, SnapshotFormat::Json).unwrap();
+        let json = String::from_utf8(bytes).unwrap();
+        assert!(json.contains("\"version\": 1"), "{}", json);
+        assert!(json.contains("\"dialect\": \"befunge93\""), "{}", json);
+    }
+
+    #[test]
+    fn test_newer_version() {
+        let mut snapshot = snapshot();
+        snapshot.version = SNAPSHOT_VERSION + 1;
+        for &format in &[SnapshotFormat::Json, SnapshotFormat::Binary] {
+            let mut bytes = Vec::new();
+            snapshot.write(&mut bytes, format).unwrap();
+            assert!(matches!(
+                Snapshot::read(&bytes[..]),
+                Err(SnapshotError::UnsupportedVersion(2))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_malformed() {
+        assert!(matches!(
+            Snapshot::read(&b"not a snapshot"[..]),
+            Err(SnapshotError::Malformed(_))
+        ));
+        assert!(matches!(
+            Snapshot::read(&b"BEFUDDLE\x01\x00\x00\x00\x07"[..]),
+            Err(SnapshotError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn test_format_for_path() {
+        assert_eq!(
+            SnapshotFormat::for_path(Path::new("state.json")),
+            SnapshotFormat::Json
+        );
+        assert_eq!(
+            SnapshotFormat::for_path(Path::new("state.snap")),
+            SnapshotFormat::Binary
+        );
+    }
+}
@@ -0,0 +1,438 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Delta, FungeCell};
+
+const SPACE: i32 = b' ' as i32;
+
+/// The smallest rectangle containing every non-space cell, inclusive of both
+/// corners.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bounds {
+    pub min_x: i32,
+    pub min_y: i32,
+    pub max_x: i32,
+    pub max_y: i32,
+}
+
+impl Bounds {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        (self.min_x..=self.max_x).contains(&x) && (self.min_y..=self.max_y).contains(&y)
+    }
+
+    pub fn width(&self) -> i64 {
+        self.max_x as i64 - self.min_x as i64 + 1
+    }
+
+    pub fn height(&self) -> i64 {
+        self.max_y as i64 - self.min_y as i64 + 1
+    }
+
+    fn include(&mut self, x: i32, y: i32) {
+        self.min_x = self.min_x.min(x);
+        self.min_y = self.min_y.min(y);
+        self.max_x = self.max_x.max(x);
+        self.max_y = self.max_y.max(y);
+    }
+
+    /// The range of `t` for which `(x, y) + t * delta` lies inside the
+    /// bounds, if the line passes through them at all.
+    fn span(&self, x: i32, y: i32, delta: Delta) -> Option<(i64, i64)> {
+        let axis = |p: i32, d: i32, min: i32, max: i32| -> Option<(i64, i64)> {
+            let (p, d, min, max) = (p as i64, d as i64, min as i64, max as i64);
+            if d == 0 {
+                if (min..=max).contains(&p) {
+                    Some((i64::MIN, i64::MAX))
+                } else {
+                    None
+                }
+            } else if d > 0 {
+                Some((div_ceil(min - p, d), (max - p).div_euclid(d)))
+            } else {
+                Some((div_ceil(p - max, -d), (p - min).div_euclid(-d)))
+            }
+        };
+
+        let (lo_x, hi_x) = axis(x, delta.dx, self.min_x, self.max_x)?;
+        let (lo_y, hi_y) = axis(y, delta.dy, self.min_y, self.max_y)?;
+        let (lo, hi) = (lo_x.max(lo_y), hi_x.min(hi_y));
+
+        if lo <= hi {
+            Some((lo, hi))
+        } else {
+            None
+        }
+    }
+}
+
+fn div_ceil(a: i64, b: i64) -> i64 {
+    -((-a).div_euclid(b))
+}
+
+/// Storage for the cells of a Funge program, addressed by signed coordinates.
+///
+/// Cells that were never written hold a space.
+pub trait FungeSpace: Debug {
+    fn cell(&self, x: i32, y: i32) -> i32;
+
+    /// Stores `value` at `(x, y)`, returning `false` if the space cannot
+    /// hold a cell there.
+    fn set_cell(&mut self, x: i32, y: i32, value: i32) -> bool;
+
+    /// Whether `(x, y)` is addressable at all.
+    fn contains(&self, x: i32, y: i32) -> bool;
+
+    fn bounds(&self) -> Bounds;
+
+    /// The position `distance` steps along `delta` from `(x, y)`, wrapping
+    /// as the space's topology dictates.
+    fn advance(&self, x: i32, y: i32, delta: Delta, distance: i64) -> (i32, i32);
+
+    /// An upper bound on the number of steps an IP moving along `delta` from
+    /// `(x, y)` takes to pass every cell it will ever visit.
+    fn path_length(&self, x: i32, y: i32, delta: Delta) -> i64;
+
+    /// Sets the bounds back to an earlier `bounds()`, once the cells written
+    /// since then have been put back. Spaces with fixed bounds ignore it.
+    fn restore_bounds(&mut self, _bounds: Bounds) {}
+
+    /// Every non-space cell as `(x, y, value)`, in no particular order.
+    fn cells(&self) -> Vec<(i32, i32, i32)> {
+        let bounds = self.bounds();
+        let mut cells = Vec::new();
+        for y in bounds.min_y..=bounds.max_y {
+            for x in bounds.min_x..=bounds.max_x {
+                let value = self.cell(x, y);
+                if value != SPACE {
+                    cells.push((x, y, value));
+                }
+            }
+        }
+        cells
+    }
+}
+
+impl<S: FungeSpace + ?Sized> FungeSpace for Box<S> {
+    fn cell(&self, x: i32, y: i32) -> i32 {
+        (**self).cell(x, y)
+    }
+
+    fn set_cell(&mut self, x: i32, y: i32, value: i32) -> bool {
+        (**self).set_cell(x, y, value)
+    }
+
+    fn contains(&self, x: i32, y: i32) -> bool {
+        (**self).contains(x, y)
+    }
+
+    fn bounds(&self) -> Bounds {
+        (**self).bounds()
+    }
+
+    fn advance(&self, x: i32, y: i32, delta: Delta, distance: i64) -> (i32, i32) {
+        (**self).advance(x, y, delta, distance)
+    }
+
+    fn path_length(&self, x: i32, y: i32, delta: Delta) -> i64 {
+        (**self).path_length(x, y, delta)
+    }
+
+    fn restore_bounds(&mut self, bounds: Bounds) {
+        (**self).restore_bounds(bounds)
+    }
+
+    fn cells(&self) -> Vec<(i32, i32, i32)> {
+        (**self).cells()
+    }
+}
+
+const CHUNK_BITS: u32 = 4;
+const CHUNK_SIZE: i32 = 1 << CHUNK_BITS;
+const CHUNK_MASK: i32 = CHUNK_SIZE - 1;
+
+type Chunk<C> = Box<[C; (CHUNK_SIZE * CHUNK_SIZE) as usize]>;
+
+/// Unbounded Funge-98 space, stored sparsely in fixed-size chunks.
+///
+/// Movement follows Lahey-space: an IP leaving the bounding box of the
+/// program re-enters it from the opposite side along the same line.
+#[derive(Clone, Debug)]
+pub struct SparseSpace<C = i32> {
+    chunks: HashMap<(i32, i32), Chunk<C>>,
+    bounds: Option<Bounds>,
+}
+
+impl SparseSpace {
+    pub fn new() -> Self {
+        Self::blank()
+    }
+}
+
+impl Default for SparseSpace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<&str> for SparseSpace {
+    fn from(input: &str) -> Self {
+        Self::from_source(input)
+    }
+}
+
+impl<C: FungeCell> SparseSpace<C> {
+    /// An empty space holding cells of type `C`.
+    pub fn blank() -> Self {
+        Self {
+            chunks: HashMap::new(),
+            bounds: None,
+        }
+    }
+
+    /// Loads a program with its first character at the origin.
+    pub fn from_source(input: &str) -> Self {
+        let mut space = Self::blank();
+        space.load_str(input, 0, 0);
+        space
+    }
+
+    /// Loads `input` with its first character at `(x, y)`. Spaces leave the
+    /// existing cells untouched and form feeds are ignored.
+    pub fn load_str(&mut self, input: &str, x: i32, y: i32) {
+        let normalized = input.replace("\r\n", "\n").replace('\r', "\n");
+
+        for (dy, line) in normalized.split('\n').enumerate() {
+            let mut cx = x;
+            for c in line.chars().filter(|&c| c != '\x0c') {
+                if c != ' ' {
+                    self.set_cell(cx, y.wrapping_add(dy as i32), c as i32);
+                }
+                cx = cx.wrapping_add(1);
+            }
+        }
+    }
+
+    fn chunk_key(x: i32, y: i32) -> ((i32, i32), usize) {
+        let key = (x >> CHUNK_BITS, y >> CHUNK_BITS);
+        let index = ((y & CHUNK_MASK) * CHUNK_SIZE + (x & CHUNK_MASK)) as usize;
+        (key, index)
+    }
+}
+
+impl<C: FungeCell> FungeSpace for SparseSpace<C> {
+    fn cell(&self, x: i32, y: i32) -> i32 {
+        let (key, index) = Self::chunk_key(x, y);
+        self.chunks
+            .get(&key)
+            .map_or(SPACE, |chunk| chunk[index].to_value())
+    }
+
+    fn set_cell(&mut self, x: i32, y: i32, value: i32) -> bool {
+        let (key, index) = Self::chunk_key(x, y);
+        let value = C::from_value(value);
+
+        if value == C::SPACE && !self.chunks.contains_key(&key) {
+            return true;
+        }
+        self.chunks
+            .entry(key)
+            .or_insert_with(|| Box::new([C::SPACE; (CHUNK_SIZE * CHUNK_SIZE) as usize]))[index] =
+            value;
+
+        if value != C::SPACE {
+            match &mut self.bounds {
+                Some(bounds) => bounds.include(x, y),
+                None => {
+                    self.bounds = Some(Bounds {
+                        min_x: x,
+                        min_y: y,
+                        max_x: x,
+                        max_y: y,
+                    })
+                }
+            }
+        }
+
+        true
+    }
+
+    fn contains(&self, _x: i32, _y: i32) -> bool {
+        true
+    }
+
+    fn bounds(&self) -> Bounds {
+        self.bounds.unwrap_or(Bounds {
+            min_x: 0,
+            min_y: 0,
+            max_x: 0,
+            max_y: 0,
+        })
+    }
+
+    fn advance(&self, x: i32, y: i32, delta: Delta, distance: i64) -> (i32, i32) {
+        if distance < 0 {
+            return self.advance(x, y, delta.reverse(), -distance);
+        }
+
+        let at = |t: i64| {
+            (
+                (x as i64 + t * delta.dx as i64) as i32,
+                (y as i64 + t * delta.dy as i64) as i32,
+            )
+        };
+        let (lo, hi) = match self.bounds().span(x, y, delta) {
+            Some(span) if delta != Delta::new(0, 0) => span,
+            _ => return at(distance),
+        };
+        let period = hi - lo + 1;
+
+        if distance <= hi {
+            // Inside the bounds, or still on the way towards them.
+            at(distance)
+        } else if hi >= 0 {
+            // Leaving through the far side and re-entering from the near one.
+            at((distance - lo).rem_euclid(period) + lo)
+        } else {
+            // Moving away from the bounds: the first step wraps to the far
+            // side of them.
+            at((distance - 1).rem_euclid(period) + lo)
+        }
+    }
+
+    fn path_length(&self, x: i32, y: i32, delta: Delta) -> i64 {
+        match self.bounds().span(x, y, delta) {
+            Some(_) if delta == Delta::new(0, 0) => 1,
+            Some((lo, hi)) => hi - lo + 1 + lo.max(0) + 1,
+            None => 0,
+        }
+    }
+
+    fn restore_bounds(&mut self, bounds: Bounds) {
+        // A space that was empty before still reports the placeholder
+        // bounds; it stays as it is.
+        if self.bounds.is_some() {
+            self.bounds = Some(bounds);
+        }
+    }
+
+    fn cells(&self) -> Vec<(i32, i32, i32)> {
+        let mut cells = Vec::new();
+        for (&(cx, cy), chunk) in &self.chunks {
+            for (index, &value) in chunk.iter().enumerate() {
+                if value != C::SPACE {
+                    let x = (cx << CHUNK_BITS) | (index as i32 & CHUNK_MASK);
+                    let y = (cy << CHUNK_BITS) | (index as i32 >> CHUNK_BITS);
+                    cells.push((x, y, value.to_value()));
+                }
+            }
+        }
+        cells
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sparse_cells() {
+        let mut space = SparseSpace::new();
+        assert_eq!(space.cell(-1000, 5000), SPACE);
+
+        space.set_cell(-1000, 5000, 7);
+        space.set_cell(3, -2, 9);
+        assert_eq!(space.cell(-1000, 5000), 7);
+        assert_eq!(space.cell(3, -2), 9);
+        assert_eq!(space.cell(-999, 5000), SPACE);
+        assert_eq!(
+            space.bounds(),
+            Bounds {
+                min_x: -1000,
+                min_y: -2,
+                max_x: 3,
+                max_y: 5000
+            }
+        );
+    }
+
+    #[test]
+    fn test_cells() {
+        let mut space = SparseSpace::new();
+        space.set_cell(-1000, 5000, 7);
+        space.set_cell(3, -2, 9);
+        space.set_cell(4, 4, 1);
+        space.set_cell(4, 4, SPACE);
+
+        let mut cells = space.cells();
+        cells.sort_unstable();
+        assert_eq!(cells, vec![(-1000, 5000, 7), (3, -2, 9)]);
+
+        let mut dense = crate::BefungeField::from_str("1 2\n 3", 3, 2);
+        dense.set_cell(0, 0, SPACE);
+        assert_eq!(
+            dense.cells(),
+            vec![(2, 0, b'2' as i32), (1, 1, b'3' as i32)]
+        );
+    }
+
+    #[test]
+    fn test_narrow_cells() {
+        let mut space = SparseSpace::<u8>::blank();
+        space.set_cell(5, 5, 256 + 32);
+        assert_eq!(space.cell(5, 5), SPACE);
+        assert_eq!(space.bounds(), SparseSpace::new().bounds());
+
+        space.set_cell(5, 5, -1);
+        assert_eq!(space.cell(5, 5), 255);
+    }
+
+    #[test]
+    fn test_load_str() {
+        let space = SparseSpace::from("ab\r\nc\x0cd\re");
+        assert_eq!(space.cell(0, 0), 'a' as i32);
+        assert_eq!(space.cell(1, 0), 'b' as i32);
+        assert_eq!(space.cell(0, 1), 'c' as i32);
+        assert_eq!(space.cell(1, 1), 'd' as i32);
+        assert_eq!(space.cell(0, 2), 'e' as i32);
+        assert_eq!(space.bounds().max_x, 1);
+    }
+
+    #[test]
+    fn test_lahey_wrap() {
+        let space = SparseSpace::from("abc\nd");
+
+        assert_eq!(space.advance(1, 0, Delta::RIGHT, 1), (2, 0));
+        assert_eq!(space.advance(2, 0, Delta::RIGHT, 1), (0, 0));
+        assert_eq!(space.advance(0, 0, Delta::LEFT, 1), (2, 0));
+        assert_eq!(space.advance(0, 1, Delta::DOWN, 1), (0, 0));
+        assert_eq!(space.advance(0, 0, Delta::new(1, 1), 1), (1, 1));
+        assert_eq!(space.advance(1, 1, Delta::new(1, 1), 1), (0, 0));
+        assert_eq!(space.advance(2, 0, Delta::new(2, 0), 1), (0, 0));
+    }
+
+    #[test]
+    fn test_long_jumps() {
+        let space = SparseSpace::from("abcd");
+
+        assert_eq!(space.advance(0, 0, Delta::RIGHT, 3), (3, 0));
+        assert_eq!(space.advance(0, 0, Delta::RIGHT, 4), (0, 0));
+        assert_eq!(space.advance(1, 0, Delta::RIGHT, 4_000_000_001), (2, 0));
+        assert_eq!(space.advance(1, 0, Delta::RIGHT, -2), (3, 0));
+    }
+
+    #[test]
+    fn test_outside_bounds() {
+        let space = SparseSpace::from("abcd");
+
+        // Approaching the bounds from outside.
+        assert_eq!(space.advance(-5, 0, Delta::RIGHT, 1), (-4, 0));
+        assert_eq!(space.advance(-5, 0, Delta::RIGHT, 6), (1, 0));
+        // Moving away from them wraps to the far side.
+        assert_eq!(space.advance(10, 0, Delta::RIGHT, 1), (0, 0));
+        // A line that misses the bounds entirely never wraps.
+        assert_eq!(space.advance(0, 5, Delta::RIGHT, 1), (1, 5));
+        assert_eq!(space.path_length(0, 5, Delta::RIGHT), 0);
+    }
+}
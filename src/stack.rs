@@ -0,0 +1,227 @@
+/// The Funge-98 stack stack.
+///
+/// Befunge-93 programs only ever see the top of stack stack (TOSS). Popping
+/// an empty stack yields zero.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StackStack {
+    stacks: Vec<Vec<i32>>,
+}
+
+impl StackStack {
+    pub fn new() -> Self {
+        Self {
+            stacks: vec![Vec::new()],
+        }
+    }
+
+    /// A stack stack holding `stacks`, the bottom stack first. An empty
+    /// list gives a single empty stack.
+    pub fn from_vecs(stacks: Vec<Vec<i32>>) -> Self {
+        if stacks.is_empty() {
+            Self::new()
+        } else {
+            Self { stacks }
+        }
+    }
+
+    /// Every stack, the bottom stack first.
+    pub fn to_vecs(&self) -> Vec<Vec<i32>> {
+        self.stacks.clone()
+    }
+
+    pub fn toss(&self) -> &Vec<i32> {
+        self.stacks.last().expect("stack stack is never empty")
+    }
+
+    pub fn toss_mut(&mut self) -> &mut Vec<i32> {
+        self.stacks.last_mut().expect("stack stack is never empty")
+    }
+
+    fn soss_mut(&mut self) -> Option<&mut Vec<i32>> {
+        let n = self.stacks.len();
+        if n > 1 {
+            Some(&mut self.stacks[n - 2])
+        } else {
+            None
+        }
+    }
+
+    pub fn push(&mut self, value: i32) {
+        self.toss_mut().push(value);
+    }
+
+    pub fn pop(&mut self) -> i32 {
+        self.toss_mut().pop().unwrap_or_default()
+    }
+
+    /// The value `depth` places below the top of the TOSS, without popping.
+    pub fn peek(&self, depth: usize) -> i32 {
+        let toss = self.toss();
+        toss.len().checked_sub(depth + 1).map_or(0, |i| toss[i])
+    }
+
+    pub fn clear(&mut self) {
+        self.toss_mut().clear();
+    }
+
+    /// The number of stacks on the stack stack.
+    pub fn depth(&self) -> usize {
+        self.stacks.len()
+    }
+
+    /// The number of values on all the stacks together.
+    pub fn len(&self) -> usize {
+        self.stacks.iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stacks.iter().all(Vec::is_empty)
+    }
+
+    /// Sizes of each stack, from the TOSS down to the bottom stack.
+    pub fn sizes(&self) -> Vec<usize> {
+        self.stacks.iter().rev().map(Vec::len).collect()
+    }
+
+    /// `{`: pushes a new TOSS, moving `n` values onto it from the old one
+    /// and saving `offset` beneath them.
+    pub fn begin_block(&mut self, n: i32, offset: (i32, i32)) {
+        let mut toss = Vec::new();
+        let soss = self.toss_mut();
+
+        if n > 0 {
+            let n = n as usize;
+            toss.resize(n.saturating_sub(soss.len()), 0);
+            let start = soss.len().saturating_sub(n);
+            toss.extend(soss.drain(start..));
+        } else {
+            soss.resize(soss.len() + n.unsigned_abs() as usize, 0);
+        }
+        soss.push(offset.0);
+        soss.push(offset.1);
+
+        self.stacks.push(toss);
+    }
+
+    /// `}`: moves `n` values from the TOSS to the stack beneath, drops the
+    /// TOSS and returns the saved storage offset, or `None` when there is
+    /// only one stack.
+    pub fn end_block(&mut self, n: i32) -> Option<(i32, i32)> {
+        if self.stacks.len() < 2 {
+            return None;
+        }
+
+        let mut toss = self.stacks.pop().expect("stack stack is never empty");
+        let soss = self.toss_mut();
+        let y = soss.pop().unwrap_or_default();
+        let x = soss.pop().unwrap_or_default();
+
+        if n > 0 {
+            let n = n as usize;
+            soss.resize(soss.len() + n.saturating_sub(toss.len()), 0);
+            let start = toss.len().saturating_sub(n);
+            soss.extend(toss.drain(start..));
+        } else {
+            let len = soss.len().saturating_sub(n.unsigned_abs() as usize);
+            soss.truncate(len);
+        }
+
+        Some((x, y))
+    }
+
+    /// `u`: transfers `n` values one at a time from the stack beneath the
+    /// TOSS onto it, or the other way when `n` is negative. Returns `false`
+    /// when there is only one stack.
+    pub fn transfer(&mut self, n: i32) -> bool {
+        if self.stacks.len() < 2 {
+            return false;
+        }
+
+        for _i in 0..n.unsigned_abs() {
+            if n > 0 {
+                let value = self.soss_mut().and_then(Vec::pop).unwrap_or_default();
+                self.push(value);
+            } else {
+                let value = self.pop();
+                if let Some(soss) = self.soss_mut() {
+                    soss.push(value);
+                }
+            }
+        }
+
+        true
+    }
+}
+
+impl Default for StackStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stack_of(values: &[i32]) -> StackStack {
+        let mut stacks = StackStack::new();
+        for &v in values {
+            stacks.push(v);
+        }
+        stacks
+    }
+
+    #[test]
+    fn test_pop_empty() {
+        let mut stacks = StackStack::new();
+        assert_eq!(stacks.pop(), 0);
+        assert_eq!(stacks.peek(3), 0);
+    }
+
+    #[test]
+    fn test_begin_block() {
+        let mut stacks = stack_of(&[1, 2, 3]);
+        stacks.begin_block(2, (7, 8));
+        assert_eq!(stacks.toss(), &vec![2, 3]);
+        assert_eq!(stacks.sizes(), vec![2, 3]);
+
+        let mut stacks = stack_of(&[1]);
+        stacks.begin_block(3, (0, 0));
+        assert_eq!(stacks.toss(), &vec![0, 0, 1]);
+
+        let mut stacks = stack_of(&[1]);
+        stacks.begin_block(-2, (4, 5));
+        assert_eq!(stacks.toss(), &Vec::<i32>::new());
+        assert_eq!(stacks.end_block(0), Some((4, 5)));
+        assert_eq!(stacks.toss(), &vec![1, 0, 0]);
+    }
+
+    #[test]
+    fn test_end_block() {
+        let mut stacks = stack_of(&[1, 2]);
+        assert_eq!(stacks.end_block(1), None);
+
+        stacks.begin_block(0, (3, 4));
+        stacks.push(5);
+        stacks.push(6);
+        assert_eq!(stacks.end_block(1), Some((3, 4)));
+        assert_eq!(stacks.toss(), &vec![1, 2, 6]);
+
+        stacks.begin_block(0, (3, 4));
+        assert_eq!(stacks.end_block(-2), Some((3, 4)));
+        assert_eq!(stacks.toss(), &vec![1]);
+    }
+
+    #[test]
+    fn test_transfer() {
+        let mut stacks = stack_of(&[1, 2]);
+        assert!(!stacks.transfer(1));
+
+        stacks.begin_block(0, (0, 0));
+        assert!(stacks.transfer(2));
+        assert_eq!(stacks.toss(), &vec![0, 0]);
+        assert!(stacks.transfer(-1));
+        assert_eq!(stacks.toss(), &vec![0]);
+        assert_eq!(stacks.sizes(), vec![1, 3]);
+    }
+}
@@ -0,0 +1,76 @@
+//! Values reported by the Funge-98 `y` instruction.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// "BFDL", identifying befuddle to programs that query `y`.
+pub const HANDPRINT: i32 = 0x4246_444C;
+
+/// The crate version as a single number, `major * 10000 + minor * 100 + patch`.
+pub fn version() -> i32 {
+    let part = |s: &str| s.parse::<i32>().unwrap_or_default();
+
+    part(env!("CARGO_PKG_VERSION_MAJOR")) * 10000
+        + part(env!("CARGO_PKG_VERSION_MINOR")) * 100
+        + part(env!("CARGO_PKG_VERSION_PATCH"))
+}
+
+/// The current UTC date and time, packed as `y` reports them:
+/// `(year - 1900) * 65536 + month * 256 + day` and
+/// `hour * 65536 + minute * 256 + second`.
+pub fn date_and_time() -> (i32, i32) {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    let time = secs.rem_euclid(86400);
+
+    (
+        (year - 1900) * 65536 + month * 256 + day,
+        (time / 3600) as i32 * 65536 + (time / 60 % 60) as i32 * 256 + (time % 60) as i32,
+    )
+}
+
+/// Converts days since 1970-01-01 to a proleptic Gregorian date.
+fn civil_from_days(days: i64) -> (i32, i32, i32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year as i32, month as i32, day as i32)
+}
+
+/// Pushes `strings` so that they pop in order, each null terminated, with an
+/// extra null after the last one.
+pub fn push_strings<'a>(stack: &mut Vec<i32>, strings: impl DoubleEndedIterator<Item = &'a str>) {
+    stack.push(0);
+    for s in strings.rev() {
+        stack.push(0);
+        stack.extend(s.bytes().rev().map(i32::from));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+
+    #[test]
+    fn test_push_strings() {
+        let mut stack = Vec::new();
+        push_strings(&mut stack, vec!["ab", "c"].into_iter());
+        stack.reverse();
+        assert_eq!(stack, vec![97, 98, 0, 99, 0, 0]);
+    }
+}
@@ -0,0 +1,205 @@
+//! Recording every step an execution takes.
+
+use std::fmt::Debug;
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::Delta;
+
+/// One instruction executed by `BefungeExecution::step`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TraceEvent {
+    /// The number of the step, counting from 1.
+    pub step: u64,
+    /// The id of the IP that took the step.
+    pub ip: i32,
+    pub x: i32,
+    pub y: i32,
+    pub instruction: i32,
+    /// The delta the IP executed the instruction with.
+    pub delta: Delta,
+    pub string_mode: bool,
+    /// The TOSS before and after the instruction, bottom first.
+    pub stack_before: Vec<i32>,
+    pub stack_after: Vec<i32>,
+}
+
+/// Receives every step a traced execution takes.
+pub trait Tracer: Debug {
+    fn trace(&mut self, event: &TraceEvent) -> io::Result<()>;
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// How a `TraceWriter` lays out its lines.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceFormat {
+    /// One readable line per step.
+    Text,
+    /// One JSON object per line.
+    JsonLines,
+}
+
+impl TraceFormat {
+    /// JSON Lines for paths ending in `.jsonl`, text for anything else.
+    pub fn for_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(extension) if extension == "jsonl" => TraceFormat::JsonLines,
+            _ => TraceFormat::Text,
+        }
+    }
+}
+
+/// A `Tracer` writing one line per step.
+#[derive(Debug)]
+pub struct TraceWriter<W> {
+    writer: W,
+    format: TraceFormat,
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(writer: W, format: TraceFormat) -> Self {
+        Self { writer, format }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Debug> Tracer for TraceWriter<W> {
+    fn trace(&mut self, event: &TraceEvent) -> io::Result<()> {
+        match self.format {
+            TraceFormat::Text => {
+                write!(
+                    self.writer,
+                    "{} ip {} at ({}, {}) ",
+                    event.step, event.ip, event.x, event.y
+                )?;
+                match std::char::from_u32(event.instruction as u32) {
+                    Some(c) if c.is_ascii_graphic() || c == ' ' => write!(self.writer, "'{}'", c)?,
+                    _ => write!(self.writer, "{}", event.instruction)?,
+                }
+                if event.string_mode {
+                    write!(self.writer, " in string mode")?;
+                }
+                writeln!(
+                    self.writer,
+                    " delta ({}, {}) stack {:?} -> {:?}",
+                    event.delta.dx, event.delta.dy, event.stack_before, event.stack_after
+                )
+            }
+            TraceFormat::JsonLines => {
+                serde_json::to_writer(&mut self.writer, event)?;
+                writeln!(self.writer)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::{BefungeExecution, BefungeField, Dialect, MemoryIo, SparseSpace};
+
+    #[derive(Debug, Default)]
+    struct Recorder(Rc<RefCell<Vec<TraceEvent>>>);
+
+    impl Tracer for Recorder {
+        fn trace(&mut self, event: &TraceEvent) -> io::Result<()> {
+            self.0.borrow_mut().push(event.clone());
+            Ok(())
+        }
+    }
+
+    fn event() -> TraceEvent {
+        TraceEvent {
+            step: 3,
+            ip: 0,
+            x: 2,
+            y: 0,
+            instruction: b'+' as i32,
+            delta: Delta::RIGHT,
+            string_mode: false,
+            stack_before: vec![3, 4],
+            stack_after: vec![7],
+        }
+    }
+
+    #[test]
+    fn test_events() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut exec = BefungeExecution::builder(BefungeField::from_str("34+\"a\"@", 80, 1))
+            .io(MemoryIo::default())
+            .trace(Recorder(Rc::clone(&events)))
+            .build();
+        exec.run().unwrap();
+
+        let events = events.borrow();
+        assert_eq!(events.len(), 7);
+        assert_eq!(events[2], event());
+        assert!(events[4].string_mode);
+        assert_eq!(events[4].instruction, b'a' as i32);
+        assert_eq!(events[6].instruction, b'@' as i32);
+        assert_eq!(events[6].stack_after, vec![7, b'a' as i32]);
+    }
+
+    #[test]
+    fn test_funge98_ips() {
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let mut exec = BefungeExecution::builder(SparseSpace::from("t1@"))
+            .io(MemoryIo::default())
+            .dialect(Dialect::Funge98)
+            .trace(Recorder(Rc::clone(&events)))
+            .build();
+        exec.run().unwrap();
+
+        let ips = events.borrow().iter().map(|e| e.ip).collect::<Vec<_>>();
+        assert_eq!(ips, vec![0, 1, 0, 0]);
+        assert_eq!(events.borrow()[1].delta, Delta::LEFT);
+    }
+
+    #[test]
+    fn test_text() {
+        let mut writer = TraceWriter::new(Vec::new(), TraceFormat::Text);
+        writer.trace(&event()).unwrap();
+        let mut string = event();
+        string.instruction = 1;
+        string.string_mode = true;
+        writer.trace(&string).unwrap();
+
+        assert_eq!(
+            String::from_utf8(writer.into_inner()).unwrap(),
+            "3 ip 0 at (2, 0) '+' delta (1, 0) stack [3, 4] -> [7]\n\
+             3 ip 0 at (2, 0) 1 in string mode delta (1, 0) stack [3, 4] -> [7]\n"
+        );
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let mut writer = TraceWriter::new(Vec::new(), TraceFormat::JsonLines);
+        writer.trace(&event()).unwrap();
+        writer.trace(&event()).unwrap();
+
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0],
+            "{\"step\":3,\"ip\":0,\"x\":2,\"y\":0,\"instruction\":43,\
+             \"delta\":{\"dx\":1,\"dy\":0},\"string_mode\":false,\
+             \"stack_before\":[3,4],\"stack_after\":[7]}"
+        );
+    }
+}
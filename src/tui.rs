@@ -0,0 +1,652 @@
+//! The interactive debugger behind `befuddle debug`.
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::mem;
+
+use cursive::event::{Event, EventResult, Key};
+use cursive::theme::{BaseColor, Color, ColorStyle, Effect};
+use cursive::{Printer, Vec2, View};
+
+use crate::{
+    BefungeExecution, Breakpoint, Delta, FungeSpace, MemoryIo, StepOutcome, Watch, HEAT_COLORS,
+};
+
+/// Instructions executed per screen refresh while running.
+const STEPS_PER_REFRESH: usize = 1000;
+
+/// Width of the panel beside the field.
+const PANEL_WIDTH: usize = 30;
+
+/// Rows below the field for output and key help.
+const FOOTER_HEIGHT: usize = 7;
+
+/// Builds the execution a debugger starts and restarts from.
+type Load<F> = dyn Fn() -> Result<BefungeExecution<MemoryIo, F>, Box<dyn Error>>;
+
+/// Memory the undo log may take up for stepping back.
+pub const HISTORY_BUDGET: usize = 64 << 20;
+
+const HELP: &str = "s step  u back  r run  c continue  C continue back  p pause  \
+                    b breakpoint  w watch  h heatmap  x reset  arrows move  q quit";
+
+/// Where a debugged program has got to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DebugStatus {
+    /// The program can take another step.
+    Ready,
+    /// Continuing stopped before this breakpoint.
+    Breakpoint(Breakpoint),
+    /// The last step changed a watched cell.
+    Watchpoint(Watch),
+    Halted,
+    /// The program ended with Funge-98 `q` and this exit code.
+    Quit(i32),
+    /// The program stopped on this error.
+    Failed(String),
+}
+
+impl DebugStatus {
+    /// Whether the program has ended and cannot step any further.
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            DebugStatus::Halted | DebugStatus::Quit(_) | DebugStatus::Failed(_)
+        )
+    }
+}
+
+/// An execution under the debugger's control.
+///
+/// The execution writes to memory so its output can be shown beside the
+/// field, and is rebuilt from scratch on reset. Build it with a `history`
+/// to be able to step back.
+pub struct Debugger<F> {
+    exec: BefungeExecution<MemoryIo, F>,
+    load: Box<Load<F>>,
+    status: DebugStatus,
+}
+
+impl<F: FungeSpace> Debugger<F> {
+    /// A debugger for the execution `load` builds, which it calls again on
+    /// every reset. Fails if the first load does.
+    pub fn new(
+        load: impl Fn() -> Result<BefungeExecution<MemoryIo, F>, Box<dyn Error>> + 'static,
+    ) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            exec: load()?,
+            load: Box::new(load),
+            status: DebugStatus::Ready,
+        })
+    }
+
+    pub fn execution(&self) -> &BefungeExecution<MemoryIo, F> {
+        &self.exec
+    }
+
+    pub fn status(&self) -> &DebugStatus {
+        &self.status
+    }
+
+    /// The number of steps taken since the last reset.
+    pub fn steps(&self) -> u64 {
+        self.exec.steps()
+    }
+
+    pub fn is_breakpoint(&self, x: i32, y: i32) -> bool {
+        self.exec
+            .breakpoints()
+            .contains(&Breakpoint::Position(x, y))
+    }
+
+    /// Sets or clears the breakpoint at `(x, y)`, returning whether it is
+    /// now set.
+    pub fn toggle_breakpoint(&mut self, x: i32, y: i32) -> bool {
+        let breakpoint = Breakpoint::Position(x, y);
+        if self.exec.remove_breakpoint(breakpoint) {
+            false
+        } else {
+            self.exec.add_breakpoint(breakpoint);
+            true
+        }
+    }
+
+    pub fn is_watched(&self, x: i32, y: i32) -> bool {
+        self.exec.watchpoints().any(|w| w == (x, y))
+    }
+
+    /// Starts or stops watching `(x, y)`, returning whether it is now
+    /// watched.
+    pub fn toggle_watch(&mut self, x: i32, y: i32) -> bool {
+        if self.exec.unwatch(x, y) {
+            false
+        } else {
+            self.exec.watch(x, y);
+            true
+        }
+    }
+
+    /// Executes one instruction, returning whether the program can go on.
+    pub fn step(&mut self) -> bool {
+        if self.status.is_finished() {
+            return false;
+        }
+
+        self.status = match self.exec.step() {
+            // Only `run` stops at limits.
+            Ok(StepOutcome::Continue) | Ok(StepOutcome::LimitExceeded(_)) => DebugStatus::Ready,
+            Ok(StepOutcome::Breakpoint(breakpoint)) => DebugStatus::Breakpoint(breakpoint),
+            Ok(StepOutcome::Watchpoint(watch)) => DebugStatus::Watchpoint(watch),
+            Ok(StepOutcome::Halted) => DebugStatus::Halted,
+            Ok(StepOutcome::Quit(code)) => DebugStatus::Quit(code),
+            Err(e) => DebugStatus::Failed(e.to_string()),
+        };
+        !self.status.is_finished()
+    }
+
+    /// Executes up to `limit` instructions, stopping early when the program
+    /// ends or, if `breakpoints` is set, when it reaches a breakpoint or
+    /// changes a watched cell. The breakpoint the program starts on does not
+    /// count. Returns whether it stopped early.
+    pub fn run(&mut self, limit: usize, breakpoints: bool) -> bool {
+        for _ in 0..limit {
+            if !self.step() {
+                return true;
+            }
+            if !breakpoints {
+                continue;
+            }
+            if let DebugStatus::Watchpoint(_) = self.status {
+                return true;
+            }
+            if let Some(breakpoint) = self.exec.hit_breakpoint() {
+                self.status = DebugStatus::Breakpoint(breakpoint);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Rewinds one step, returning `false` if there is no history left.
+    pub fn step_back(&mut self) -> bool {
+        if !self.exec.step_back() {
+            return false;
+        }
+
+        self.status = DebugStatus::Ready;
+        true
+    }
+
+    /// Steps back to the last breakpoint or watched change, or as far as the
+    /// history goes.
+    pub fn run_back(&mut self) {
+        self.status = match self.exec.run_back_to_breakpoint() {
+            Some(StepOutcome::Breakpoint(breakpoint)) => DebugStatus::Breakpoint(breakpoint),
+            Some(StepOutcome::Watchpoint(watch)) => DebugStatus::Watchpoint(watch),
+            _ => DebugStatus::Ready,
+        };
+    }
+
+    /// The output written so far, leaving out what stepping back undid.
+    pub fn output(&self) -> String {
+        let mut output = self.exec.io().output_string();
+        let rewound = self.exec.rewound_output().len();
+        output.truncate(output.len().saturating_sub(rewound));
+        output
+    }
+
+    /// Starts the program over, keeping the breakpoints and watchpoints.
+    ///
+    /// If the execution cannot be loaded again, the old one stays and the
+    /// status says why.
+    pub fn reset(&mut self) {
+        let exec = match (self.load)() {
+            Ok(exec) => exec,
+            Err(e) => {
+                self.status = DebugStatus::Failed(format!("reset failed: {}", e));
+                return;
+            }
+        };
+        let old = mem::replace(&mut self.exec, exec);
+        for &breakpoint in old.breakpoints() {
+            self.exec.add_breakpoint(breakpoint);
+        }
+        for (x, y) in old.watchpoints() {
+            self.exec.watch(x, y);
+        }
+        self.status = DebugStatus::Ready;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Mode {
+    Paused,
+    Running,
+    /// Running until a breakpoint.
+    Continuing,
+}
+
+/// Draws a `Debugger` and drives it from the keyboard.
+struct DebuggerView<F> {
+    debugger: Debugger<F>,
+    mode: Mode,
+    /// The cell breakpoints are toggled on.
+    cursor: (i32, i32),
+    /// The field position drawn in the top left corner.
+    scroll: (i32, i32),
+    /// The number of field cells that fit on screen.
+    viewport: (i32, i32),
+    /// Whether cells are coloured by how often they ran.
+    heatmap: bool,
+}
+
+/// How `value` is drawn in the field.
+fn glyph(value: i32) -> char {
+    match value {
+        0x20..=0x7e => value as u8 as char,
+        _ => '·',
+    }
+}
+
+fn arrow(delta: Delta) -> &'static str {
+    match (delta.dx, delta.dy) {
+        (1, 0) => "→",
+        (-1, 0) => "←",
+        (0, 1) => "↓",
+        (0, -1) => "↑",
+        _ => "",
+    }
+}
+
+/// The smallest offset that brings `p` into a window of `len` cells
+/// starting at `start`.
+fn follow(start: i32, len: i32, p: i32) -> i32 {
+    if p < start {
+        p
+    } else if p >= start.saturating_add(len) {
+        p.saturating_sub(len - 1)
+    } else {
+        start
+    }
+}
+
+impl<F: FungeSpace> DebuggerView<F> {
+    fn new(debugger: Debugger<F>) -> Self {
+        let bounds = debugger.execution().field().bounds();
+        let cursor = debugger.execution().ip().position();
+        Self {
+            debugger,
+            mode: Mode::Paused,
+            cursor,
+            scroll: (bounds.min_x, bounds.min_y),
+            viewport: (1, 1),
+            heatmap: false,
+        }
+    }
+
+    fn scroll_to(&mut self, (x, y): (i32, i32)) {
+        self.scroll = (
+            follow(self.scroll.0, self.viewport.0, x),
+            follow(self.scroll.1, self.viewport.1, y),
+        );
+    }
+
+    /// Moves the cursor to the next IP and scrolls it into view.
+    fn follow_ip(&mut self) {
+        self.cursor = self.debugger.execution().ip().position();
+        self.scroll_to(self.cursor);
+    }
+
+    fn move_cursor(&mut self, dx: i32, dy: i32) {
+        let (x, y) = self.cursor;
+        let field = self.debugger.execution().field();
+        let (x, y) = (x.wrapping_add(dx), y.wrapping_add(dy));
+        if field.contains(x, y) {
+            self.cursor = (x, y);
+            self.scroll_to(self.cursor);
+        }
+    }
+
+    fn status_text(&self) -> String {
+        match (self.debugger.status(), self.mode) {
+            (_, Mode::Running) => String::from("running"),
+            (_, Mode::Continuing) => String::from("continuing"),
+            (DebugStatus::Ready, Mode::Paused) => String::from("paused"),
+            (DebugStatus::Breakpoint(breakpoint), _) => format!("at {}", breakpoint),
+            (DebugStatus::Watchpoint(watch), _) => format!("{}", watch),
+            (DebugStatus::Halted, _) => String::from("halted"),
+            (DebugStatus::Quit(code), _) => format!("quit with {}", code),
+            (DebugStatus::Failed(e), _) => format!("error: {}", e),
+        }
+    }
+
+    fn draw_field(&self, printer: &Printer) {
+        let exec = self.debugger.execution();
+        let field = exec.field();
+        let current = exec.ip().position();
+        let ips: HashSet<(i32, i32)> = exec.ips().iter().map(|ip| ip.position()).collect();
+        let breakpoint =
+            ColorStyle::new(Color::Light(BaseColor::White), Color::Dark(BaseColor::Red));
+        let watched = ColorStyle::new(
+            Color::Dark(BaseColor::Black),
+            Color::Dark(BaseColor::Yellow),
+        );
+        let profile = exec.profile().filter(|_| self.heatmap);
+
+        for row in 0..self.viewport.1 {
+            for col in 0..self.viewport.0 {
+                let x = self.scroll.0.wrapping_add(col);
+                let y = self.scroll.1.wrapping_add(row);
+                if !field.contains(x, y) {
+                    continue;
+                }
+
+                let mut style = ColorStyle::primary();
+                if self.debugger.is_breakpoint(x, y) {
+                    style = breakpoint;
+                } else if self.debugger.is_watched(x, y) {
+                    style = watched;
+                } else if (x, y) != current && ips.contains(&(x, y)) {
+                    style = ColorStyle::highlight_inactive();
+                } else if let Some(level) = profile.and_then(|p| p.heat(x, y)) {
+                    style = ColorStyle::new(
+                        Color::Light(BaseColor::White),
+                        Color::from_256colors(HEAT_COLORS[level]),
+                    );
+                }
+                let effect = if (x, y) == current {
+                    Effect::Reverse
+                } else if (x, y) == self.cursor {
+                    Effect::Underline
+                } else {
+                    Effect::Simple
+                };
+
+                let text = glyph(field.cell(x, y)).to_string();
+                printer.with_color(style, |printer| {
+                    printer.with_effect(effect, |printer| {
+                        printer.print((col as usize, row as usize), &text);
+                    });
+                });
+            }
+        }
+    }
+
+    fn draw_panel(&self, printer: &Printer) {
+        let exec = self.debugger.execution();
+        let ip = exec.ip();
+        let (x, y) = ip.position();
+        let delta = ip.delta();
+        let (cx, cy) = self.cursor;
+        let under = exec.field().cell(cx, cy);
+
+        let mut lines = vec![
+            format!("{:?}", exec.dialect()),
+            format!("Status   {}", self.status_text()),
+            format!("Steps    {}", self.debugger.steps()),
+            format!("IP       {} of {}", ip.id(), exec.ips().len()),
+            format!("Position ({}, {})", x, y),
+            format!("Delta    ({}, {}) {}", delta.dx, delta.dy, arrow(delta)),
+            format!("String   {}", if ip.string_mode() { "on" } else { "off" }),
+            format!("Cursor   ({}, {}) = {}", cx, cy, under),
+        ];
+        if let Some(profile) = exec.profile() {
+            lines.push(format!(
+                "Ran      {}, written {}",
+                profile.executions(cx, cy),
+                profile.writes(cx, cy)
+            ));
+        }
+        lines.extend(vec![String::new(), String::from("Stack (top first)")]);
+        lines.extend(ip.stack().iter().rev().map(|v| match *v {
+            0x21..=0x7e => format!("  {} '{}'", v, *v as u8 as char),
+            _ => format!("  {}", v),
+        }));
+
+        for (row, line) in lines.iter().take(printer.size.y).enumerate() {
+            printer.print((0, row), line);
+        }
+    }
+
+    fn draw_footer(&self, printer: &Printer) {
+        printer.print_hline((0, 0), printer.size.x, "─");
+        printer.print((1, 0), " Output ");
+
+        let output = self.debugger.output();
+        let rows = printer.size.y.saturating_sub(2);
+        let lines: Vec<&str> = output.split('\n').collect();
+        let shown = &lines[lines.len().saturating_sub(rows)..];
+        for (row, line) in shown.iter().enumerate() {
+            printer.print((0, row + 1), line);
+        }
+
+        printer.with_color(ColorStyle::secondary(), |printer| {
+            printer.print((0, printer.size.y.saturating_sub(1)), HELP);
+        });
+    }
+}
+
+impl<F: FungeSpace + 'static> View for DebuggerView<F> {
+    fn draw(&self, printer: &Printer) {
+        let field_width = printer.size.x.saturating_sub(PANEL_WIDTH + 1);
+        let field_height = printer.size.y.saturating_sub(FOOTER_HEIGHT);
+
+        self.draw_field(&printer.cropped((field_width, field_height)));
+        for row in 0..field_height {
+            printer.print((field_width, row), "│");
+        }
+        self.draw_panel(
+            &printer
+                .offset((field_width + 1, 0))
+                .cropped((PANEL_WIDTH, field_height)),
+        );
+        self.draw_footer(&printer.offset((0, field_height)));
+    }
+
+    fn layout(&mut self, size: Vec2) {
+        let width = size.x.saturating_sub(PANEL_WIDTH + 1).max(1);
+        let height = size.y.saturating_sub(FOOTER_HEIGHT).max(1);
+        self.viewport = (width as i32, height as i32);
+        self.scroll_to(self.cursor);
+    }
+
+    fn required_size(&mut self, constraint: Vec2) -> Vec2 {
+        constraint
+    }
+
+    fn take_focus(&mut self, _: cursive::direction::Direction) -> bool {
+        true
+    }
+
+    fn on_event(&mut self, event: Event) -> EventResult {
+        match event {
+            Event::Char('s') | Event::Char(' ') => {
+                self.mode = Mode::Paused;
+                self.debugger.step();
+                self.follow_ip();
+            }
+            Event::Char('r') => self.mode = Mode::Running,
+            Event::Char('c') => self.mode = Mode::Continuing,
+            Event::Char('p') => self.mode = Mode::Paused,
+            Event::Char('b') => {
+                let (x, y) = self.cursor;
+                self.debugger.toggle_breakpoint(x, y);
+            }
+            Event::Char('h') => self.heatmap = !self.heatmap,
+            Event::Char('w') => {
+                let (x, y) = self.cursor;
+                self.debugger.toggle_watch(x, y);
+            }
+            Event::Char('u') => {
+                self.mode = Mode::Paused;
+                self.debugger.step_back();
+                self.follow_ip();
+            }
+            Event::Char('C') => {
+                self.mode = Mode::Paused;
+                self.debugger.run_back();
+                self.follow_ip();
+            }
+            Event::Char('x') => {
+                self.mode = Mode::Paused;
+                self.debugger.reset();
+                self.follow_ip();
+            }
+            Event::Key(Key::Left) => self.move_cursor(-1, 0),
+            Event::Key(Key::Right) => self.move_cursor(1, 0),
+            Event::Key(Key::Up) => self.move_cursor(0, -1),
+            Event::Key(Key::Down) => self.move_cursor(0, 1),
+            Event::Refresh if self.mode != Mode::Paused => {
+                let breakpoints = self.mode == Mode::Continuing;
+                if self.debugger.run(STEPS_PER_REFRESH, breakpoints) {
+                    self.mode = Mode::Paused;
+                }
+                self.follow_ip();
+            }
+            _ => return EventResult::Ignored,
+        }
+        EventResult::Consumed(None)
+    }
+}
+
+/// Shows `debugger` in the terminal until the user quits.
+pub fn debug<F: FungeSpace + 'static>(debugger: Debugger<F>) -> Result<(), Box<dyn Error>> {
+    let mut siv = cursive::crossterm();
+    siv.set_fps(30);
+    siv.add_global_callback('q', |s| s.quit());
+    siv.add_fullscreen_layer(DebuggerView::new(debugger));
+    siv.try_run()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BefungeField;
+    use std::cell::Cell;
+
+    fn debugger(program: &'static str) -> Debugger<BefungeField> {
+        Debugger::new(move || {
+            Ok(
+                BefungeExecution::builder(BefungeField::from_str(program, 80, 1))
+                    .io(MemoryIo::default())
+                    .history(HISTORY_BUDGET)
+                    .build(),
+            )
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_step_and_reset() {
+        let mut debugger = debugger("12+.@");
+        assert!(debugger.step());
+        assert!(debugger.step());
+        assert_eq!(debugger.execution().stack(), vec![1, 2]);
+        assert!(debugger.run(10, false));
+        assert_eq!(debugger.status(), &DebugStatus::Halted);
+        assert_eq!(debugger.execution().io().output_string(), "3 ");
+        assert!(!debugger.step());
+
+        debugger.reset();
+        assert_eq!(debugger.status(), &DebugStatus::Ready);
+        assert_eq!(debugger.steps(), 0);
+        assert!(debugger.execution().stack().is_empty());
+    }
+
+    #[test]
+    fn test_failed_reset() {
+        let loads = Cell::new(0);
+        let mut debugger = Debugger::new(move || {
+            if loads.replace(loads.get() + 1) > 0 {
+                return Err("trace file gone".into());
+            }
+            Ok(
+                BefungeExecution::builder(BefungeField::from_str("12@", 80, 1))
+                    .io(MemoryIo::default())
+                    .build(),
+            )
+        })
+        .unwrap();
+        assert!(debugger.step());
+
+        debugger.reset();
+        assert_eq!(
+            debugger.status(),
+            &DebugStatus::Failed(String::from("reset failed: trace file gone"))
+        );
+        assert_eq!(debugger.steps(), 1);
+        assert_eq!(debugger.execution().stack(), vec![1]);
+    }
+
+    #[test]
+    fn test_breakpoints() {
+        let mut debugger = debugger("1234@");
+        assert!(debugger.toggle_breakpoint(2, 0));
+        assert!(debugger.run(100, true));
+        assert_eq!(
+            debugger.status(),
+            &DebugStatus::Breakpoint(Breakpoint::Position(2, 0))
+        );
+        assert_eq!(debugger.execution().stack(), vec![1, 2]);
+
+        // Continuing leaves the breakpoint it stopped on.
+        assert!(debugger.run(100, true));
+        assert_eq!(debugger.status(), &DebugStatus::Halted);
+
+        debugger.reset();
+        assert!(!debugger.toggle_breakpoint(2, 0));
+        assert!(!debugger.run(3, true));
+        assert_eq!(debugger.execution().stack(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_watch_survives_reset() {
+        let mut debugger = debugger("77*00p@");
+        assert!(debugger.toggle_watch(0, 0));
+        debugger.reset();
+        assert!(debugger.is_watched(0, 0));
+        assert!(debugger.run(100, true));
+        assert_eq!(
+            debugger.status(),
+            &DebugStatus::Watchpoint(Watch {
+                x: 0,
+                y: 0,
+                old: b'7' as i32,
+                new: 49,
+            })
+        );
+        assert!(debugger.run(100, true));
+        assert_eq!(debugger.status(), &DebugStatus::Halted);
+    }
+
+    #[test]
+    fn test_step_back() {
+        let mut debugger = debugger("1.2.3.@");
+        assert!(debugger.run(100, false));
+        assert_eq!(debugger.output(), "1 2 3 ");
+
+        assert!(debugger.step_back());
+        assert!(debugger.step_back());
+        assert_eq!(debugger.status(), &DebugStatus::Ready);
+        assert_eq!(debugger.steps(), 5);
+        assert_eq!(debugger.output(), "1 2 ");
+
+        debugger.toggle_breakpoint(2, 0);
+        debugger.run_back();
+        assert_eq!(
+            debugger.status(),
+            &DebugStatus::Breakpoint(Breakpoint::Position(2, 0))
+        );
+        assert_eq!(debugger.output(), "1 ");
+        debugger.run_back();
+        assert_eq!(debugger.steps(), 0);
+        assert!(!debugger.step_back());
+    }
+
+    #[test]
+    fn test_failure() {
+        let mut debugger = debugger("00/@");
+        debugger.run(10, false);
+        assert!(matches!(debugger.status(), DebugStatus::Failed(_)));
+    }
+}
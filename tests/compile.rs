@@ -0,0 +1,125 @@
+//! Builds compiled programs with `rustc` and checks they print what the
+//! interpreter does.
+//!
+//! `rustc` is taken from `RUSTC`, as Cargo sets it, or from the path.
+//! Without it these tests fail rather than pass without checking anything.
+
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use befuddle::compile::{self, CompileSettings};
+use befuddle::{BefungeExecution, BefungeField, MemoryIo};
+
+const SEED: u64 = 3;
+
+/// Programs and the input they are given.
+const PROGRAMS: &[(&str, &str, &str)] = &[
+    ("hello", "\"!dlroW ,olleH\">:#,_@", ""),
+    // Reads two numbers and prints their quotient and remainder.
+    ("divide", "&&\\:01p\\:01g/.01g%.@", "17 5"),
+    // A string read backwards, reached across the top and left edges.
+    ("edges", "^\n\n<@,,,,,\"hello\"", ""),
+    // Writes its counter as a digit into the loop it runs and prints it
+    // from there, so it has to fall back to interpreting.
+    ("rewrite", "0>1+:5`#@_:\"0\"+91pv\n ^      ,0        <", ""),
+    // Wanders at random until it falls into the `@`.
+    ("wander", "v\n>?<\n ?@\n >^", ""),
+    // Division by zero.
+    ("zero", "12-0/@", ""),
+    // `g` off the field.
+    ("outside", "99g@", ""),
+    // Echoes its input, ending at end of input.
+    ("echo", "~:1+!#@_,", "echo\n"),
+];
+
+fn rustc() -> String {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| String::from("rustc"));
+    match Command::new(&rustc).arg("--version").output() {
+        Ok(output) if output.status.success() => rustc,
+        Ok(output) => panic!(
+            "{} --version failed, so compiled programs cannot be checked: {}",
+            rustc,
+            String::from_utf8_lossy(&output.stderr)
+        ),
+        Err(e) => panic!(
+            "{} is not available, so compiled programs cannot be checked: {}",
+            rustc, e
+        ),
+    }
+}
+
+/// What `befuddle` prints and exits with for `source`.
+fn interpret(name: &str, source: &str, input: &str) -> (Vec<u8>, String, i32) {
+    let field = BefungeField::<u8>::from_source(source, 80, 25);
+    let mut exec = BefungeExecution::builder(field)
+        .io(MemoryIo::from_input(input))
+        .seed(SEED)
+        .build();
+    let result = exec.run();
+    let mut output = exec.io().output().to_vec();
+    output.push(b'\n');
+    match result {
+        Ok(_) => (output, String::new(), 0),
+        Err(e) => (output, format!("{}: {}\n", name, e), 1),
+    }
+}
+
+#[test]
+fn test_matches_interpreter() {
+    let rustc = rustc();
+    let dir: PathBuf = env::temp_dir().join(format!("befuddle-compile-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    for &(name, source, input) in PROGRAMS {
+        let field = BefungeField::<u8>::from_source(source, 80, 25);
+        let settings = CompileSettings {
+            name: String::from(name),
+            seed: Some(SEED),
+        };
+        let compiled = compile::compile(&field, &settings);
+        let program = dir.join(format!("{}.rs", name));
+        let binary = dir.join(name);
+        fs::write(&program, &compiled.source).unwrap();
+
+        let built = Command::new(&rustc)
+            .args(["--edition", "2018", "-o"])
+            .arg(&binary)
+            .arg(&program)
+            .output()
+            .unwrap();
+        assert!(
+            built.status.success(),
+            "{} did not build:\n{}",
+            name,
+            String::from_utf8_lossy(&built.stderr)
+        );
+
+        let mut child = Command::new(&binary)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        let ran = child.wait_with_output().unwrap();
+
+        let (output, errors, code) = interpret(name, source, input);
+        assert_eq!(
+            String::from_utf8_lossy(&ran.stdout),
+            String::from_utf8_lossy(&output),
+            "{} printed something else",
+            name
+        );
+        assert_eq!(String::from_utf8_lossy(&ran.stderr), errors, "{}", name);
+        assert_eq!(ran.status.code(), Some(code), "{}", name);
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,143 @@
+//! Runs the Mycology test suite and summarizes how much of it befuddle
+//! passes.
+//!
+//! Mycology reports each behaviour it checks on a line starting with
+//! `GOOD`, `BAD` or `UNDEF`. The suite belongs in `tests/mycology`, with
+//! its licence, but is not vendored yet, so the tests that run it are
+//! ignored. Run them with `--ignored --nocapture` once it is there.
+
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+use befuddle::{
+    BefungeExecution, BefungeField, Dialect, FungeSpace, Limits, MemoryIo, SparseSpace, StepOutcome,
+};
+
+/// The `BAD` lines befuddle is known to get as Befunge-93. A change to
+/// these is a change in conformance, for better or worse. Not yet checked
+/// against a run.
+const KNOWN_BAD_BEFUNGE93: &[&str] = &[];
+
+/// The `BAD` lines befuddle is known to get as Funge-98. Not yet checked
+/// against a run.
+const KNOWN_BAD_FUNGE98: &[&str] = &[];
+
+/// Far more steps than Mycology needs, so a program stuck in a loop still
+/// fails.
+const MAX_STEPS: u64 = 50_000_000;
+
+/// The results Mycology printed.
+#[derive(Debug, Default, PartialEq)]
+struct Summary {
+    good: usize,
+    bad: Vec<String>,
+    undef: usize,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} good, {} bad, {} undefined",
+            self.good,
+            self.bad.len(),
+            self.undef
+        )?;
+        for line in &self.bad {
+            write!(f, "\n  {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+fn summarize(output: &str) -> Summary {
+    let mut summary = Summary::default();
+    for line in output.lines().map(str::trim) {
+        if line.starts_with("GOOD") {
+            summary.good += 1;
+        } else if line.starts_with("BAD") {
+            summary.bad.push(String::from(line));
+        } else if line.starts_with("UNDEF") {
+            summary.undef += 1;
+        }
+    }
+    summary
+}
+
+/// Runs `mycology.b98` as `dialect`.
+fn mycology(dialect: Dialect) -> Summary {
+    let path: PathBuf = [
+        env!("CARGO_MANIFEST_DIR"),
+        "tests",
+        "mycology",
+        "mycology.b98",
+    ]
+    .iter()
+    .collect();
+    let source = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("{}: {}; see tests/mycology/README.md", path.display(), e));
+
+    // The same spaces and cell types the command line picks.
+    let space: Box<dyn FungeSpace> = match dialect {
+        Dialect::Funge98 => Box::new(SparseSpace::<i32>::from_source(&source)),
+        _ => Box::new(BefungeField::<u8>::from_source(&source, 80, 25)),
+    };
+    let mut exec = BefungeExecution::builder(space)
+        .io(MemoryIo::default())
+        .dialect(dialect)
+        .args(vec![path.display().to_string()])
+        .seed(0)
+        .limits(Limits::default().steps(MAX_STEPS))
+        .build();
+
+    let outcome = exec.run();
+    let output = exec.io().output_string();
+    match outcome {
+        Ok(StepOutcome::LimitExceeded(limit)) => {
+            panic!("Mycology hit the {}, output:\n{}", limit, output)
+        }
+        Err(e) => panic!("Mycology failed: {}, output:\n{}", e, output),
+        Ok(_) => {}
+    }
+
+    let summary = summarize(&output);
+    println!("Mycology as {:?}: {}", dialect, summary);
+    summary
+}
+
+#[test]
+#[ignore = "needs tests/mycology/mycology.b98, which is not vendored yet"]
+fn test_befunge93() {
+    let summary = mycology(Dialect::Befunge93);
+    assert!(summary.good > 0, "no GOOD lines");
+    assert_eq!(summary.bad, KNOWN_BAD_BEFUNGE93, "{}", summary);
+}
+
+#[test]
+#[ignore = "needs tests/mycology/mycology.b98, which is not vendored yet"]
+fn test_funge98() {
+    let summary = mycology(Dialect::Funge98);
+    assert!(summary.good > 0, "no GOOD lines");
+    assert_eq!(summary.bad, KNOWN_BAD_FUNGE98, "{}", summary);
+}
+
+#[test]
+fn test_summarize() {
+    let summary = summarize(
+        "0 1 2 3 \nGOOD: , works\nBAD: 0! != 1\nUNDEF: # across the edge skips\n\
+         GOOD: : duplicates\nThe Befunge-93 tests are done.\n",
+    );
+    assert_eq!(
+        summary,
+        Summary {
+            good: 2,
+            bad: vec![String::from("BAD: 0! != 1")],
+            undef: 1,
+        }
+    );
+    assert_eq!(
+        summary.to_string(),
+        "2 good, 1 bad, 1 undefined\n  BAD: 0! != 1"
+    );
+}